@@ -0,0 +1,320 @@
+use crate::commit::{self, Commit, RevRange};
+use crate::init::{RUC_DIR, WORKING_DIR};
+use crate::object;
+use crate::oid::Oid;
+use crate::tree;
+
+use anyhow::{bail, Context, Result};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+// File inside of `.ruc` with the place where HEAD was before starting to
+// bisect: either the full name of a branch or a commit ID. The commits being
+// marked are kept as references under `refs/bisect/`: "bad" for the bad one,
+// and "good-<id>" or "skip-<id>" for the rest.
+const BISECT_START: &str = "BISECT_START";
+const BISECT_REFS: &str = "refs/bisect/";
+
+// Where the search is at after marking commits.
+enum Status {
+    // Still missing a good or a bad commit.
+    Waiting,
+    // The given commit has been checked out for testing, and roughly the given
+    // number of commits are left to test after it.
+    Testing(Oid, usize),
+    // The given commit is the first bad one.
+    Found(Oid),
+    // Only skipped commits are left, and any of them could be the first bad
+    // one.
+    OnlySkipped(Vec<Oid>),
+}
+
+fn start_file() -> PathBuf {
+    WORKING_DIR.join(RUC_DIR).join(BISECT_START)
+}
+
+fn ensure_in_progress() -> Result<()> {
+    if !start_file().is_file() {
+        bail!("not bisecting, use 'ruc bisect start' first");
+    }
+    Ok(())
+}
+
+// Starts bisecting, marking the given commits as bad and good if given.
+pub fn start(bad: Option<&String>, good: &[String]) -> Result<()> {
+    if start_file().is_file() {
+        bail!("already bisecting, use 'ruc bisect reset' first");
+    }
+
+    let head = commit::head_commit()?;
+    commit::ensure_clean(&head, "bisect")?;
+
+    let original = match commit::symbolic_ref("HEAD")? {
+        Some(name) => name,
+        None => head.id.to_string(),
+    };
+    fs::write(start_file(), format!("{}\n", original)).context("could not start bisecting")?;
+
+    if let Some(bad) = bad {
+        mark("bad", &commit::ref_to_oid(bad)?)?;
+    }
+    for rev in good {
+        mark("good", &commit::ref_to_oid(rev)?)?;
+    }
+
+    report(next()?)
+}
+
+// Stores the given mark (i.e. "good", "bad" or "skip") for the given commit.
+fn mark(kind: &str, id: &Oid) -> Result<()> {
+    let id = commit::get_commit(id)?.id;
+
+    match kind {
+        "bad" => commit::update_ref(&format!("{}bad", BISECT_REFS), &id),
+        _ => commit::update_ref(&format!("{}{}-{}", BISECT_REFS, kind, id), &id),
+    }
+}
+
+// Marks the given revision (or HEAD) with the given kind (i.e. "good", "bad"
+// or "skip"), and moves on to the next commit to test.
+pub fn mark_and_continue(kind: &str, rev: Option<&String>) -> Result<()> {
+    ensure_in_progress()?;
+
+    let id = match rev {
+        Some(rev) => commit::ref_to_oid(rev)?,
+        None => commit::head_commit()?.id,
+    };
+    mark(kind, &id)?;
+
+    report(next()?)
+}
+
+// Picks the next commit to test given the commits marked so far, and checks
+// it out. The candidates are the commits reachable from the bad one but not
+// from good ones, and the best one to test is the one that splits them in
+// halves as evenly as possible: the one with the number of candidates it
+// reaches being closest to half of them. This works for any shape of history,
+// including merges.
+fn next() -> Result<Status> {
+    let refs = commit::list_refs(BISECT_REFS)?;
+    let bad = match refs.iter().find(|(name, _)| name.ends_with("/bad")) {
+        Some((_, id)) => *id,
+        None => return Ok(Status::Waiting),
+    };
+    let marked = |kind: &str| {
+        refs.iter()
+            .filter(|(name, _)| name.starts_with(&format!("{}{}-", BISECT_REFS, kind)))
+            .map(|(_, id)| *id)
+            .collect::<Vec<_>>()
+    };
+    let (good, skipped) = (marked("good"), marked("skip"));
+    if good.is_empty() {
+        return Ok(Status::Waiting);
+    }
+
+    let range = RevRange {
+        include: vec![(String::new(), bad)],
+        exclude: good,
+    };
+    let candidates = commit::rev_list(&range)?;
+    let skipped = skipped.into_iter().collect::<HashSet<_>>();
+
+    let testable = candidates
+        .iter()
+        .filter(|c| c.id != bad && !skipped.contains(&c.id))
+        .collect::<Vec<_>>();
+    if testable.is_empty() {
+        let left = candidates
+            .iter()
+            .filter(|c| c.id != bad)
+            .map(|c| c.id)
+            .collect::<Vec<_>>();
+        if left.is_empty() {
+            return Ok(Status::Found(bad));
+        }
+        return Ok(Status::OnlySkipped(left.into_iter().chain([bad]).collect()));
+    }
+
+    let reached = reached_counts(&candidates);
+    let total = candidates.len();
+    let best = testable
+        .iter()
+        .max_by_key(|c| {
+            let count = reached[&c.id];
+            count.min(total - count)
+        })
+        .unwrap();
+
+    checkout(&best.id)?;
+    Ok(Status::Testing(best.id, total / 2))
+}
+
+// Returns, for each of the given commits, how many of them it can reach
+// (including itself). Commits come in topological order, so walking them in
+// reverse means that parents are always handled before their children. Like
+// Git, a commit with a single parent reaches one more commit than its parent,
+// so a linear history is counted in one pass. Only merges need walking their
+// ancestry, which reuses the same seen-set by marking it with the position of
+// the merge being counted instead of clearing it.
+fn reached_counts(commits: &[Commit]) -> HashMap<Oid, usize> {
+    let index = commits
+        .iter()
+        .enumerate()
+        .map(|(idx, c)| (c.id, idx))
+        .collect::<HashMap<_, _>>();
+    let parents = commits
+        .iter()
+        .map(|c| {
+            c.parents
+                .iter()
+                .filter_map(|p| index.get(p).copied())
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    let mut counts = vec![0; commits.len()];
+    let mut seen = vec![usize::MAX; commits.len()];
+    let mut pending = vec![];
+
+    for idx in (0..commits.len()).rev() {
+        counts[idx] = match parents[idx].as_slice() {
+            [] => 1,
+            [parent] => counts[*parent] + 1,
+            _ => {
+                let mut count = 0;
+                seen[idx] = idx;
+                pending.push(idx);
+                while let Some(next) = pending.pop() {
+                    count += 1;
+                    for &parent in &parents[next] {
+                        if seen[parent] != idx {
+                            seen[parent] = idx;
+                            pending.push(parent);
+                        }
+                    }
+                }
+                count
+            }
+        };
+    }
+
+    commits
+        .iter()
+        .zip(counts)
+        .map(|(c, count)| (c.id, count))
+        .collect()
+}
+
+// Detaches HEAD into the given commit, updating the working directory.
+fn checkout(id: &Oid) -> Result<()> {
+    let store = object::store();
+    let head = commit::head_commit()?;
+    let target = commit::get_commit(id)?;
+
+    tree::update_working_dir(
+        &tree::flatten(store.as_ref(), &head.tree)?,
+        &tree::flatten(store.as_ref(), &target.tree)?,
+    )?;
+    commit::detach_head(&target.id)
+}
+
+// Prints the given status, returning an error if bisecting cannot go on.
+fn report(status: Status) -> Result<()> {
+    match status {
+        Status::Waiting => println!("Waiting for both good and bad commits"),
+        Status::Testing(id, left) => {
+            let steps = usize::BITS - left.leading_zeros();
+
+            println!(
+                "Bisecting: {} revisions left to test after this (roughly {} steps)",
+                left, steps
+            );
+            println!("[{}] {}", id, commit::get_commit(&id)?.subject());
+        }
+        Status::Found(id) => {
+            println!("{} is the first bad commit", id);
+            commit::show(&id.to_string())?;
+        }
+        Status::OnlySkipped(ids) => {
+            println!("There are only 'skip'ped commits left to test.");
+            println!("The first bad commit could be any of:");
+            for id in ids {
+                println!("{}", id);
+            }
+            bail!("we cannot bisect more");
+        }
+    }
+
+    Ok(())
+}
+
+// Stops bisecting, going back to where HEAD was before starting.
+pub fn reset() -> Result<()> {
+    ensure_in_progress()?;
+
+    let original = fs::read_to_string(start_file())?.trim().to_owned();
+    let store = object::store();
+    let head = commit::head_commit()?;
+
+    let (id, branch) = match original.strip_prefix("refs/") {
+        Some(_) => (commit::get_ref(&original)?, Some(original.as_str())),
+        None => (Some(Oid::from_hex(&original)?), None),
+    };
+    if let Some(id) = id {
+        tree::update_working_dir(
+            &tree::flatten(store.as_ref(), &head.tree)?,
+            &tree::flatten(store.as_ref(), &commit::get_commit(&id)?.tree)?,
+        )?;
+        match branch {
+            Some(branch) => commit::update_symbolic_ref("HEAD", branch)?,
+            None => commit::detach_head(&id)?,
+        }
+    }
+
+    let refs_dir = WORKING_DIR.join(RUC_DIR).join(BISECT_REFS);
+    if refs_dir.is_dir() {
+        fs::remove_dir_all(refs_dir)?;
+    }
+    fs::remove_file(start_file()).context("could not stop bisecting")
+}
+
+// Runs the given command on each commit to be tested, marking it as good if
+// the command succeeds, as skipped if it exits with 125, and as bad for any
+// other exit code below 128. Anything else stops bisecting.
+pub fn run(command: &[String]) -> Result<()> {
+    ensure_in_progress()?;
+
+    let (program, args) = match command.split_first() {
+        Some(split) => split,
+        None => bail!("'ruc bisect run' needs a command to run"),
+    };
+    let command = command.join(" ");
+    let mut status = next()?;
+
+    loop {
+        match status {
+            Status::Testing(_, _) => report(status)?,
+            Status::Waiting => bail!("'ruc bisect run' needs both good and bad commits"),
+            _ => return report(status),
+        }
+
+        println!("running '{}'", command);
+        let exit = Command::new(program)
+            .args(args)
+            .current_dir(WORKING_DIR.as_path())
+            .status()
+            .with_context(|| format!("could not run '{}'", command))?;
+
+        let kind = match exit.code() {
+            Some(0) => "good",
+            Some(125) => "skip",
+            Some(code) if code < 128 => "bad",
+            _ => bail!("bisect run failed: '{}' exited abnormally", command),
+        };
+        mark(kind, &commit::head_commit()?.id)?;
+
+        status = next()?;
+    }
+}
@@ -0,0 +1,277 @@
+use crate::commit::{self, Commit};
+use crate::diff::{self, Edit};
+use crate::object;
+use crate::oid::Oid;
+use crate::tree;
+
+use anyhow::{bail, Result};
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+
+// Commit which last changed a line, and the number of the line (starting at
+// zero) in the version of the file from that commit.
+#[derive(Clone, Copy)]
+struct Origin {
+    id: Oid,
+    line: usize,
+}
+
+// Returns the contents of the file on the given path from the given commit, or
+// None if the file does not exist there.
+fn file_at(commit: &Commit, path: &str) -> Result<Option<String>> {
+    let store = object::store();
+
+    match tree::lookup(store.as_ref(), &commit.tree, path)? {
+        Some(id) => {
+            let obj = object::get(&id)?;
+            if obj.kind != object::Kind::Blob {
+                return Ok(None);
+            }
+            Ok(Some(String::from_utf8_lossy(&obj.contents).into_owned()))
+        }
+        None => Ok(None),
+    }
+}
+
+// Parses the given range for `-L`, which can be given as "start,end",
+// "start,+count", "start," or ",end", where lines start at one. It returns the
+// range of lines starting at zero for the given file with the given number of
+// lines.
+fn parse_range(range: &str, path: &str, total: usize) -> Result<(usize, usize)> {
+    let invalid = || anyhow::anyhow!("invalid -L range '{}'", range);
+
+    let (start, end) = range.split_once(',').unwrap_or((range, ""));
+    let start = match start {
+        "" => 1,
+        start => start.parse::<usize>().map_err(|_| invalid())?,
+    };
+    if start == 0 {
+        return Err(invalid());
+    }
+    let end = match end {
+        "" => total,
+        end => match end.strip_prefix('+') {
+            Some(count) => match count.parse::<usize>() {
+                Ok(count) if count > 0 => start.checked_add(count - 1).ok_or_else(invalid)?,
+                _ => return Err(invalid()),
+            },
+            None => end.parse::<usize>().map_err(|_| invalid())?,
+        },
+    };
+
+    if start > total {
+        bail!("file {} has only {} lines", path, total);
+    }
+    if end < start {
+        return Err(invalid());
+    }
+
+    Ok((start - 1, end.min(total)))
+}
+
+// Returns the commit which last changed each of the lines from the given
+// range. History is walked through first parents: lines which are the same
+// in the parent are passed on to it, and the rest are blamed on the commit
+// being looked at.
+fn blame_lines(
+    commit: &Commit,
+    path: &str,
+    contents: &str,
+    range: (usize, usize),
+) -> Result<Vec<Origin>> {
+    let mut res: Vec<Option<Origin>> = vec![None; range.1 - range.0];
+    // Lines still to be blamed, as pairs of the line from the final version and
+    // the same line on the version from `current`.
+    let mut pending = (range.0..range.1)
+        .map(|line| (line - range.0, line))
+        .collect::<Vec<_>>();
+    let mut current = commit.to_owned();
+    let mut text = contents.to_owned();
+
+    for parent in commit.clone() {
+        let parent_text = match file_at(&parent, path)? {
+            Some(text) => text,
+            None => break,
+        };
+
+        let (old, new) = (diff::lines(&parent_text), diff::lines(&text));
+        let unchanged = diff::diff(&old, &new)
+            .into_iter()
+            .filter_map(|edit| match edit {
+                Edit::Equal(x, y) => Some((y, x)),
+                _ => None,
+            })
+            .collect::<HashMap<_, _>>();
+
+        let mut still_pending = vec![];
+        for (idx, line) in pending {
+            match unchanged.get(&line) {
+                Some(old_line) => still_pending.push((idx, *old_line)),
+                None => {
+                    res[idx] = Some(Origin {
+                        id: current.id,
+                        line,
+                    })
+                }
+            }
+        }
+
+        pending = still_pending;
+        if pending.is_empty() {
+            break;
+        }
+        current = parent;
+        text = parent_text;
+    }
+
+    // Whatever is left was there since the file was added.
+    for (idx, line) in pending {
+        res[idx] = Some(Origin {
+            id: current.id,
+            line,
+        });
+    }
+
+    Ok(res.into_iter().flatten().collect())
+}
+
+// Shows which commit last changed each line of the file on the given path, as
+// it is on the given revision. The path is relative to the current directory.
+// Lines can be restricted to the given range (see `parse_range`), and with
+// `porcelain` the output is meant for scripts.
+pub fn blame(path: &str, rev: &str, range: Option<&String>, porcelain: bool) -> Result<()> {
+    let path = &tree::normalize_pathspec(path)?;
+    let commit = commit::get_commit(&commit::ref_to_oid(rev)?)?;
+    let contents = match file_at(&commit, path)? {
+        Some(contents) => contents,
+        None => bail!("no such path '{}' in {}", path, rev),
+    };
+
+    let lines = diff::lines(&contents);
+    let range = match range {
+        Some(range) => parse_range(range, path, lines.len())?,
+        None => (0, lines.len()),
+    };
+    let origins = blame_lines(&commit, path, &contents, range)?;
+
+    let mut commits = HashMap::new();
+    for origin in &origins {
+        if let Entry::Vacant(entry) = commits.entry(origin.id) {
+            entry.insert(commit::get_commit(&origin.id)?);
+        }
+    }
+
+    let blamed = origins
+        .iter()
+        .zip(&lines[range.0..range.1])
+        .enumerate()
+        .map(|(idx, (origin, line))| (range.0 + idx, origin, line.trim_end_matches('\n')))
+        .collect::<Vec<_>>();
+    if porcelain {
+        print_porcelain(path, &blamed, &commits)
+    } else {
+        print_lines(&blamed, &commits)
+    }
+}
+
+// Returns the name and the email of the given identity (e.g. "A U Thor
+// <author@example.com>").
+fn name_and_email(identity: &str) -> (&str, &str) {
+    match identity.split_once(" <") {
+        Some((name, email)) => (name, &email[..email.len().saturating_sub(1)]),
+        None => (identity, ""),
+    }
+}
+
+// Prints each line prefixed by the abbreviated ID of the commit which last
+// changed it, its author, its date and the line number. Commits with no
+// parents are marked with a caret.
+fn print_lines(blamed: &[(usize, &Origin, &str)], commits: &HashMap<Oid, Commit>) -> Result<()> {
+    let mut rows = vec![];
+    for (number, origin, line) in blamed {
+        let commit = &commits[&origin.id];
+        let (name, date) = match commit.author.as_deref().and_then(commit::split_signature) {
+            Some((identity, date)) => (name_and_email(identity).0, commit::format_date_iso(date)?),
+            None => ("", String::new()),
+        };
+        let id = match commit.parents.is_empty() {
+            true => format!("^{}", commit.id.abbrev(7)),
+            false => commit.id.abbrev(8),
+        };
+
+        rows.push((id, name, date, number + 1, line));
+    }
+
+    let name_width = rows.iter().map(|r| r.1.chars().count()).max().unwrap_or(0);
+    let number_width = rows.last().map(|r| r.3.to_string().len()).unwrap_or(0);
+    for (id, name, date, number, line) in rows {
+        println!(
+            "{} ({:<name_width$} {} {:>number_width$}) {}",
+            id, name, date, number, line
+        );
+    }
+
+    Ok(())
+}
+
+// Returns whether the blamed line `b`, which comes after `a`, belongs to the
+// same group of lines as `a`: the same commit changed both, and the lines are
+// just as far apart on the version from that commit.
+fn same_group(a: &(usize, &Origin, &str), b: &(usize, &Origin, &str)) -> bool {
+    a.1.id == b.1.id && b.1.line == a.1.line + (b.0 - a.0)
+}
+
+// Prints the output from `print_lines` in the porcelain format from Git. Each
+// group of lines coming from the same commit starts with the ID of the commit,
+// the line number in the original file, the line number in the final file and
+// the number of lines in the group. The rest of lines from the group only have
+// the first three. The first time a commit is shown it's followed by details
+// about it. Then each line is given prefixed by a tab.
+fn print_porcelain(
+    path: &str,
+    blamed: &[(usize, &Origin, &str)],
+    commits: &HashMap<Oid, Commit>,
+) -> Result<()> {
+    let mut shown = HashSet::new();
+
+    for (idx, (number, origin, line)) in blamed.iter().enumerate() {
+        let starts_group = idx == 0 || !same_group(&blamed[idx - 1], &blamed[idx]);
+
+        if !starts_group {
+            println!("{} {} {}", origin.id, origin.line + 1, number + 1);
+            println!("\t{}", line);
+            continue;
+        }
+
+        let size = blamed[idx..]
+            .iter()
+            .take_while(|other| same_group(&blamed[idx], other))
+            .count();
+        println!("{} {} {} {}", origin.id, origin.line + 1, number + 1, size);
+
+        if shown.insert(origin.id) {
+            let commit = &commits[&origin.id];
+            for (role, signature) in [("author", &commit.author), ("committer", &commit.committer)]
+            {
+                if let Some((identity, date)) =
+                    signature.as_deref().and_then(commit::split_signature)
+                {
+                    let (name, email) = name_and_email(identity);
+                    let (time, zone) = date.split_once(' ').unwrap_or((date, ""));
+                    println!("{} {}", role, name);
+                    println!("{}-mail <{}>", role, email);
+                    println!("{}-time {}", role, time);
+                    println!("{}-tz {}", role, zone);
+                }
+            }
+            println!("summary {}", commit.subject());
+            if commit.parents.is_empty() {
+                println!("boundary");
+            }
+            println!("filename {}", path);
+        }
+        println!("\t{}", line);
+    }
+
+    Ok(())
+}
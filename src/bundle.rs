@@ -0,0 +1,302 @@
+use crate::commit::{self, RevRange};
+use crate::object::{self, MemoryStore, ObjectStore};
+use crate::oid::{HashAlgorithm, Oid};
+use crate::pack;
+use crate::remote::{RefUpdate, Transport};
+
+use anyhow::{bail, Context, Result};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+// Bundles are single files which contain everything needed in order to
+// transfer history between repositories without a network connection. They
+// start with a header like this:
+//
+//   # ruc bundle v1
+//   @object-format=sha1
+//   -<id> <subject>
+//   <id> <reference>
+//
+// The lines starting with "-" are prerequisites, that is, commits which are not
+// included in the bundle and that the receiving repository must already have.
+// Then, after an empty line, there's a pack with all the objects.
+const SIGNATURE: &str = "# ruc bundle v1";
+const OBJECT_FORMAT: &str = "@object-format=";
+
+#[derive(Debug)]
+pub struct Bundle {
+    pub algorithm: HashAlgorithm,
+    pub prerequisites: Vec<Oid>,
+    pub refs: Vec<(String, Oid)>,
+    pub pack: Vec<u8>,
+}
+
+impl Bundle {
+    // Reads the bundle from the given path.
+    pub fn open(path: &Path) -> Result<Bundle> {
+        let data =
+            fs::read(path).with_context(|| format!("could not read '{}'", path.display()))?;
+        let mut rest = data.as_slice();
+
+        let mut bundle = Bundle {
+            algorithm: HashAlgorithm::default(),
+            prerequisites: vec![],
+            refs: vec![],
+            pack: vec![],
+        };
+
+        let mut first = true;
+        loop {
+            let pos = match rest.iter().position(|&b| b == b'\n') {
+                Some(pos) => pos,
+                None => bail!("'{}' is not a valid bundle", path.display()),
+            };
+            let line = std::str::from_utf8(&rest[..pos])
+                .with_context(|| format!("'{}' is not a valid bundle", path.display()))?;
+            rest = &rest[pos + 1..];
+
+            if first {
+                if line != SIGNATURE {
+                    bail!("'{}' is not a valid bundle", path.display());
+                }
+                first = false;
+            } else if line.is_empty() {
+                break;
+            } else if let Some(format) = line.strip_prefix(OBJECT_FORMAT) {
+                bundle.algorithm = HashAlgorithm::from_str(format)?;
+            } else if let Some(prerequisite) = line.strip_prefix('-') {
+                let id = prerequisite.split(' ').next().unwrap_or("");
+                bundle.prerequisites.push(Oid::from_hex(id)?);
+            } else {
+                // References from bundles may end up being written inside of
+                // `.ruc` (e.g. when fetching from them), so their names must be
+                // valid.
+                match line.split_once(' ') {
+                    Some((id, name)) => {
+                        commit::check_ref_name(name).with_context(|| {
+                            format!("'{}' is not a valid bundle", path.display())
+                        })?;
+                        bundle.refs.push((name.to_owned(), Oid::from_hex(id)?))
+                    }
+                    None => bail!("bad line in bundle: '{}'", line),
+                }
+            }
+        }
+
+        bundle.pack = rest.to_vec();
+        Ok(bundle)
+    }
+
+    // Returns the prerequisites from this bundle which are missing from the
+    // given store.
+    fn missing_prerequisites(&self, store: &dyn ObjectStore) -> Vec<Oid> {
+        self.prerequisites
+            .iter()
+            .filter(|id| !store.contains(id))
+            .copied()
+            .collect()
+    }
+
+    // Checks that the bundle can be applied into the given store.
+    fn check(&self, store: &dyn ObjectStore) -> Result<()> {
+        if self.algorithm != store.algorithm() {
+            bail!(
+                "the bundle uses {} but the repository uses {}",
+                self.algorithm,
+                store.algorithm()
+            );
+        }
+
+        let missing = self.missing_prerequisites(store);
+        if !missing.is_empty() {
+            let list = missing
+                .iter()
+                .map(|id| id.to_string())
+                .collect::<Vec<_>>()
+                .join("\n");
+            bail!("the repository lacks these prerequisite commits:\n{}", list);
+        }
+
+        Ok(())
+    }
+}
+
+// Creates a bundle on the given path with the commits from the given range of
+// revisions. The references being included are the ones explicitly given in
+// the range (e.g. "main" for "v1.0..main").
+pub fn create(path: &Path, revs: &[String]) -> Result<()> {
+    let range = RevRange::parse(revs)?;
+    let store = object::store();
+
+    let mut refs = vec![];
+    for (name, id) in &range.include {
+        let full_name = if name == "HEAD" {
+            Some(name.to_owned())
+        } else {
+            commit::full_ref_name(name)?
+        };
+
+        if let Some(full_name) = full_name {
+            if !refs.iter().any(|(n, _)| *n == full_name) {
+                refs.push((full_name, *id));
+            }
+        }
+    }
+    if refs.is_empty() {
+        bail!("refusing to create a bundle without references");
+    }
+
+    // Walk the commits from the given range. Parents which fall out of the
+    // range are prerequisites for the bundle.
+    let commits = commit::rev_list(&range)?;
+    let ids = commits.iter().map(|c| c.id).collect::<HashSet<_>>();
+    let mut prerequisites = vec![];
+    for c in &commits {
+        for parent in &c.parents {
+            if !ids.contains(parent) && !prerequisites.contains(parent) {
+                prerequisites.push(*parent);
+            }
+        }
+    }
+    if commits.is_empty() {
+        bail!("refusing to create an empty bundle");
+    }
+
+    // Objects reachable from the prerequisites are assumed to be on the
+    // receiving side already, so they are skipped.
+    let prerequisite_trees = prerequisites
+        .iter()
+        .map(|id| commit::get_commit(id).map(|c| c.tree))
+        .collect::<Result<Vec<_>>>()?;
+    let known = pack::reachable(store.as_ref(), &prerequisite_trees, &HashSet::new())?
+        .into_iter()
+        .collect::<HashSet<_>>();
+    let trees = commits.iter().map(|c| c.tree).collect::<Vec<_>>();
+
+    let mut objects = commits.iter().map(|c| c.id).collect::<Vec<_>>();
+    objects.extend(pack::reachable(store.as_ref(), &trees, &known)?);
+
+    let mut data = format!("{}\n{}{}\n", SIGNATURE, OBJECT_FORMAT, store.algorithm()).into_bytes();
+    for id in &prerequisites {
+        let subject = commit::get_commit(id)?
+            .contents
+            .lines()
+            .next()
+            .unwrap_or("")
+            .to_owned();
+        data.extend_from_slice(format!("-{} {}\n", id, subject).as_bytes());
+    }
+    for (name, id) in &refs {
+        data.extend_from_slice(format!("{} {}\n", id, name).as_bytes());
+    }
+    data.push(b'\n');
+    data.extend(pack::write(store.as_ref(), &objects)?);
+
+    fs::write(path, data).with_context(|| format!("could not write '{}'", path.display()))?;
+
+    Ok(())
+}
+
+// Checks that the given bundle is valid and that it can be applied into the
+// current repository.
+pub fn verify(path: &Path) -> Result<()> {
+    let bundle = Bundle::open(path)?;
+    bundle.check(object::store().as_ref())?;
+
+    // Unpack it on a throwaway store so the contents are checked without
+    // touching the repository.
+    pack::read(&bundle.pack, &MemoryStore::new(bundle.algorithm))?;
+
+    println!("The bundle contains {} ref(s):", bundle.refs.len());
+    for (name, id) in &bundle.refs {
+        println!("{} {}", id, name);
+    }
+    if bundle.prerequisites.is_empty() {
+        println!("The bundle records a complete history.");
+    } else {
+        println!(
+            "The bundle requires {} commit(s):",
+            bundle.prerequisites.len()
+        );
+        for id in &bundle.prerequisites {
+            println!("{}", id);
+        }
+    }
+    println!("{} is okay", path.display());
+
+    Ok(())
+}
+
+// Unpacks the objects from the given bundle into the current repository, and
+// prints the references from it. References are not updated: use `fetch` for
+// that instead.
+pub fn unbundle(path: &Path) -> Result<()> {
+    let bundle = Bundle::open(path)?;
+    let store = object::store();
+
+    bundle.check(store.as_ref())?;
+    pack::read(&bundle.pack, store.as_ref())?;
+
+    for (name, id) in &bundle.refs {
+        println!("{} {}", id, name);
+    }
+
+    Ok(())
+}
+
+// Transport which allows to clone and fetch from a bundle as if it was a
+// remote repository.
+pub struct BundleTransport {
+    bundle: Bundle,
+}
+
+impl BundleTransport {
+    pub fn new(path: &Path) -> Result<BundleTransport> {
+        Ok(BundleTransport {
+            bundle: Bundle::open(path)?,
+        })
+    }
+}
+
+impl Transport for BundleTransport {
+    fn algorithm(&self) -> Result<HashAlgorithm> {
+        Ok(self.bundle.algorithm)
+    }
+
+    fn refs(&self) -> Result<Vec<(String, Oid)>> {
+        Ok(self.bundle.refs.clone())
+    }
+
+    // Bundles do not record where HEAD points to. Hence, take the branch
+    // matching HEAD if it was included, or the only branch otherwise.
+    fn head(&self) -> Result<Option<String>> {
+        let branches = self
+            .bundle
+            .refs
+            .iter()
+            .filter(|(name, _)| name.starts_with("refs/heads/"))
+            .collect::<Vec<_>>();
+        let head = self.bundle.refs.iter().find(|(name, _)| name == "HEAD");
+
+        Ok(match head {
+            Some((_, id)) => branches
+                .iter()
+                .find(|(_, branch)| branch == id)
+                .map(|(name, _)| name.to_owned()),
+            None if branches.len() == 1 => Some(branches[0].0.to_owned()),
+            None => None,
+        })
+    }
+
+    fn fetch(&self, _wants: &[Oid], _haves: &[Oid]) -> Result<Vec<u8>> {
+        self.bundle.check(object::store().as_ref())?;
+
+        Ok(self.bundle.pack.clone())
+    }
+
+    fn push(&self, _pack: &[u8], _updates: &[RefUpdate], _force: bool) -> Result<()> {
+        bail!("cannot push into a bundle")
+    }
+}
@@ -1,23 +1,38 @@
+use crate::config;
+use crate::diff;
+use crate::hooks;
 use crate::init::{RUC_DIR, WORKING_DIR};
-use crate::object;
+use crate::object::{self, Kind, Object, ObjectStore};
+use crate::oid::Oid;
 use crate::tree;
 
 use anyhow::{bail, Context, Result};
+use std::collections::HashSet;
 use std::env;
 use std::fs;
 use std::io::prelude::*;
 use std::io::Read;
+use std::path::Path;
 use std::process;
 use std::process::{Command, Stdio};
+use std::time::{SystemTime, UNIX_EPOCH};
 
-pub fn editor() -> Result<String> {
+// Opens the EDITOR for writing a commit message, which starts with the given
+// one.
+pub fn editor(initial: &str) -> Result<String> {
+    edit_file("COMMIT_EDITMSG", initial)
+}
+
+// Opens the EDITOR on a file inside of `.ruc` with the given name, which starts
+// with the given contents, and returns whatever was left in there.
+pub fn edit_file(name: &str, initial: &str) -> Result<String> {
     let program = match env::var("EDITOR") {
         Ok(v) => v,
         Err(_) => bail!("could not get the default EDITOR"),
     };
 
-    let path = WORKING_DIR.join(RUC_DIR).join("COMMIT_EDITMSG");
-    fs::File::create(&path).expect("could not create temporary file for editing the message");
+    let path = WORKING_DIR.join(RUC_DIR).join(name);
+    fs::write(&path, initial).expect("could not create temporary file for editing the message");
 
     process::Command::new(program).arg(&path).status()?;
 
@@ -28,70 +43,680 @@ pub fn editor() -> Result<String> {
     Ok(editable.trim_end().to_owned())
 }
 
-pub fn commit(message: String) -> Result<()> {
-    let id = tree::traverse_write_tree(&WORKING_DIR)?;
+// Returns the identity for the given role (i.e. "author" or "committer") as
+// it's written in commit headers: "Name <email> timestamp timezone". Each value
+// can be given through the environment (e.g. RUC_AUTHOR_NAME,
+// RUC_COMMITTER_DATE), otherwise the name and the email are taken from the
+// `user.name` and `user.email` configuration values, and the date is the
+// current time.
+pub fn signature(role: &str) -> Result<String> {
+    let config = config::load()?;
+    let var = |key: &str| env::var(format!("RUC_{}_{}", role.to_uppercase(), key)).ok();
+    let user = env::var("USER").unwrap_or_else(|_| String::from("ruc"));
 
-    let parent_id = get_ref(&String::from("HEAD"))?;
-    let contents = if parent_id.is_empty() {
-        format!("tree {}\n\n{}", id, message)
-    } else {
-        format!("tree {}\nparent {}\n\n{}", id, parent_id, message)
+    let name = var("NAME")
+        .or_else(|| config.get("user.name").map(|v| v.to_owned()))
+        .unwrap_or_else(|| user.clone());
+    let email = var("EMAIL")
+        .or_else(|| config.get("user.email").map(|v| v.to_owned()))
+        .unwrap_or_else(|| format!("{}@localhost", user));
+    let date = match var("DATE") {
+        Some(date) => date,
+        None => format!(
+            "{} +0000",
+            SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs()
+        ),
+    };
+
+    let valid_date = match date.split_once(' ') {
+        Some((time, zone)) => {
+            time.parse::<u64>().is_ok()
+                && zone.len() == 5
+                && (zone.starts_with('+') || zone.starts_with('-'))
+                && zone[1..].chars().all(|c| c.is_ascii_digit())
+        }
+        None => false,
+    };
+    if !valid_date {
+        bail!("bad date '{}' for the {}", date, role);
+    }
+    if name.contains(['<', '>', '\n']) || email.contains(['<', '>', '\n']) {
+        bail!("bad identity '{} <{}>' for the {}", name, email, role);
+    }
+
+    Ok(format!("{} <{}> {}", name, email, date))
+}
+
+// Identity used for commits which don't record one, since they were made
+// before ruc started recording them.
+pub const UNKNOWN_IDENTITY: &str = "ruc <ruc@localhost> 0 +0000";
+
+// Returns the author of the given commit, or the current one if the commit
+// does not record it.
+pub fn author_of(commit: &Commit) -> Result<String> {
+    match &commit.author {
+        Some(author) => Ok(author.to_owned()),
+        None => signature("author"),
+    }
+}
+
+// Commits the working directory on top of HEAD with the given message, or the
+// one from the editor if none is given. With `amend`, the commit replaces HEAD
+// instead: it takes its parents and author, and the editor starts with its
+// message. Unless `verify` is false, the pre-commit hook is run before
+// anything else, and the commit-msg hook can reject or change the message from
+// the file given as its argument.
+pub fn commit(message: Option<&String>, amend: bool, verify: bool) -> Result<()> {
+    let amended = match amend {
+        true => match get_ref("HEAD")? {
+            Some(id) => Some(get_commit(&id)?),
+            None => bail!("there is no commit to amend"),
+        },
+        false => None,
     };
+    if verify {
+        hooks::run_pre("pre-commit", &[], None)?;
+    }
+
+    let mut message = match (message, &amended) {
+        (Some(message), _) => message.to_owned(),
+        (None, Some(head)) => editor(&head.contents)?,
+        (None, None) => editor("")?,
+    };
+    if verify && hooks::exists("commit-msg") {
+        let path = WORKING_DIR.join(RUC_DIR).join("COMMIT_EDITMSG");
+        fs::write(&path, &message).context("could not save the commit message")?;
+
+        let res = hooks::run_pre("commit-msg", &[&path.to_string_lossy()], None);
+        let edited = fs::read_to_string(&path)?;
+        fs::remove_file(&path)?;
+        res?;
+
+        if edited != message {
+            message = edited.trim_end().to_owned();
+        }
+    }
+
+    let id = tree::traverse_write_tree(&WORKING_DIR)?;
+    let (parents, author) = match &amended {
+        Some(head) => (head.parents.clone(), author_of(head)?),
+        None => (
+            get_ref("HEAD")?.into_iter().collect::<Vec<_>>(),
+            signature("author")?,
+        ),
+    };
+
+    let commit_id = write_commit(&id, &parents, &author, &message)?;
+    update_ref("HEAD", &commit_id)?;
+
+    hooks::run("post-commit", &[], None)?;
+
+    Ok(())
+}
+
+// Stores a new commit object with the given tree, parents, author and message,
+// and returns its ID. The committer is always the current one.
+pub fn write_commit(tree: &Oid, parents: &[Oid], author: &str, message: &str) -> Result<Oid> {
+    let mut contents = format!("tree {}\n", tree);
+    for parent in parents {
+        contents.push_str(&format!("parent {}\n", parent));
+    }
+    contents.push_str(&format!(
+        "author {}\ncommitter {}\n\n{}",
+        author,
+        signature("committer")?,
+        message
+    ));
 
-    let commit_id = object::hash_contents(&contents, object::Kind::Commit)?;
-    update_ref(&String::from("HEAD"), &commit_id)?;
+    object::hash_contents(contents.as_bytes(), Kind::Commit)
+}
+
+// Returns the commit being pointed to by HEAD, or an error if there is none.
+pub fn head_commit() -> Result<Commit> {
+    match get_ref("HEAD")? {
+        Some(id) => get_commit(&id),
+        None => bail!("current branch has no commit yet"),
+    }
+}
+
+// Returns an error if the working directory does not match the tree of the
+// given commit. Since there's no index, any change (including new files) counts
+// as a local change which could be overwritten by the given action.
+pub fn ensure_clean(head: &Commit, action: &str) -> Result<()> {
+    if tree::traverse_write_tree(&WORKING_DIR)? != head.tree {
+        bail!(
+            "cannot {}: you have local changes in the working directory",
+            action
+        );
+    }
 
     Ok(())
 }
 
-pub fn ref_to_oid(name: &String) -> Result<String> {
-    for path in &["", "refs/", "refs/tags/", "refs/heads/"] {
+// Returns the full name of the reference for the given short name (e.g.
+// "refs/heads/main" for "main"), or None if there's no such reference.
+pub fn full_ref_name(name: &str) -> Result<Option<String>> {
+    for path in &["", "refs/", "refs/tags/", "refs/heads/", "refs/remotes/"] {
         let full_path = WORKING_DIR.join(RUC_DIR).join(path).join(name);
 
-        if full_path.exists() {
-            return get_ref(&format!("{}{}", path, name));
+        if full_path.is_file() {
+            let full_name = format!("{}{}", path, name);
+            if get_ref(&full_name)?.is_some() {
+                return Ok(Some(full_name));
+            }
         }
     }
 
-    Ok(name.to_owned())
+    Ok(None)
 }
 
-pub fn get_ref(name: &String) -> Result<String> {
-    let ref_file = WORKING_DIR.join(RUC_DIR).join(name);
+// Returns the object ID for the given name, which can either be a reference
+// or a (possibly abbreviated) object ID. The name can be followed by "~n" to
+// get its n-th ancestor following first parents, or by "^n" to get its n-th
+// parent (both "~" and "^" alone mean the first parent). These can be chained
+// (e.g. "main~2^2"). Last but not least, "<rev>:<path>" names the object found
+// at the given path inside of the tree from the given revision.
+pub fn ref_to_oid(name: &str) -> Result<Oid> {
+    if let Some((rev, path)) = name.split_once(':') {
+        let tree = tree::peel_to_tree(&ref_to_oid(rev)?)?;
+        return match tree::lookup(object::store().as_ref(), &tree, path)? {
+            Some(id) => Ok(id),
+            None => bail!("path '{}' does not exist in '{}'", path, rev),
+        };
+    }
+
+    let (base, mut suffix) = match name.find(['~', '^']) {
+        Some(pos) if pos > 0 => name.split_at(pos),
+        _ => (name, ""),
+    };
+
+    let mut id = match full_ref_name(base)? {
+        Some(full_name) => get_ref(&full_name)?.unwrap(),
+        None => object::expand_prefix(base)?,
+    };
+
+    // Parse the whole suffix before walking anything, so bad revisions are
+    // reported as such.
+    let mut steps = vec![];
+    while let Some(op) = suffix.chars().next() {
+        if op != '~' && op != '^' {
+            bail!("'{}' is not a valid revision", name);
+        }
+
+        let rest = &suffix[op.len_utf8()..];
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let n = match &rest[..digits] {
+            "" => 1,
+            number => match number.parse::<usize>() {
+                Ok(n) => n,
+                Err(_) => bail!("'{}' is not a valid revision", name),
+            },
+        };
+        steps.push((op, n));
+        suffix = &rest[digits..];
+    }
+
+    for (op, n) in steps {
+        match op {
+            '~' => {
+                for _ in 0..n {
+                    id = match get_commit(&id)?.parents.first() {
+                        Some(parent) => *parent,
+                        None => bail!("'{}' does not have that many ancestors", name),
+                    };
+                }
+            }
+            '^' if n == 0 => id = get_commit(&id)?.id,
+            '^' => {
+                id = match get_commit(&id)?.parents.get(n - 1) {
+                    Some(parent) => *parent,
+                    None => bail!("'{}' does not have that many parents", name),
+                };
+            }
+            _ => bail!("'{}' is not a valid revision", name),
+        }
+    }
+
+    Ok(id)
+}
+
+// Range of commits as given by a list of revisions. Each revision can be
+// either a plain revision (e.g. "main"), which includes everything reachable
+// from it; a negated revision (e.g. "^main"), which excludes everything
+// reachable from it; or a range like "A..B", which is the same as "^A B".
+#[derive(Debug, Default)]
+pub struct RevRange {
+    // Revisions to be included, along with the name they were given with.
+    pub include: Vec<(String, Oid)>,
+    pub exclude: Vec<Oid>,
+}
+
+impl RevRange {
+    pub fn parse(revs: &[String]) -> Result<RevRange> {
+        let mut range = RevRange::default();
+
+        for rev in revs {
+            if let Some(negated) = rev.strip_prefix('^') {
+                range.exclude.push(ref_to_oid(negated)?);
+            } else if let Some((from, to)) = rev.split_once("..") {
+                let from = if from.is_empty() { "HEAD" } else { from };
+                let to = if to.is_empty() { "HEAD" } else { to };
+
+                range.exclude.push(ref_to_oid(from)?);
+                range.include.push((to.to_owned(), ref_to_oid(to)?));
+            } else {
+                range.include.push((rev.to_owned(), ref_to_oid(rev)?));
+            }
+        }
+
+        Ok(range)
+    }
+}
+
+// Returns the commits from the given range. Commits always come before their
+// parents, so for a linear history they go from newest to oldest.
+pub fn rev_list(range: &RevRange) -> Result<Vec<Commit>> {
+    rev_list_ids(range)?.iter().map(get_commit).collect()
+}
+
+// Same as `rev_list` but only returning the IDs of the commits. Parents are
+// taken from the commit graph when possible, so with one commit objects are
+// only read for the revisions given in the range.
+pub fn rev_list_ids(range: &RevRange) -> Result<Vec<Oid>> {
+    let store = object::store();
+    let exclude = range
+        .exclude
+        .iter()
+        .map(|id| Ok(get_commit(id)?.id))
+        .collect::<Result<Vec<_>>>()?;
+    let excluded = crate::graph::ancestors(store.as_ref(), &exclude)?;
+
+    // Walk the history depth-first, adding each commit once all of its parents
+    // have been added. The reverse of this order is the one we are after.
+    let mut res = vec![];
+    let mut seen = HashSet::new();
+    let mut pending = vec![];
+    for (_, id) in range.include.iter().rev() {
+        pending.push((get_commit(id)?.id, false));
+    }
+
+    while let Some((id, done)) = pending.pop() {
+        if done {
+            res.push(id);
+            continue;
+        }
+        if excluded.contains(&id) || !seen.insert(id) {
+            continue;
+        }
+
+        pending.push((id, true));
+        for parent in crate::graph::parents(store.as_ref(), &id)?.iter().rev() {
+            if !seen.contains(parent) && !excluded.contains(parent) {
+                pending.push((*parent, false));
+            }
+        }
+    }
+
+    res.reverse();
+    Ok(res)
+}
+
+// Prints the commits from the given range. If `objects` is true, then the
+// trees and the blobs from these commits which are not reachable from the
+// excluded side of the range are printed as well, along with their path.
+pub fn print_rev_list(range: &RevRange, objects: bool) -> Result<()> {
+    let ids = rev_list_ids(range)?;
+    for id in &ids {
+        println!("{}", id);
+    }
+    if !objects {
+        return Ok(());
+    }
+
+    let store = object::store();
+    let excluded_trees = range
+        .exclude
+        .iter()
+        .map(|id| Ok(get_commit(id)?.tree))
+        .collect::<Result<Vec<_>>>()?;
+    let mut seen = crate::pack::reachable(store.as_ref(), &excluded_trees, &HashSet::new())?
+        .into_iter()
+        .collect::<HashSet<_>>();
+
+    for id in &ids {
+        let mut pending = vec![(get_commit(id)?.tree, String::new())];
+
+        while let Some((id, path)) = pending.pop() {
+            if !seen.insert(id) {
+                continue;
+            }
+            println!("{} {}", id, path);
+
+            if object::get(&id)?.kind == Kind::Tree {
+                let entries = tree::entries_from(store.as_ref(), &id)?;
+                for entry in entries.into_iter().rev() {
+                    let child = if path.is_empty() {
+                        entry.name
+                    } else {
+                        format!("{}/{}", path, entry.name)
+                    };
+                    pending.push((entry.id, child));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Prefix used by references which point to other references instead of
+// pointing directly to an object (e.g. "ref: refs/heads/main" for HEAD).
+const SYMBOLIC_PREFIX: &str = "ref: ";
+
+// Returns the object ID stored in the given reference, or None if the reference
+// does not exist yet. Symbolic references are followed.
+pub fn get_ref(name: &str) -> Result<Option<Oid>> {
+    get_ref_in(&WORKING_DIR.join(RUC_DIR), name)
+}
+
+// Same as `get_ref` but for the repository on the given `.ruc` directory.
+pub fn get_ref_in(ruc_dir: &Path, name: &str) -> Result<Option<Oid>> {
+    let ref_file = ruc_dir.join(name);
 
     match std::fs::read_to_string(ref_file) {
-        Ok(contents) => Ok(contents),
+        Ok(contents) => match contents.trim().strip_prefix(SYMBOLIC_PREFIX) {
+            Some(target) => get_ref_in(ruc_dir, target),
+            None => {
+                let id = Oid::from_hex(contents.trim())
+                    .with_context(|| format!("bad contents for reference {}", name))?;
+                Ok(Some(id))
+            }
+        },
+        Err(e) => match e.kind() {
+            std::io::ErrorKind::NotFound => Ok(None),
+            _ => bail!(format!("could not get the current value for {}!", name)),
+        },
+    }
+}
+
+// Returns the reference being pointed to by the given symbolic reference, or
+// None if the given reference is not symbolic.
+pub fn symbolic_ref(name: &str) -> Result<Option<String>> {
+    symbolic_ref_in(&WORKING_DIR.join(RUC_DIR), name)
+}
+
+// Same as `symbolic_ref` but for the repository on the given `.ruc` directory.
+pub fn symbolic_ref_in(ruc_dir: &Path, name: &str) -> Result<Option<String>> {
+    match std::fs::read_to_string(ruc_dir.join(name)) {
+        Ok(contents) => Ok(contents
+            .trim()
+            .strip_prefix(SYMBOLIC_PREFIX)
+            .map(|target| target.to_owned())),
         Err(e) => match e.kind() {
-            std::io::ErrorKind::NotFound => Ok(String::new()),
+            std::io::ErrorKind::NotFound => Ok(None),
             _ => bail!(format!("could not get the current value for {}!", name)),
         },
     }
 }
 
-pub fn update_ref(name: &String, commit_id: &String) -> Result<()> {
-    let ref_file = WORKING_DIR.join(RUC_DIR).join(name);
+// Makes the given reference point to the given target reference.
+pub fn update_symbolic_ref(name: &str, target: &str) -> Result<()> {
+    write_ref_file(
+        &WORKING_DIR.join(RUC_DIR),
+        name,
+        &format!("{}{}", SYMBOLIC_PREFIX, target),
+    )
+}
+
+// Updates the given reference so it points to the given object. If the given
+// reference is symbolic, then the reference being pointed to is updated
+// instead.
+pub fn update_ref(name: &str, commit_id: &Oid) -> Result<()> {
+    update_ref_in(&WORKING_DIR.join(RUC_DIR), name, commit_id)
+}
+
+// Same as `update_ref` but for the repository on the given `.ruc` directory.
+pub fn update_ref_in(ruc_dir: &Path, name: &str, commit_id: &Oid) -> Result<()> {
+    match symbolic_ref_in(ruc_dir, name)? {
+        Some(target) => update_ref_in(ruc_dir, &target, commit_id),
+        None => write_ref_file(ruc_dir, name, &commit_id.to_string()),
+    }
+}
+
+// Makes HEAD point directly to the given object, regardless of it being a
+// symbolic reference before.
+pub fn detach_head(commit_id: &Oid) -> Result<()> {
+    write_ref_file(&WORKING_DIR.join(RUC_DIR), "HEAD", &commit_id.to_string())
+}
+
+fn write_ref_file(ruc_dir: &Path, name: &str, contents: &str) -> Result<()> {
+    let ref_file = ruc_dir.join(name);
+
+    if let Some(dir) = ref_file.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("could not save {} state", name))?;
+    }
 
     let mut file =
         fs::File::create(ref_file).with_context(|| format!("could not save {} state", name))?;
-    file.write_all(commit_id.as_bytes())
+    file.write_all(contents.as_bytes())
         .with_context(|| format!("could not save {} state", name))?;
 
     Ok(())
 }
 
+// Removes the given reference.
+pub fn delete_ref(name: &str) -> Result<()> {
+    fs::remove_file(WORKING_DIR.join(RUC_DIR).join(name))
+        .with_context(|| format!("could not remove {}", name))
+}
+
+// Returns all the references under the given prefix (e.g. "refs/heads/") along
+// with the object they point to, sorted by name.
+pub fn list_refs(prefix: &str) -> Result<Vec<(String, Oid)>> {
+    list_refs_in(&WORKING_DIR.join(RUC_DIR), prefix)
+}
+
+// Same as `list_refs` but for the repository on the given `.ruc` directory.
+pub fn list_refs_in(ruc_dir: &Path, prefix: &str) -> Result<Vec<(String, Oid)>> {
+    let mut res = vec![];
+    let mut pending = vec![prefix.trim_end_matches('/').to_owned()];
+
+    while let Some(current) = pending.pop() {
+        let entries = match fs::read_dir(ruc_dir.join(&current)) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e.into()),
+        };
+
+        for entry in entries {
+            let entry = entry?;
+            let name = match entry.file_name().to_str() {
+                Some(name) => format!("{}/{}", current, name),
+                None => continue,
+            };
+
+            if entry.file_type()?.is_dir() {
+                pending.push(name);
+            } else if let Some(id) = get_ref_in(ruc_dir, &name)? {
+                res.push((name, id));
+            }
+        }
+    }
+
+    res.sort();
+    Ok(res)
+}
+
+// Prints the references under `refs/`, optionally only branches and/or tags,
+// along with the object they point to. It fails if there's nothing to show.
+pub fn show_ref(heads: bool, tags: bool) -> Result<()> {
+    let refs = list_refs("refs/")?
+        .into_iter()
+        .filter(|(name, _)| {
+            (!heads && !tags)
+                || (heads && name.starts_with("refs/heads/"))
+                || (tags && name.starts_with("refs/tags/"))
+        })
+        .collect::<Vec<_>>();
+    if refs.is_empty() {
+        bail!("no references found");
+    }
+
+    for (name, id) in refs {
+        println!("{} {}", id, name);
+    }
+
+    Ok(())
+}
+
+// Returns the value of the given field (e.g. "refname:short") for the given
+// reference, as used by `for_each_ref`.
+fn ref_field(name: &str, id: &Oid, field: &str) -> Result<String> {
+    let obj = object::get(id)?;
+    let (headers, message) = match obj.kind {
+        Kind::Commit | Kind::Tag => split_headers(obj.text()?),
+        _ => (vec![], ""),
+    };
+    let header = |key: &str| {
+        headers
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.to_owned())
+            .unwrap_or_default()
+    };
+    let identity = header(if obj.kind == Kind::Tag {
+        "tagger"
+    } else {
+        "author"
+    });
+
+    Ok(match field {
+        "refname" => name.to_owned(),
+        "refname:short" => ["refs/heads/", "refs/tags/", "refs/remotes/", "refs/"]
+            .iter()
+            .find_map(|prefix| name.strip_prefix(prefix))
+            .unwrap_or(name)
+            .to_owned(),
+        "objectname" => id.to_string(),
+        "objectname:short" => id.abbrev(7),
+        "objecttype" => obj.kind.to_string(),
+        "subject" => message.lines().next().unwrap_or_default().to_owned(),
+        "body" => message
+            .split_once("\n\n")
+            .map(|(_, body)| body.to_owned())
+            .unwrap_or_default(),
+        "authorname" | "taggername" => identity
+            .split_once(" <")
+            .map(|(name, _)| name.to_owned())
+            .unwrap_or_default(),
+        "authoremail" | "taggeremail" => identity
+            .split_once(" <")
+            .and_then(|(_, rest)| rest.split_once('>'))
+            .map(|(email, _)| format!("<{}>", email))
+            .unwrap_or_default(),
+        "authordate" | "taggerdate" => identity
+            .rsplitn(3, ' ')
+            .collect::<Vec<_>>()
+            .into_iter()
+            .rev()
+            .skip(1)
+            .collect::<Vec<_>>()
+            .join(" "),
+        _ => bail!("unknown field name: {}", field),
+    })
+}
+
+// Prints the references starting with any of the given patterns (all of them
+// if none is given) with the given format. Fields are given as "%(name)", and
+// "%%" is a literal percent sign.
+pub fn for_each_ref(format: &str, patterns: &[String]) -> Result<()> {
+    for (name, id) in list_refs("refs/")? {
+        if !patterns.is_empty() && !patterns.iter().any(|p| name.starts_with(p.as_str())) {
+            continue;
+        }
+
+        let mut line = String::new();
+        let mut rest = format;
+        while let Some(pos) = rest.find('%') {
+            line.push_str(&rest[..pos]);
+            rest = &rest[pos..];
+
+            if let Some(after) = rest.strip_prefix("%%") {
+                line.push('%');
+                rest = after;
+            } else if let Some((field, after)) =
+                rest.strip_prefix("%(").and_then(|r| r.split_once(')'))
+            {
+                line.push_str(&ref_field(&name, &id, field)?);
+                rest = after;
+            } else {
+                line.push('%');
+                rest = &rest[1..];
+            }
+        }
+        line.push_str(rest);
+
+        println!("{}", line);
+    }
+
+    Ok(())
+}
+
+// Prints the reference being pointed to by the given symbolic reference, or
+// makes it point to the given target. The symbolic reference is either HEAD or
+// one under `refs/`.
+pub fn symbolic_ref_command(name: &str, target: Option<&String>, short: bool) -> Result<()> {
+    if name != "HEAD" {
+        if !name.starts_with("refs/") {
+            bail!("'{}' is neither HEAD nor a reference under refs/", name);
+        }
+        check_ref_name(name)?;
+    }
+
+    match target {
+        Some(target) => {
+            if !target.starts_with("refs/") {
+                bail!("refusing to point {} outside of refs/", name);
+            }
+            check_ref_name(target)?;
+
+            update_symbolic_ref(name, target)
+        }
+        None => match symbolic_ref(name)? {
+            Some(target) => {
+                let shown = if short {
+                    target.strip_prefix("refs/heads/").unwrap_or(&target)
+                } else {
+                    &target
+                };
+                println!("{}", shown);
+
+                Ok(())
+            }
+            None => bail!("ref {} is not a symbolic ref", name),
+        },
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Commit {
-    pub id: String,
-    pub tree: String,
-    pub parent: Option<String>,
+    pub id: Oid,
+    pub tree: Oid,
+    pub parents: Vec<Oid>,
+    pub author: Option<String>,
+    pub committer: Option<String>,
     pub contents: String,
 }
 
+// Iterating over a commit follows its first parent, which is the one
+// containing the history of the branch where the commit was made.
 impl Iterator for Commit {
     type Item = Commit;
 
     fn next(&mut self) -> Option<Commit> {
-        match &self.parent {
+        match self.parents.first() {
             Some(parent) => match get_commit(parent) {
                 Ok(commit) => {
                     *self = commit;
@@ -108,83 +733,447 @@ impl Iterator for Commit {
 }
 
 impl Commit {
+    // Returns the first line from the message of the commit.
+    pub fn subject(&self) -> &str {
+        self.contents.lines().next().unwrap_or_default()
+    }
+
     // Creates an empty commit with the given string as the ID of its parent.
     // This way it can be iterated through the Commit Iterator.
-    fn iter_as_parent(from: &String) -> Commit {
+    pub fn iter_as_parent(from: &Oid) -> Commit {
         Commit {
-            id: String::new(),
-            tree: String::new(),
-            parent: Some(from.to_owned()),
+            id: Oid::null(from.algorithm()),
+            tree: Oid::null(from.algorithm()),
+            parents: vec![from.to_owned()],
+            author: None,
+            committer: None,
             contents: String::new(),
         }
     }
 }
 
-fn parse_header_element(element: Option<&str>, key: &str) -> Option<String> {
-    match element {
-        Some(el) => {
-            let fields = el.split_whitespace().collect::<Vec<_>>();
-            if fields.len() == 2 && fields[0] == key {
-                Some(fields[1].to_owned())
-            } else {
-                None
+// Splits the text from a commit or a tag object into its headers and its
+// message. Headers are given as key and value pairs, and values spanning
+// multiple lines (e.g. signatures) have their continuation lines joined with a
+// newline.
+pub fn split_headers(text: &str) -> (Vec<(String, String)>, &str) {
+    let (header, message) = match text.split_once("\n\n") {
+        Some((header, message)) => (header, message),
+        None => (text.strip_suffix('\n').unwrap_or(text), ""),
+    };
+
+    let mut headers: Vec<(String, String)> = vec![];
+    for line in header.lines() {
+        if let Some(continuation) = line.strip_prefix(' ') {
+            if let Some((_, value)) = headers.last_mut() {
+                value.push('\n');
+                value.push_str(continuation);
             }
+            continue;
+        }
+
+        match line.split_once(' ') {
+            Some((key, value)) => headers.push((key.to_owned(), value.to_owned())),
+            None => headers.push((line.to_owned(), String::new())),
         }
-        None => None,
+    }
+
+    (headers, message)
+}
+
+// The reverse of `split_headers`.
+pub fn join_headers(headers: &[(String, String)], message: &str) -> String {
+    let mut text = String::new();
+
+    for (key, value) in headers {
+        text.push_str(&format!("{} {}\n", key, value.replace('\n', "\n ")));
+    }
+    text.push('\n');
+    text.push_str(message);
+
+    text
+}
+
+// Returns the ID of the object being pointed to by the given tag object.
+pub fn tag_target(obj: &Object) -> Result<Oid> {
+    let (headers, _) = split_headers(obj.text()?);
+
+    match headers.iter().find(|(key, _)| key == "object") {
+        Some((_, id)) => Oid::from_hex(id),
+        None => bail!("bad format for tag object"),
     }
 }
 
-fn get_commit(id: &String) -> Result<Commit> {
-    let obj = object::get(id).with_context(|| format!("while getting commit {}", id))?;
+pub fn get_commit(id: &Oid) -> Result<Commit> {
+    get_commit_from(object::store().as_ref(), id)
+}
 
-    let mut lines = obj.contents.lines();
-    let tree = parse_header_element(lines.next(), "tree").unwrap();
-    let parent = parse_header_element(lines.next(), "parent");
+// Same as `get_commit` but fetching the commit from the given store. Tag
+// objects are peeled until reaching the commit they point to.
+pub fn get_commit_from(store: &dyn ObjectStore, id: &Oid) -> Result<Commit> {
+    let obj =
+        object::get_from(store, id).with_context(|| format!("while getting commit {}", id))?;
+    if obj.kind == Kind::Tag {
+        return get_commit_from(store, &tag_target(&obj)?);
+    }
+    if obj.kind != Kind::Commit {
+        bail!("object '{}' is not a commit!", id);
+    }
 
-    if parent.is_some() {
-        lines.next();
+    let (headers, message) = split_headers(obj.text()?);
+    let mut tree = None;
+    let mut parents = vec![];
+    let mut author = None;
+    let mut committer = None;
+
+    for (key, value) in headers {
+        match key.as_str() {
+            "tree" => tree = Some(Oid::from_hex(&value)?),
+            "parent" => parents.push(Oid::from_hex(&value)?),
+            "author" => author = Some(value),
+            "committer" => committer = Some(value),
+            _ => {}
+        }
     }
-    let contents = lines.fold(String::new(), |acc, x| format!("{}{}", acc, x));
+
+    let tree = match tree {
+        Some(tree) => tree,
+        None => bail!("bad format for commit {}", id),
+    };
 
     Ok(Commit {
         id: id.to_owned(),
         tree,
-        parent,
-        contents,
+        parents,
+        author,
+        committer,
+        contents: message.to_owned(),
     })
 }
 
-pub fn log(from: &String) -> Result<()> {
+pub fn log(from: &Oid) -> Result<()> {
+    let store = object::store();
+    let mut next = Some(get_commit(from)?);
     let mut first = true;
 
-    for commit in Commit::iter_as_parent(from) {
+    // Follow first parents through the commit graph when there's one, so only
+    // the commits being printed are read.
+    while let Some(commit) = next {
         if !first {
             println!();
         }
+        println!("commit {}\n\n{}", commit.id, commit.contents.trim_end());
+        first = false;
 
-        println!("commit {}\n\n{}", commit.id, commit.contents);
+        let parent = match store.commit_graph().map(|graph| graph.get(&commit.id)) {
+            Some(Ok(Some(entry))) => entry.parents.first().copied(),
+            _ => commit.parents.first().copied(),
+        };
+        next = parent.map(|id| get_commit(&id)).transpose()?;
+    }
 
-        first = false;
+    Ok(())
+}
+
+// Date from a signature converted into the time zone it was recorded in.
+struct LocalDate<'a> {
+    // Days since the epoch.
+    days: i64,
+    year: i64,
+    month: i64,
+    day: i64,
+    // Seconds since midnight.
+    seconds: i64,
+    zone: &'a str,
+}
+
+// Parses the date from a signature (e.g. "1112911993 -0700").
+fn local_date(date: &str) -> Result<LocalDate<'_>> {
+    let (timestamp, zone) = match date.split_once(' ') {
+        Some((timestamp, zone)) if zone.len() == 5 => (timestamp.parse::<i64>()?, zone),
+        _ => bail!("bad date '{}'", date),
+    };
+    let minutes = zone[1..3].parse::<i64>()? * 60 + zone[3..].parse::<i64>()?;
+    let local = timestamp
+        + if zone.starts_with('-') {
+            -minutes
+        } else {
+            minutes
+        } * 60;
+
+    // Converting days since the epoch into a civil date, as explained in
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days.
+    let (days, seconds) = (local.div_euclid(86400), local.rem_euclid(86400));
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    Ok(LocalDate {
+        days,
+        year,
+        month,
+        day,
+        seconds,
+        zone,
+    })
+}
+
+// Formats the date from a signature (e.g. "1112911993 -0700") in the same way
+// as Git does (e.g. "Thu Apr 7 15:13:13 2005 -0700").
+pub fn format_date(date: &str) -> Result<String> {
+    const DAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+
+    let date = local_date(date)?;
+    Ok(format!(
+        "{} {} {} {:02}:{:02}:{:02} {} {}",
+        DAYS[date.days.rem_euclid(7) as usize],
+        MONTHS[date.month as usize - 1],
+        date.day,
+        date.seconds / 3600,
+        date.seconds % 3600 / 60,
+        date.seconds % 60,
+        date.year,
+        date.zone
+    ))
+}
+
+// Same as `format_date` but in the ISO 8601 like format from Git (e.g.
+// "2005-04-07 15:13:13 -0700").
+pub fn format_date_iso(date: &str) -> Result<String> {
+    let date = local_date(date)?;
+    Ok(format!(
+        "{}-{:02}-{:02} {:02}:{:02}:{:02} {}",
+        date.year,
+        date.month,
+        date.day,
+        date.seconds / 3600,
+        date.seconds % 3600 / 60,
+        date.seconds % 60,
+        date.zone
+    ))
+}
+
+// Splits a signature (e.g. "A U Thor <author@example.com> 1112911993 -0700")
+// into the identity and the date.
+pub fn split_signature(signature: &str) -> Option<(&str, &str)> {
+    match signature.rsplitn(3, ' ').collect::<Vec<_>>()[..] {
+        [_, _, identity] => Some((identity, &signature[identity.len() + 1..])),
+        _ => None,
+    }
+}
+
+// Prints the identity and the date from the given signature (e.g. the value
+// of an "author" header) with the given label.
+fn print_signature(label: &str, signature: &str) -> Result<()> {
+    match split_signature(signature) {
+        Some((identity, date)) => {
+            println!("{}: {}", label, identity);
+            println!("Date:   {}", format_date(date)?);
+        }
+        None => println!("{}: {}", label, signature),
     }
 
     Ok(())
 }
 
-pub fn checkout(id: &String) -> Result<()> {
-    let commit = get_commit(id)?;
+// Shows the object with the given name: commits are shown with their header,
+// their message and the diff against their parent; trees are listed; blobs
+// are printed as they are; and tags are shown with their annotation followed
+// by the object they point to.
+pub fn show(name: &str) -> Result<()> {
+    show_object(&ref_to_oid(name)?, name)
+}
+
+fn show_object(id: &Oid, name: &str) -> Result<()> {
+    let obj = object::get(id)?;
+
+    match obj.kind {
+        Kind::Blob => std::io::stdout().write_all(&obj.contents)?,
+        Kind::Tree => {
+            println!("tree {}\n", name);
+            for entry in tree::get_entries(&obj.contents, id.algorithm())? {
+                let slash = if entry.kind == Kind::Tree { "/" } else { "" };
+                println!("{}{}", entry.name, slash);
+            }
+        }
+        Kind::Tag => {
+            let (headers, message) = split_headers(obj.text()?);
+            for (key, value) in &headers {
+                match key.as_str() {
+                    "tag" => println!("tag {}", value),
+                    "tagger" => print_signature("Tagger", value)?,
+                    _ => {}
+                }
+            }
+            println!("\n{}\n", message.trim_end());
+
+            show_object(&tag_target(&obj)?, name)?;
+        }
+        Kind::Commit => show_commit(&get_commit(id)?)?,
+        Kind::None => bail!("unknown object kind for '{}'", id),
+    }
+
+    Ok(())
+}
+
+fn show_commit(commit: &Commit) -> Result<()> {
+    println!("commit {}", commit.id);
+    if commit.parents.len() > 1 {
+        let parents = commit
+            .parents
+            .iter()
+            .map(|p| p.abbrev(7))
+            .collect::<Vec<_>>();
+        println!("Merge: {}", parents.join(" "));
+    }
+    if let Some(author) = &commit.author {
+        print_signature("Author", author)?;
+    }
+    println!();
+    for line in commit.contents.trim_end().lines() {
+        println!("    {}", line);
+    }
+
+    // Merges are not diffed, as there's no single parent to compare against.
+    if commit.parents.len() > 1 {
+        return Ok(());
+    }
+
+    let store = object::store();
+    let old = match commit.parents.first() {
+        Some(parent) => tree::flatten(store.as_ref(), &get_commit(parent)?.tree)?,
+        None => tree::Files::new(),
+    };
+    let new = tree::flatten(store.as_ref(), &commit.tree)?;
+
+    let patch = diff::diff_files(&old, &new)?;
+    if !patch.is_empty() {
+        print!("\n{}", patch);
+    }
+
+    Ok(())
+}
+
+// Checks out the given revision. If it's the name of a branch, then HEAD will
+// point to this branch, otherwise HEAD will be detached into the given commit.
+pub fn checkout(name: &String) -> Result<()> {
+    let commit = get_commit(&ref_to_oid(name)?)?;
+    let previous = get_ref("HEAD")?.unwrap_or_else(|| Oid::null(commit.id.algorithm()));
 
     tree::read_tree(&commit.tree)?;
 
-    update_ref(&String::from("HEAD"), id)?;
+    let branch = format!("refs/heads/{}", name);
+    if get_ref(&branch)?.is_some() {
+        update_symbolic_ref("HEAD", &branch)?;
+    } else {
+        detach_head(&commit.id)?;
+    }
+
+    // The last argument tells the post-checkout hook that it was a checkout of
+    // a branch (or a commit) rather than of individual files.
+    hooks::run(
+        "post-checkout",
+        &[&previous.to_string(), &commit.id.to_string(), "1"],
+        None,
+    )?;
+
+    Ok(())
+}
+
+// Returns an error if the given name cannot be used for a reference. Since
+// references are stored as files inside of `.ruc`, this also makes sure that
+// they cannot point outside of it.
+pub fn check_ref_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && !name.starts_with('-')
+        && !name.starts_with('/')
+        && !name.ends_with('/')
+        && !name.ends_with(".lock")
+        && !name.contains("..")
+        && !name.contains("//")
+        && !name.split('/').any(|component| component.starts_with('.'))
+        && !name.contains("@{")
+        && !name
+            .chars()
+            .any(|c| c.is_whitespace() || c.is_control() || "~^:?*[\\".contains(c));
+
+    if !valid {
+        bail!("'{}' is not a valid reference name", name);
+    }
+
     Ok(())
 }
 
-pub fn create_tag(name: &String, id: &String) -> Result<()> {
-    update_ref(&format!("refs/tags/{}", name), id)?;
+// Returns the name of the branch being pointed to by HEAD, or None if HEAD is
+// detached.
+pub fn current_branch() -> Result<Option<String>> {
+    Ok(symbolic_ref("HEAD")?
+        .and_then(|target| target.strip_prefix("refs/heads/").map(|b| b.to_owned())))
+}
+
+// Creates a new branch with the given name pointing to the given commit.
+pub fn create_branch(name: &String, id: &Oid) -> Result<()> {
+    check_ref_name(name)?;
+
+    let branch = format!("refs/heads/{}", name);
+    if get_ref(&branch)?.is_some() {
+        bail!("a branch named '{}' already exists", name);
+    }
+
+    update_ref(&branch, &get_commit(id)?.id)
+}
+
+// Prints all the branches, highlighting the one being pointed to by HEAD.
+pub fn list_branches() -> Result<()> {
+    let current = current_branch()?;
+
+    for (name, _) in list_refs("refs/heads/")? {
+        let name = name.strip_prefix("refs/heads/").unwrap_or(&name);
+        let marker = if current.as_deref() == Some(name) {
+            "*"
+        } else {
+            " "
+        };
+
+        println!("{} {}", marker, name);
+    }
 
     Ok(())
 }
 
+// Creates a tag with the given name pointing to the given object. If a message
+// is given, then a tag object is created with it, and the tag points to this
+// object instead.
+pub fn create_tag(name: &str, id: &Oid, message: Option<&String>) -> Result<()> {
+    check_ref_name(name)?;
+
+    let target = match message {
+        Some(message) => {
+            let contents = format!(
+                "object {}\ntype {}\ntag {}\ntagger {}\n\n{}",
+                id,
+                object::get(id)?.kind,
+                name,
+                signature("committer")?,
+                message
+            );
+            object::hash_contents(contents.as_bytes(), Kind::Tag)?
+        }
+        None => *id,
+    };
+
+    update_ref(&format!("refs/tags/{}", name), &target)
+}
+
 pub fn graph() -> Result<()> {
     let paths = fs::read_dir(WORKING_DIR.join(RUC_DIR).join("refs").join("tags")).unwrap();
     let mut dot = String::from("digraph commits {\n");
@@ -195,9 +1184,12 @@ pub fn graph() -> Result<()> {
         let file = path.unwrap();
         let fname = file.file_name();
         let name = fname.to_str().unwrap();
-        let rf = get_ref(&format!("refs/tags/{}", &name.to_string()))?;
+        let rf = match get_ref(&format!("refs/tags/{}", &name.to_string()))? {
+            Some(rf) => rf,
+            None => continue,
+        };
 
-        commits.push(rf.clone());
+        commits.push(rf);
 
         dot.push_str(format!("\"{}\" [shape=note]\n", &name).as_str());
         dot.push_str(format!("\"{}\" -> \"{}\"\n", &name, rf).as_str());
@@ -205,7 +1197,7 @@ pub fn graph() -> Result<()> {
 
     for commit_id in commits {
         for commit in Commit::iter_as_parent(&commit_id) {
-            let abbreved = commit.id.get(0..12).unwrap_or(&commit.id);
+            let abbreved = commit.id.abbrev(12);
             dot.push_str(
                 format!(
                     "\"{}\" [shape=box style=filled label=\"{}\"]\n",
@@ -214,7 +1206,7 @@ pub fn graph() -> Result<()> {
                 .as_str(),
             );
 
-            if let Some(parent) = commit.parent {
+            for parent in &commit.parents {
                 dot.push_str(format!("\"{}\" -> \"{}\"", commit.id, parent).as_str());
             }
         }
@@ -0,0 +1,336 @@
+use crate::commit;
+use crate::init::{RUC_DIR, WORKING_DIR};
+use crate::object::{self, Kind, ObjectStore};
+use crate::oid::{HashAlgorithm, Oid};
+
+use anyhow::{bail, Context, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+// The commit graph is a file which caches the parents, the root tree, the
+// generation number and the date of commits, so walking the history does not
+// need to read and parse every commit object. It follows the format from Git:
+//
+//   - A header with the "CGPH" signature, the version (1), the hash version
+//     (1 for SHA-1, 2 for SHA-256), the number of chunks and the number of
+//     base graphs (always 0).
+//   - A table of contents with the ID and the offset of each chunk, ending
+//     with an entry with a zero ID and the offset where the last chunk ends.
+//   - The chunks themselves:
+//     - OIDF: 256 entries, where the nth one is the number of commits whose
+//       ID starts with a byte lower or equal than n.
+//     - OIDL: the IDs of all commits, sorted.
+//     - CDAT: for each commit, the ID of its tree, the positions of its first
+//       two parents (or NO_PARENT), and 8 bytes with the generation number on
+//       the upper 30 bits and the commit date on the lower 34.
+//     - EDGE: only if there are octopus merges. When a commit has more than
+//       two parents, its second parent in CDAT is an index into this list
+//       (with the EXTRA_EDGES bit set) where the rest of positions are, with
+//       the last one having the LAST_EDGE bit set.
+//   - The checksum of everything above.
+const SIGNATURE: &[u8] = b"CGPH";
+const VERSION: u8 = 1;
+const OIDF: &[u8] = b"OIDF";
+const OIDL: &[u8] = b"OIDL";
+const CDAT: &[u8] = b"CDAT";
+const EDGE: &[u8] = b"EDGE";
+const NO_PARENT: u32 = 0x70000000;
+const EXTRA_EDGES: u32 = 0x80000000;
+const LAST_EDGE: u32 = 0x80000000;
+
+// Path of the commit graph relative to the objects directory.
+pub const COMMIT_GRAPH: &str = "info/commit-graph";
+
+// Commit as stored in the commit graph. The generation number of a commit is
+// one more than the highest one from its parents, starting at one for root
+// commits, so a commit can never reach another one with a higher or equal
+// generation number.
+#[derive(Debug, Clone)]
+pub struct GraphCommit {
+    pub tree: Oid,
+    pub parents: Vec<Oid>,
+    pub generation: u32,
+    pub date: i64,
+}
+
+#[derive(Debug)]
+pub struct CommitGraph {
+    algorithm: HashAlgorithm,
+    ids: Vec<Oid>,
+    data: Vec<u8>,
+    edges: Vec<u8>,
+}
+
+fn hash_version(algorithm: HashAlgorithm) -> u8 {
+    match algorithm {
+        HashAlgorithm::Sha1 => 1,
+        HashAlgorithm::Sha256 => 2,
+    }
+}
+
+fn read_u32(data: &[u8], pos: usize) -> u32 {
+    u32::from_be_bytes(data[pos..pos + 4].try_into().unwrap())
+}
+
+impl CommitGraph {
+    // Reads the commit graph from the given path.
+    pub fn open(path: &Path, algorithm: HashAlgorithm) -> Result<CommitGraph> {
+        let data =
+            fs::read(path).with_context(|| format!("could not read '{}'", path.display()))?;
+        CommitGraph::parse(&data, algorithm).context("bad commit graph")
+    }
+
+    fn parse(data: &[u8], algorithm: HashAlgorithm) -> Result<CommitGraph> {
+        let len = algorithm.byte_len();
+        if data.len() < 8 + len || &data[..4] != SIGNATURE || data[4] != VERSION {
+            bail!("unknown header");
+        }
+        if data[5] != hash_version(algorithm) {
+            bail!("it does not use {}", algorithm);
+        }
+        let (contents, checksum) = data.split_at(data.len() - len);
+        if algorithm.hash(contents).as_bytes() != checksum {
+            bail!("wrong checksum");
+        }
+
+        // Collect the offsets from the table of contents: the end of each
+        // chunk is given by the offset of the next one.
+        let count = data[6] as usize;
+        if contents.len() < 8 + (count + 1) * 12 {
+            bail!("unexpected end of data");
+        }
+        let mut chunks = HashMap::new();
+        for idx in 0..count {
+            let entry = 8 + idx * 12;
+            let offset = |pos: usize| u64::from_be_bytes(data[pos..pos + 8].try_into().unwrap());
+            let (start, end) = (offset(entry + 4) as usize, offset(entry + 16) as usize);
+            if start > end || end > contents.len() {
+                bail!("bad offset for chunk");
+            }
+            chunks.insert(&data[entry..entry + 4], &data[start..end]);
+        }
+
+        let (oidl, cdat) = match (chunks.get(OIDL), chunks.get(CDAT)) {
+            (Some(oidl), Some(cdat)) => (*oidl, *cdat),
+            _ => bail!("missing chunks"),
+        };
+        if oidl.len() % len != 0 || cdat.len() != oidl.len() / len * (len + 16) {
+            bail!("bad size for chunks");
+        }
+
+        Ok(CommitGraph {
+            algorithm,
+            ids: oidl
+                .chunks(len)
+                .map(Oid::from_bytes)
+                .collect::<Result<Vec<_>>>()?,
+            data: cdat.to_vec(),
+            edges: chunks.get(EDGE).map(|e| e.to_vec()).unwrap_or_default(),
+        })
+    }
+
+    // Returns the number of commits in the graph.
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    fn id_at(&self, pos: u32) -> Result<Oid> {
+        match self.ids.get(pos as usize) {
+            Some(id) => Ok(*id),
+            None => bail!("bad commit graph: unknown commit position {}", pos),
+        }
+    }
+
+    // Returns the commit with the given ID, or None if it's not in the graph.
+    pub fn get(&self, id: &Oid) -> Result<Option<GraphCommit>> {
+        let pos = match self.ids.binary_search(id) {
+            Ok(pos) => pos,
+            Err(_) => return Ok(None),
+        };
+        let len = self.algorithm.byte_len();
+        let entry = &self.data[pos * (len + 16)..(pos + 1) * (len + 16)];
+
+        let mut parents = vec![];
+        let (first, second) = (read_u32(entry, len), read_u32(entry, len + 4));
+        if first != NO_PARENT {
+            parents.push(self.id_at(first)?);
+        }
+        if second & EXTRA_EDGES != 0 {
+            let mut edge = (second & !EXTRA_EDGES) as usize * 4;
+            loop {
+                if edge + 4 > self.edges.len() {
+                    bail!("bad commit graph: unexpected end of edges");
+                }
+                let value = read_u32(&self.edges, edge);
+                parents.push(self.id_at(value & !LAST_EDGE)?);
+                if value & LAST_EDGE != 0 {
+                    break;
+                }
+                edge += 4;
+            }
+        } else if second != NO_PARENT {
+            parents.push(self.id_at(second)?);
+        }
+
+        let (high, low) = (read_u32(entry, len + 8), read_u32(entry, len + 12));
+        Ok(Some(GraphCommit {
+            tree: Oid::from_bytes(&entry[..len])?,
+            parents,
+            generation: high >> 2,
+            date: ((high as i64 & 0x3) << 32) | low as i64,
+        }))
+    }
+}
+
+// Returns the committer date of the given commit as a timestamp.
+fn commit_date(commit: &commit::Commit) -> i64 {
+    commit
+        .committer
+        .as_deref()
+        .and_then(commit::split_signature)
+        .and_then(|(_, date)| date.split(' ').next())
+        .and_then(|timestamp| timestamp.parse::<i64>().ok())
+        .unwrap_or(0)
+}
+
+// Writes the commit graph for all the commits in the given store into the
+// given path, and returns the number of commits in it.
+pub fn write(store: &dyn ObjectStore, path: &Path) -> Result<usize> {
+    let algorithm = store.algorithm();
+    let mut ids = vec![];
+    for id in store.ids()? {
+        if object::get_from(store, &id)?.kind == Kind::Commit {
+            ids.push(id);
+        }
+    }
+    ids.sort();
+
+    let positions = ids
+        .iter()
+        .enumerate()
+        .map(|(pos, id)| (*id, pos as u32))
+        .collect::<HashMap<_, _>>();
+    let mut commits = Vec::with_capacity(ids.len());
+    for id in &ids {
+        let commit = commit::get_commit_from(store, id)?;
+        if let Some(parent) = commit.parents.iter().find(|p| !positions.contains_key(p)) {
+            bail!("commit {} has a missing parent {}", id, parent);
+        }
+        commits.push(commit);
+    }
+
+    // Compute the generation numbers, making sure that parents are always
+    // handled before their children.
+    let mut generations = vec![0u32; ids.len()];
+    for start in 0..ids.len() {
+        let mut pending = vec![start];
+        while let Some(&pos) = pending.last() {
+            if generations[pos] != 0 {
+                pending.pop();
+                continue;
+            }
+
+            let parents = commits[pos]
+                .parents
+                .iter()
+                .map(|p| positions[p] as usize)
+                .collect::<Vec<_>>();
+            let missing = parents
+                .iter()
+                .filter(|p| generations[**p] == 0)
+                .copied()
+                .collect::<Vec<_>>();
+            if missing.is_empty() {
+                generations[pos] = 1 + parents.iter().map(|p| generations[*p]).max().unwrap_or(0);
+                pending.pop();
+            } else {
+                pending.extend(missing);
+            }
+        }
+    }
+
+    let mut oidf = vec![];
+    for byte in 0..=255u8 {
+        let count = ids.iter().take_while(|id| id.as_bytes()[0] <= byte).count();
+        oidf.extend_from_slice(&(count as u32).to_be_bytes());
+    }
+
+    let mut oidl = vec![];
+    let mut cdat = vec![];
+    let mut edge = vec![];
+    for (pos, commit) in commits.iter().enumerate() {
+        oidl.extend_from_slice(ids[pos].as_bytes());
+        cdat.extend_from_slice(commit.tree.as_bytes());
+
+        let parents = commit
+            .parents
+            .iter()
+            .map(|p| positions[p])
+            .collect::<Vec<_>>();
+        let (first, second) = match parents[..] {
+            [] => (NO_PARENT, NO_PARENT),
+            [first] => (first, NO_PARENT),
+            [first, second] => (first, second),
+            [first, ..] => {
+                let start = (edge.len() / 4) as u32 | EXTRA_EDGES;
+                for (idx, parent) in parents[1..].iter().enumerate() {
+                    let last = if idx == parents.len() - 2 {
+                        LAST_EDGE
+                    } else {
+                        0
+                    };
+                    edge.extend_from_slice(&(parent | last).to_be_bytes());
+                }
+                (first, start)
+            }
+        };
+        cdat.extend_from_slice(&first.to_be_bytes());
+        cdat.extend_from_slice(&second.to_be_bytes());
+
+        let date = commit_date(commit).clamp(0, (1 << 34) - 1) as u64;
+        let generation = (generations[pos] as u64).min((1 << 30) - 1);
+        cdat.extend_from_slice(&((generation << 34) | date).to_be_bytes());
+    }
+
+    let mut chunks = vec![(OIDF, oidf), (OIDL, oidl), (CDAT, cdat)];
+    if !edge.is_empty() {
+        chunks.push((EDGE, edge));
+    }
+
+    let mut data = SIGNATURE.to_vec();
+    data.extend_from_slice(&[VERSION, hash_version(algorithm), chunks.len() as u8, 0]);
+    let mut offset = (8 + (chunks.len() + 1) * 12) as u64;
+    for (name, contents) in &chunks {
+        data.extend_from_slice(name);
+        data.extend_from_slice(&offset.to_be_bytes());
+        offset += contents.len() as u64;
+    }
+    data.extend_from_slice(&[0; 4]);
+    data.extend_from_slice(&offset.to_be_bytes());
+    for (_, contents) in &chunks {
+        data.extend_from_slice(contents);
+    }
+    let checksum = algorithm.hash(&data);
+    data.extend_from_slice(checksum.as_bytes());
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, data).context("could not write the commit graph")?;
+
+    Ok(ids.len())
+}
+
+// Writes the commit graph for the current repository.
+pub fn write_command() -> Result<()> {
+    let path = WORKING_DIR.join(RUC_DIR).join("objects").join(COMMIT_GRAPH);
+    let count = write(object::store().as_ref(), &path)?;
+
+    println!("Wrote a commit graph with {} commits", count);
+    Ok(())
+}
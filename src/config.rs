@@ -0,0 +1,181 @@
+use crate::init::{RUC_DIR, WORKING_DIR};
+
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Configuration of a repository, stored in a Git-like format under
+// `.ruc/config`. That is:
+//
+//   [core]
+//       objectformat = sha1
+//   [remote "origin"]
+//       url = /some/path
+//
+// Values are referred to with a dotted name which contains the section, the
+// optional subsection and the key (e.g. `core.objectformat` or
+// `remote.origin.url`).
+#[derive(Debug, Default)]
+pub struct Config {
+    path: PathBuf,
+    entries: Vec<(String, String, String)>,
+}
+
+// Splits the given dotted name into its section and its key.
+fn split_name(name: &str) -> Result<(&str, &str)> {
+    match name.rsplit_once('.') {
+        Some((section, key)) if !section.is_empty() && !key.is_empty() => Ok((section, key)),
+        _ => bail!("bad config name '{}'", name),
+    }
+}
+
+// Parses a section header such as `[remote "origin"]` into its dotted form
+// (e.g. `remote.origin`).
+fn parse_section(line: &str) -> Option<String> {
+    let inner = line.strip_prefix('[')?.strip_suffix(']')?.trim();
+
+    match inner.split_once(char::is_whitespace) {
+        Some((section, sub)) => {
+            let sub = sub.trim().strip_prefix('"')?.strip_suffix('"')?;
+            Some(format!("{}.{}", section.to_lowercase(), sub))
+        }
+        None => Some(inner.to_lowercase()),
+    }
+}
+
+// Returns the header to be written for the given dotted section.
+fn format_section(section: &str) -> String {
+    match section.split_once('.') {
+        Some((name, sub)) => format!("[{} \"{}\"]", name, sub),
+        None => format!("[{}]", section),
+    }
+}
+
+impl Config {
+    // Loads the configuration stored in the given path. If the file does not
+    // exist yet, then an empty configuration is returned.
+    pub fn open(path: &Path) -> Result<Config> {
+        let mut config = Config {
+            path: path.to_path_buf(),
+            entries: vec![],
+        };
+
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(config),
+            Err(e) => return Err(e).context("while reading the configuration"),
+        };
+
+        let mut section: Option<String> = None;
+        for (idx, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+
+            if line.starts_with('[') {
+                section = parse_section(line);
+                if section.is_none() {
+                    bail!("bad config line {} in {}", idx + 1, path.display());
+                }
+                continue;
+            }
+
+            match (&section, line.split_once('=')) {
+                (Some(s), Some((key, value))) => config.entries.push((
+                    s.to_owned(),
+                    key.trim().to_lowercase(),
+                    value.trim().to_owned(),
+                )),
+                _ => bail!("bad config line {} in {}", idx + 1, path.display()),
+            }
+        }
+
+        Ok(config)
+    }
+
+    // Returns the value for the given dotted name, if any.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        let (section, key) = split_name(name).ok()?;
+        let key = key.to_lowercase();
+
+        self.entries
+            .iter()
+            .rev()
+            .find(|(s, k, _)| s == section && *k == key)
+            .map(|(_, _, v)| v.as_str())
+    }
+
+    // Sets the given value for the given dotted name. Call `save` in order to
+    // write it down into disk.
+    pub fn set(&mut self, name: &str, value: &str) -> Result<()> {
+        let (section, key) = split_name(name)?;
+        let key = key.to_lowercase();
+
+        match self
+            .entries
+            .iter_mut()
+            .find(|(s, k, _)| s == section && *k == key)
+        {
+            Some(entry) => entry.2 = value.to_owned(),
+            None => {
+                // Keep entries from the same section together.
+                let pos = self
+                    .entries
+                    .iter()
+                    .rposition(|(s, _, _)| s == section)
+                    .map_or(self.entries.len(), |p| p + 1);
+                self.entries
+                    .insert(pos, (section.to_owned(), key, value.to_owned()));
+            }
+        }
+
+        Ok(())
+    }
+
+    // Removes all the values from the given dotted section (e.g.
+    // `remote.origin`).
+    pub fn remove_section(&mut self, section: &str) {
+        self.entries.retain(|(s, _, _)| s != section);
+    }
+
+    // Returns the subsections that exist for the given section. For example,
+    // for `remote` it returns the names of all the configured remotes.
+    pub fn subsections(&self, section: &str) -> Vec<String> {
+        let prefix = format!("{}.", section);
+        let mut res: Vec<String> = vec![];
+
+        for (s, _, _) in &self.entries {
+            if let Some(sub) = s.strip_prefix(&prefix) {
+                if !res.iter().any(|r| r == sub) {
+                    res.push(sub.to_owned());
+                }
+            }
+        }
+
+        res
+    }
+
+    // Writes the configuration into disk.
+    pub fn save(&self) -> Result<()> {
+        let mut contents = String::new();
+        let mut current: Option<&str> = None;
+
+        for (section, key, value) in &self.entries {
+            if current != Some(section.as_str()) {
+                contents.push_str(&format_section(section));
+                contents.push('\n');
+                current = Some(section.as_str());
+            }
+            contents.push_str(&format!("\t{} = {}\n", key, value));
+        }
+
+        fs::write(&self.path, contents)
+            .with_context(|| format!("while writing {}", self.path.display()))
+    }
+}
+
+// Returns the configuration for the current repository.
+pub fn load() -> Result<Config> {
+    Config::open(&WORKING_DIR.join(RUC_DIR).join("config"))
+}
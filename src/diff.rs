@@ -0,0 +1,296 @@
+use crate::object;
+use crate::oid::Oid;
+use crate::tree::{Files, Mode};
+
+use anyhow::Result;
+use std::collections::BTreeSet;
+
+// Number of unchanged lines shown around each change.
+const CONTEXT: usize = 3;
+
+// Single step in order to turn a sequence into another one. Indexes point to
+// the elements from the old sequence (`a`) and/or the new one (`b`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edit {
+    Equal(usize, usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+// Returns the shortest list of edits which turns `a` into `b`, as given by the
+// algorithm from Eugene W. Myers ("An O(ND) Difference Algorithm and Its
+// Variations"). This is the linear space variant from the same paper: instead
+// of recording every step in order to walk back the path, it looks for the
+// middle snake of the shortest path from both ends at once and then splits the
+// problem in two around it.
+pub fn diff<T: PartialEq>(a: &[T], b: &[T]) -> Vec<Edit> {
+    let mut edits = vec![];
+    compare(a, b, (0, 0), &mut edits);
+    edits
+}
+
+// Pushes the edits turning `a` into `b`, which start at the given offsets in
+// the sequences given to `diff`.
+fn compare<T: PartialEq>(a: &[T], b: &[T], offset: (usize, usize), edits: &mut Vec<Edit>) {
+    // Common prefixes and suffixes are trivial, and skipping them also means
+    // that both sides are different at their ends in the rest.
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    let (x0, y0) = offset;
+    edits.extend((0..prefix).map(|i| Edit::Equal(x0 + i, y0 + i)));
+
+    let (a_mid, b_mid) = (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+    let (x0, y0) = (x0 + prefix, y0 + prefix);
+    if a_mid.is_empty() {
+        edits.extend((0..b_mid.len()).map(|i| Edit::Insert(y0 + i)));
+    } else if b_mid.is_empty() {
+        edits.extend((0..a_mid.len()).map(|i| Edit::Delete(x0 + i)));
+    } else {
+        // Both sides are not empty and differ at their ends, so at least two
+        // changes are needed and both halves are smaller than the whole.
+        let ((x, y), (u, v)) = middle_snake(a_mid, b_mid);
+        compare(&a_mid[..x], &b_mid[..y], (x0, y0), edits);
+        edits.extend((0..u - x).map(|i| Edit::Equal(x0 + x + i, y0 + y + i)));
+        compare(&a_mid[u..], &b_mid[v..], (x0 + u, y0 + v), edits);
+    }
+
+    let (x0, y0) = (x0 + a_mid.len(), y0 + b_mid.len());
+    edits.extend((0..suffix).map(|i| Edit::Equal(x0 + i, y0 + i)));
+}
+
+// Returns the start and the end of the snake in the middle of a shortest path
+// from the beginning of `a` and `b` to their end. Paths are explored forward
+// from the beginning and backward from the end, keeping for each diagonal `k`
+// only the furthest point reached, until both directions overlap.
+fn middle_snake<T: PartialEq>(a: &[T], b: &[T]) -> ((usize, usize), (usize, usize)) {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let delta = n - m;
+    let max = (n + m + 1) / 2;
+    let offset = max + 1;
+    // Forward paths are stored by their `x`, and backward ones by how far they
+    // went from the end of `a`, with diagonals counted from the end as well.
+    let mut forward = vec![0isize; 2 * max as usize + 3];
+    let mut backward = vec![0isize; 2 * max as usize + 3];
+
+    for d in 0..=max {
+        for k in (-d..=d).step_by(2) {
+            let idx = (k + offset) as usize;
+            let mut x = if k == -d || (k != d && forward[idx - 1] < forward[idx + 1]) {
+                forward[idx + 1]
+            } else {
+                forward[idx - 1] + 1
+            };
+            let mut y = x - k;
+            let start = (x as usize, y as usize);
+
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            forward[idx] = x;
+
+            let reverse_k = delta - k;
+            if delta % 2 != 0
+                && (-(d - 1)..=d - 1).contains(&reverse_k)
+                && x + backward[(reverse_k + offset) as usize] >= n
+            {
+                return (start, (x as usize, y as usize));
+            }
+        }
+
+        for k in (-d..=d).step_by(2) {
+            let idx = (k + offset) as usize;
+            let mut x = if k == -d || (k != d && backward[idx - 1] < backward[idx + 1]) {
+                backward[idx + 1]
+            } else {
+                backward[idx - 1] + 1
+            };
+            let mut y = x - k;
+            let end = ((n - x) as usize, (m - y) as usize);
+
+            while x < n && y < m && a[(n - x - 1) as usize] == b[(m - y - 1) as usize] {
+                x += 1;
+                y += 1;
+            }
+            backward[idx] = x;
+
+            let forward_k = delta - k;
+            if delta % 2 == 0
+                && (-d..=d).contains(&forward_k)
+                && x + forward[(forward_k + offset) as usize] >= n
+            {
+                return (((n - x) as usize, (m - y) as usize), end);
+            }
+        }
+    }
+
+    unreachable!("the paths from both ends always meet")
+}
+
+// Splits the given contents into lines, keeping their newline character so
+// a missing newline at the end of a file also counts as a change.
+pub fn lines(contents: &str) -> Vec<&str> {
+    contents.split_inclusive('\n').collect()
+}
+
+// Returns true if the given contents look like binary data, which is what Git
+// does as well: checking for a NUL byte at the beginning of the contents.
+pub fn is_binary(contents: &[u8]) -> bool {
+    contents.iter().take(8000).any(|&b| b == b'\0')
+}
+
+fn push_line(out: &mut String, marker: char, line: &str) {
+    out.push(marker);
+    out.push_str(line);
+    if !line.ends_with('\n') {
+        out.push_str("\n\\ No newline at end of file\n");
+    }
+}
+
+// Returns the hunks in the unified format which turn the text from `a` into the
+// text from `b`.
+pub fn unified(a: &str, b: &str) -> String {
+    let (old, new) = (lines(a), lines(b));
+    let edits = diff(&old, &new);
+    let mut out = String::new();
+
+    // Group changes which are close enough to share their context.
+    let changes = edits
+        .iter()
+        .enumerate()
+        .filter(|(_, e)| !matches!(e, Edit::Equal(_, _)))
+        .map(|(idx, _)| idx)
+        .collect::<Vec<_>>();
+    let mut groups: Vec<(usize, usize)> = vec![];
+    for idx in changes {
+        match groups.last_mut() {
+            Some((_, end)) if idx <= *end + 2 * CONTEXT + 1 => *end = idx,
+            _ => groups.push((idx, idx)),
+        }
+    }
+
+    for (first, last) in groups {
+        let start = first.saturating_sub(CONTEXT);
+        let end = (last + CONTEXT + 1).min(edits.len());
+        let hunk = &edits[start..end];
+
+        // Line numbers start at 1, but an empty side refers to the line right
+        // before the hunk.
+        let (mut old_start, mut new_start) = (None, None);
+        let (mut old_len, mut new_len) = (0, 0);
+        let mut old_before = 0;
+        let mut new_before = 0;
+        for edit in &edits[..start] {
+            match edit {
+                Edit::Equal(_, _) => {
+                    old_before += 1;
+                    new_before += 1;
+                }
+                Edit::Delete(_) => old_before += 1,
+                Edit::Insert(_) => new_before += 1,
+            }
+        }
+
+        let mut body = String::new();
+        for edit in hunk {
+            match *edit {
+                Edit::Equal(x, y) => {
+                    old_start.get_or_insert(x + 1);
+                    new_start.get_or_insert(y + 1);
+                    old_len += 1;
+                    new_len += 1;
+                    push_line(&mut body, ' ', old[x]);
+                }
+                Edit::Delete(x) => {
+                    old_start.get_or_insert(x + 1);
+                    old_len += 1;
+                    push_line(&mut body, '-', old[x]);
+                }
+                Edit::Insert(y) => {
+                    new_start.get_or_insert(y + 1);
+                    new_len += 1;
+                    push_line(&mut body, '+', new[y]);
+                }
+            }
+        }
+
+        let range = |start: Option<usize>, before: usize, len: usize| match len {
+            1 => format!("{}", start.unwrap()),
+            0 => format!("{},0", before),
+            _ => format!("{},{}", start.unwrap(), len),
+        };
+        out.push_str(&format!(
+            "@@ -{} +{} @@\n",
+            range(old_start, old_before, old_len),
+            range(new_start, new_before, new_len)
+        ));
+        out.push_str(&body);
+    }
+
+    out
+}
+
+// Returns the patch which turns the files from `old` into the files from `new`,
+// in the same format as `git diff`.
+pub fn diff_files(old: &Files, new: &Files) -> Result<String> {
+    let mut out = String::new();
+    let paths = old.keys().chain(new.keys()).collect::<BTreeSet<_>>();
+
+    for path in paths {
+        let (before, after) = (old.get(path), new.get(path));
+        if before == after {
+            continue;
+        }
+
+        let abbrev = |entry: Option<&(Mode, Oid)>| match entry {
+            Some((_, id)) => id.abbrev(7),
+            None => "0000000".to_owned(),
+        };
+        out.push_str(&format!("diff --git a/{} b/{}\n", path, path));
+
+        match (before, after) {
+            (None, Some((mode, _))) => out.push_str(&format!("new file mode {}\n", mode)),
+            (Some((mode, _)), None) => out.push_str(&format!("deleted file mode {}\n", mode)),
+            (Some((old_mode, _)), Some((new_mode, _))) if old_mode != new_mode => {
+                out.push_str(&format!("old mode {}\nnew mode {}\n", old_mode, new_mode));
+            }
+            _ => {}
+        }
+
+        let same_id = before.map(|e| e.1) == after.map(|e| e.1);
+        if same_id {
+            continue;
+        }
+        match (before, after) {
+            (Some((old_mode, _)), Some((new_mode, _))) if old_mode == new_mode => out.push_str(
+                &format!("index {}..{} {}\n", abbrev(before), abbrev(after), old_mode),
+            ),
+            _ => out.push_str(&format!("index {}..{}\n", abbrev(before), abbrev(after))),
+        }
+
+        let contents = |entry: Option<&(Mode, Oid)>| -> Result<Vec<u8>> {
+            match entry {
+                Some((_, id)) => Ok(object::get(id)?.contents),
+                None => Ok(vec![]),
+            }
+        };
+        let (a, b) = (contents(before)?, contents(after)?);
+        let a_name = before.map_or("/dev/null".to_owned(), |_| format!("a/{}", path));
+        let b_name = after.map_or("/dev/null".to_owned(), |_| format!("b/{}", path));
+
+        match (std::str::from_utf8(&a), std::str::from_utf8(&b)) {
+            (Ok(a), Ok(b)) if !is_binary(a.as_bytes()) && !is_binary(b.as_bytes()) => {
+                out.push_str(&format!("--- {}\n+++ {}\n", a_name, b_name));
+                out.push_str(&unified(a, b));
+            }
+            _ => out.push_str(&format!("Binary files {} and {} differ\n", a_name, b_name)),
+        }
+    }
+
+    Ok(out)
+}
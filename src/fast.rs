@@ -0,0 +1,607 @@
+use crate::commit::{self, Commit};
+use crate::object::{self, Kind};
+use crate::oid::Oid;
+use crate::tree::{self, Files, Mode};
+
+use anyhow::{bail, Context, Result};
+use std::collections::{HashMap, HashSet};
+use std::io::prelude::*;
+use std::io::BufWriter;
+use std::str::FromStr;
+
+// Streams in the format from `git fast-import`, which is understood by the
+// tools migrating history between version control systems. A stream is a list
+// of commands such as:
+//
+//   blob
+//   mark :1
+//   data 2
+//   a
+//
+//   commit refs/heads/main
+//   mark :2
+//   committer C O Mitter <committer@example.com> 1112911993 -0700
+//   data 5
+//   First
+//   M 100644 :1 a.txt
+//
+// Objects are referred to through marks (e.g. ":1"), which are given by the
+// stream itself, so it doesn't depend on object IDs.
+
+// Quotes the given path as expected by fast-import if needed, that is, if it
+// has characters which would otherwise be ambiguous.
+fn quote_path(path: &str) -> String {
+    if !path.starts_with('"') && !path.contains(['\n', '\\']) {
+        return path.to_owned();
+    }
+
+    let mut res = String::from("\"");
+    for c in path.chars() {
+        match c {
+            '"' => res.push_str("\\\""),
+            '\\' => res.push_str("\\\\"),
+            '\n' => res.push_str("\\n"),
+            _ => res.push(c),
+        }
+    }
+    res.push('"');
+
+    res
+}
+
+fn write_data(out: &mut impl Write, data: &[u8]) -> Result<()> {
+    writeln!(out, "data {}", data.len())?;
+    out.write_all(data)?;
+    writeln!(out)?;
+
+    Ok(())
+}
+
+// Returns the commits reachable from the given tips, with parents always
+// coming before their children. Each commit comes along with the reference
+// from which it was first reached.
+fn topological_order(tips: &[(String, Oid)]) -> Result<Vec<(String, Commit)>> {
+    let mut res = vec![];
+    let mut seen = HashSet::new();
+    let mut pending: Vec<(String, Oid, Option<Commit>)> = tips
+        .iter()
+        .rev()
+        .map(|(name, id)| (name.to_owned(), *id, None))
+        .collect();
+
+    while let Some((name, id, commit)) = pending.pop() {
+        if let Some(commit) = commit {
+            res.push((name, commit));
+            continue;
+        }
+
+        let commit = commit::get_commit(&id)?;
+        if !seen.insert(commit.id) {
+            continue;
+        }
+
+        let parents = commit.parents.clone();
+        pending.push((name.clone(), commit.id, Some(commit)));
+        for parent in parents.iter().rev() {
+            if !seen.contains(parent) {
+                pending.push((name.clone(), *parent, None));
+            }
+        }
+    }
+
+    Ok(res)
+}
+
+// Writes a stream with the history of the given references into the standard
+// output. If no references are given, then all branches and tags are exported.
+pub fn export(names: &[String]) -> Result<()> {
+    let refs = if names.is_empty() {
+        let mut refs = commit::list_refs("refs/heads/")?;
+        refs.extend(commit::list_refs("refs/tags/")?);
+        refs
+    } else {
+        let mut refs = vec![];
+        for name in names {
+            match commit::full_ref_name(name)? {
+                Some(full_name) => {
+                    let id = commit::get_ref(&full_name)?.unwrap();
+                    refs.push((full_name, id));
+                }
+                None => bail!("'{}' is not a reference", name),
+            }
+        }
+        refs
+    };
+
+    let tips = refs
+        .iter()
+        .map(|(name, id)| Ok((name.to_owned(), commit::get_commit(id)?.id)))
+        .collect::<Result<Vec<_>>>()?;
+
+    let store = object::store();
+    let stdout = std::io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    let mut marks: HashMap<Oid, usize> = HashMap::new();
+
+    for (name, commit) in topological_order(&tips)? {
+        let old = match commit.parents.first() {
+            Some(parent) => tree::flatten(store.as_ref(), &commit::get_commit(parent)?.tree)?,
+            None => Files::new(),
+        };
+        let new = tree::flatten(store.as_ref(), &commit.tree)?;
+
+        // Blobs go first, so they can be referred to by the commit.
+        let modified = new
+            .iter()
+            .filter(|(path, entry)| old.get(*path) != Some(entry))
+            .collect::<Vec<_>>();
+        for (_, (_, id)) in &modified {
+            if !marks.contains_key(id) {
+                let mark = marks.len() + 1;
+                marks.insert(*id, mark);
+
+                writeln!(out, "blob\nmark :{}", mark)?;
+                write_data(&mut out, &object::get(id)?.contents)?;
+            }
+        }
+
+        // Root commits would otherwise get the current tip of the reference
+        // as their parent.
+        if commit.parents.is_empty() {
+            writeln!(out, "reset {}", name)?;
+        }
+
+        let mark = marks.len() + 1;
+        marks.insert(commit.id, mark);
+
+        writeln!(out, "commit {}\nmark :{}", name, mark)?;
+        if let Some(author) = &commit.author {
+            writeln!(out, "author {}", author)?;
+        }
+        writeln!(
+            out,
+            "committer {}",
+            commit
+                .committer
+                .as_deref()
+                .unwrap_or(commit::UNKNOWN_IDENTITY)
+        )?;
+        write_data(&mut out, commit.contents.as_bytes())?;
+
+        for (idx, parent) in commit.parents.iter().enumerate() {
+            let command = if idx == 0 { "from" } else { "merge" };
+            writeln!(out, "{} :{}", command, marks[parent])?;
+        }
+        for path in old.keys().filter(|path| !new.contains_key(*path)) {
+            writeln!(out, "D {}", quote_path(path))?;
+        }
+        for (path, (mode, id)) in modified {
+            writeln!(out, "M {} :{} {}", mode, marks[id], quote_path(path))?;
+        }
+        writeln!(out)?;
+    }
+
+    // Finally, make sure that each reference points to the right commit, and
+    // write the tag objects.
+    for (name, id) in &refs {
+        let obj = object::get(id)?;
+        let target = commit::get_commit(id)?.id;
+
+        match name.strip_prefix("refs/tags/") {
+            Some(tag) if obj.kind == Kind::Tag => {
+                let (headers, message) = commit::split_headers(obj.text()?);
+
+                writeln!(out, "tag {}\nfrom :{}", tag, marks[&target])?;
+                if let Some((_, tagger)) = headers.iter().find(|(key, _)| key == "tagger") {
+                    writeln!(out, "tagger {}", tagger)?;
+                }
+                write_data(&mut out, message.as_bytes())?;
+            }
+            _ => writeln!(out, "reset {}\nfrom :{}\n", name, marks[&target])?,
+        }
+    }
+
+    out.flush()?;
+
+    Ok(())
+}
+
+// Reader for the lines of a stream. Comments are skipped.
+struct Parser<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    // Returns the next line without consuming it.
+    fn peek(&mut self) -> Result<Option<&'a str>> {
+        loop {
+            if self.pos >= self.data.len() {
+                return Ok(None);
+            }
+
+            let rest = &self.data[self.pos..];
+            let end = rest.iter().position(|&b| b == b'\n').unwrap_or(rest.len());
+            let line = std::str::from_utf8(&rest[..end]).context("bad encoding for stream")?;
+
+            if line.starts_with('#') {
+                self.pos += end + 1;
+                continue;
+            }
+
+            return Ok(Some(line));
+        }
+    }
+
+    // Returns the next line, consuming it.
+    fn next(&mut self) -> Result<Option<&'a str>> {
+        let line = self.peek()?;
+        if let Some(line) = line {
+            self.pos += line.len() + 1;
+        }
+
+        Ok(line)
+    }
+
+    // Consumes the next line if it's the given command, returning its
+    // argument.
+    fn optional(&mut self, command: &str) -> Result<Option<&'a str>> {
+        match self.peek()?.and_then(|line| line.strip_prefix(command)) {
+            Some(arg) if arg.is_empty() || arg.starts_with(' ') => {
+                self.next()?;
+                Ok(Some(arg.trim_start()))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    // Reads a `data` command, which is given either with the exact number of
+    // bytes or with a delimiter (i.e. "data <<EOF").
+    fn data(&mut self) -> Result<Vec<u8>> {
+        let arg = match self.optional("data")? {
+            Some(arg) => arg,
+            None => bail!("expected a data command"),
+        };
+
+        if let Some(delimiter) = arg.strip_prefix("<<") {
+            let mut res = vec![];
+            loop {
+                match self.next()? {
+                    Some(line) if line == delimiter => return Ok(res),
+                    Some(line) => {
+                        res.extend_from_slice(line.as_bytes());
+                        res.push(b'\n');
+                    }
+                    None => bail!("unexpected end of stream in data"),
+                }
+            }
+        }
+
+        let len = arg
+            .parse::<usize>()
+            .with_context(|| format!("bad length for data '{}'", arg))?;
+        let res = match self.data.get(self.pos..self.pos + len) {
+            Some(data) => data.to_vec(),
+            None => bail!("unexpected end of stream in data"),
+        };
+        self.pos += len;
+
+        // There can be an optional newline after the data.
+        if self.data.get(self.pos) == Some(&b'\n') {
+            self.pos += 1;
+        }
+
+        Ok(res)
+    }
+}
+
+// Returns an error if the given path cannot be stored in a tree, since it would
+// either produce a broken tree (e.g. "a//b") or point outside of the working
+// directory once checked out (e.g. "../a").
+fn check_path(path: &str) -> Result<()> {
+    for component in path.split('/') {
+        tree::check_entry_name(component).with_context(|| format!("invalid path '{}'", path))?;
+    }
+
+    Ok(())
+}
+
+// Splits the given path from the rest of the line. Paths can be quoted, and
+// otherwise they go until the next space if `last` is false, or until the end
+// of the line otherwise.
+fn parse_path(line: &str, last: bool) -> Result<(String, &str)> {
+    if let Some(quoted) = line.strip_prefix('"') {
+        let mut res = String::new();
+        let mut chars = quoted.char_indices();
+
+        while let Some((idx, c)) = chars.next() {
+            match c {
+                '"' => {
+                    check_path(&res)?;
+                    return Ok((res, quoted[idx + 1..].trim_start()));
+                }
+                '\\' => match chars.next() {
+                    Some((_, 'n')) => res.push('\n'),
+                    Some((_, 't')) => res.push('\t'),
+                    Some((_, c)) => res.push(c),
+                    None => break,
+                },
+                _ => res.push(c),
+            }
+        }
+
+        bail!("bad quoted path '{}'", line);
+    }
+
+    let (path, rest) = match line.split_once(' ') {
+        Some((path, rest)) if !last => (path, rest),
+        _ => (line, ""),
+    };
+    check_path(path)?;
+
+    Ok((path.to_owned(), rest))
+}
+
+// Removes the given path from the given files, which can be either a file or a
+// directory.
+fn remove_path(files: &mut Files, path: &str) {
+    let prefix = format!("{}/", path);
+    files.retain(|other, _| other != path && !other.starts_with(&prefix));
+}
+
+// State of an import: marks seen so far and the tips of the references being
+// updated.
+#[derive(Default)]
+struct Import {
+    marks: HashMap<String, Oid>,
+    refs: HashMap<String, Option<Oid>>,
+    objects: usize,
+}
+
+impl Import {
+    // Returns the object for the given mark, object ID or reference.
+    fn resolve(&self, name: &str) -> Result<Oid> {
+        if let Some(mark) = name.strip_prefix(':') {
+            return match self.marks.get(mark) {
+                Some(id) => Ok(*id),
+                None => bail!("unknown mark ':{}'", mark),
+            };
+        }
+        if let Ok(id) = Oid::from_hex(name) {
+            return Ok(id);
+        }
+
+        for candidate in [name.to_owned(), format!("refs/heads/{}", name)] {
+            if let Some(Some(id)) = self.refs.get(&candidate) {
+                return Ok(*id);
+            }
+        }
+
+        commit::ref_to_oid(name)
+    }
+
+    fn store(&mut self, mark: Option<&str>, contents: &[u8], kind: Kind) -> Result<Oid> {
+        let id = object::hash_contents(contents, kind)?;
+
+        if let Some(mark) = mark {
+            self.marks
+                .insert(mark.trim_start_matches(':').to_owned(), id);
+        }
+        self.objects += 1;
+
+        Ok(id)
+    }
+
+    fn commit(&mut self, parser: &mut Parser, name: &str) -> Result<()> {
+        let mark = parser.optional("mark")?;
+        parser.optional("original-oid")?;
+        let author = parser.optional("author")?;
+        let committer = match parser.optional("committer")? {
+            Some(committer) => committer,
+            None => bail!("missing committer for commit on {}", name),
+        };
+        parser.optional("encoding")?;
+        let message = String::from_utf8(parser.data()?).context("bad encoding for message")?;
+
+        // Without an explicit parent, the commit goes on top of the current
+        // tip of the reference.
+        let mut parents = vec![];
+        match parser.optional("from")? {
+            Some(from) => parents.push(self.resolve(from)?),
+            None => match self.refs.get(name) {
+                Some(tip) => parents.extend(*tip),
+                None => parents.extend(commit::get_ref(name)?),
+            },
+        }
+        while let Some(merge) = parser.optional("merge")? {
+            parents.push(self.resolve(merge)?);
+        }
+
+        let mut files = match parents.first() {
+            Some(parent) => {
+                tree::flatten(object::store().as_ref(), &commit::get_commit(parent)?.tree)?
+            }
+            None => Files::new(),
+        };
+
+        while let Some(line) = parser.peek()? {
+            if line == "deleteall" {
+                parser.next()?;
+                files.clear();
+            } else if let Some(rest) = line.strip_prefix("M ") {
+                parser.next()?;
+
+                let (mode, rest) = rest.split_once(' ').unwrap_or((rest, ""));
+                let (dataref, rest) = rest.split_once(' ').unwrap_or((rest, ""));
+                let (path, _) = parse_path(rest, true)?;
+                let mode = match mode {
+                    "644" => Mode::Regular,
+                    "755" => Mode::Executable,
+                    "160000" => bail!("submodules are not supported ('{}')", path),
+                    _ => Mode::from_str(mode)?,
+                };
+                if mode == Mode::Directory {
+                    bail!("directories cannot be modified ('{}')", path);
+                }
+
+                let id = if dataref == "inline" {
+                    let data = parser.data()?;
+                    self.store(None, &data, Kind::Blob)?
+                } else {
+                    self.resolve(dataref)?
+                };
+
+                // Whatever was in the way, either a file where a directory is
+                // expected or the other way around, goes away.
+                remove_path(&mut files, &path);
+                let mut parent = path.as_str();
+                while let Some((dir, _)) = parent.rsplit_once('/') {
+                    files.remove(dir);
+                    parent = dir;
+                }
+                files.insert(path, (mode, id));
+            } else if let Some(rest) = line.strip_prefix("D ") {
+                parser.next()?;
+
+                let (path, _) = parse_path(rest, true)?;
+                remove_path(&mut files, &path);
+            } else if line.starts_with("C ") || line.starts_with("R ") {
+                parser.next()?;
+
+                let (source, rest) = parse_path(&line[2..], false)?;
+                let (destination, _) = parse_path(rest, true)?;
+                let prefix = format!("{}/", source);
+                let copied = files
+                    .iter()
+                    .filter_map(|(path, entry)| {
+                        if *path == source {
+                            Some((destination.clone(), *entry))
+                        } else {
+                            path.strip_prefix(&prefix)
+                                .map(|rest| (format!("{}/{}", destination, rest), *entry))
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                if copied.is_empty() {
+                    bail!("path '{}' does not exist", source);
+                }
+
+                if line.starts_with("R ") {
+                    remove_path(&mut files, &source);
+                }
+                remove_path(&mut files, &destination);
+                files.extend(copied);
+            } else if line.starts_with("N ") {
+                bail!("notes are not supported");
+            } else {
+                break;
+            }
+        }
+
+        let tree = tree::build(object::store().as_ref(), &files)?;
+        let mut contents = format!("tree {}\n", tree);
+        for parent in &parents {
+            contents.push_str(&format!("parent {}\n", parent));
+        }
+        contents.push_str(&format!(
+            "author {}\ncommitter {}\n\n{}",
+            author.unwrap_or(committer),
+            committer,
+            message
+        ));
+
+        let id = self.store(mark, contents.as_bytes(), Kind::Commit)?;
+        self.refs.insert(name.to_owned(), Some(id));
+
+        Ok(())
+    }
+
+    fn tag(&mut self, parser: &mut Parser, name: &str) -> Result<()> {
+        let mark = parser.optional("mark")?;
+        let target = match parser.optional("from")? {
+            Some(from) => self.resolve(from)?,
+            None => bail!("missing object for tag {}", name),
+        };
+        parser.optional("original-oid")?;
+        let tagger = parser.optional("tagger")?;
+        let message = String::from_utf8(parser.data()?).context("bad encoding for message")?;
+
+        let mut contents = format!(
+            "object {}\ntype {}\ntag {}\n",
+            target,
+            object::get(&target)?.kind,
+            name
+        );
+        if let Some(tagger) = tagger {
+            contents.push_str(&format!("tagger {}\n", tagger));
+        }
+        contents.push_str(&format!("\n{}", message));
+
+        let id = self.store(mark, contents.as_bytes(), Kind::Tag)?;
+        self.refs.insert(format!("refs/tags/{}", name), Some(id));
+
+        Ok(())
+    }
+}
+
+// Reads a stream from the standard input, storing its objects and updating the
+// references from it. References are only updated once the whole stream has
+// been imported successfully.
+pub fn import() -> Result<()> {
+    let mut data = vec![];
+    std::io::stdin().read_to_end(&mut data)?;
+
+    let mut parser = Parser {
+        data: &data,
+        pos: 0,
+    };
+    let mut state = Import::default();
+
+    while let Some(line) = parser.next()? {
+        let (command, arg) = line.split_once(' ').unwrap_or((line, ""));
+
+        match command {
+            "" => {}
+            "blob" => {
+                let mark = parser.optional("mark")?;
+                parser.optional("original-oid")?;
+                let contents = parser.data()?;
+                state.store(mark, &contents, Kind::Blob)?;
+            }
+            "commit" => state.commit(&mut parser, arg)?,
+            "tag" => state.tag(&mut parser, arg)?,
+            "reset" => {
+                let target = match parser.optional("from")? {
+                    Some(from) => Some(state.resolve(from)?),
+                    None => None,
+                };
+                state.refs.insert(arg.to_owned(), target);
+            }
+            "progress" => println!("{}", arg),
+            "feature" | "option" | "checkpoint" => {}
+            "done" => break,
+            _ => bail!("unsupported command '{}'", line),
+        }
+    }
+
+    let mut refs = state
+        .refs
+        .iter()
+        .filter_map(|(name, id)| id.map(|id| (name, id)))
+        .collect::<Vec<_>>();
+    refs.sort();
+    for (name, _) in &refs {
+        commit::check_ref_name(name)?;
+    }
+    for (name, id) in &refs {
+        commit::update_ref(name, id)?;
+    }
+
+    println!(
+        "Imported {} objects and {} references",
+        state.objects,
+        refs.len()
+    );
+
+    Ok(())
+}
@@ -0,0 +1,670 @@
+use crate::commit;
+use crate::config::Config;
+use crate::init::{RUC_DIR, WORKING_DIR};
+use crate::object::{self, Kind};
+use crate::oid::{HashAlgorithm, Oid};
+use crate::tree::{self, Mode, TreeEntry};
+
+use anyhow::{bail, Context, Result};
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use std::collections::HashMap;
+use std::fs;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+// Objects from Git are the same as the ones from ruc, but they have a
+// different header ("kind size\0" instead of "kind\0") and they are compressed
+// with zlib. Hence, the same contents end up with different IDs on each side,
+// and objects pointing to other objects have to be rewritten when moving them
+// from one side to the other.
+
+// Kind and contents of an object, without any header.
+type RawObject = (Kind, Vec<u8>);
+
+// Repository from Git, which is read directly from its `.git` directory.
+// Objects are either loose (one file for each object) or inside of packs.
+struct GitRepository {
+    path: PathBuf,
+    algorithm: HashAlgorithm,
+    packs: Vec<Pack>,
+}
+
+// A pack from Git, along with the offsets of its objects as given by its
+// index file (sorted by ID).
+struct Pack {
+    data: Vec<u8>,
+    index: Vec<(Oid, usize)>,
+}
+
+// Returns the big-endian integer of the given size at the given position.
+fn read_be(data: &[u8], at: usize, size: usize) -> Result<u64> {
+    match data.get(at..at + size) {
+        Some(bytes) => Ok(bytes.iter().fold(0, |acc, &b| (acc << 8) | b as u64)),
+        None => bail!("unexpected end of data"),
+    }
+}
+
+// Returns the byte at the given position, advancing it.
+fn next_byte(data: &[u8], pos: &mut usize) -> Result<u8> {
+    match data.get(*pos) {
+        Some(&b) => {
+            *pos += 1;
+            Ok(b)
+        }
+        None => bail!("unexpected end of data"),
+    }
+}
+
+fn inflate(data: &[u8]) -> Result<Vec<u8>> {
+    let mut res = vec![];
+    ZlibDecoder::new(data)
+        .read_to_end(&mut res)
+        .context("bad compressed data")?;
+
+    Ok(res)
+}
+
+fn deflate(data: &[u8]) -> Result<Vec<u8>> {
+    let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+    encoder.write_all(data)?;
+
+    Ok(encoder.finish()?)
+}
+
+// Parses an index file (version 2) from Git. Its layout is: the signature and
+// the version, a fan-out table of 256 entries, the sorted IDs, a CRC32 for each
+// object, the 32-bit offsets and finally the 64-bit offsets for those which
+// did not fit.
+fn parse_index(data: &[u8], algorithm: HashAlgorithm) -> Result<Vec<(Oid, usize)>> {
+    if !data.starts_with(b"\xfftOc") || read_be(data, 4, 4)? != 2 {
+        bail!("unsupported version for pack index");
+    }
+
+    let count = read_be(data, 8 + 255 * 4, 4)? as usize;
+    let ids = 8 + 256 * 4;
+    let offsets = ids + count * (algorithm.byte_len() + 4);
+    let large_offsets = offsets + count * 4;
+
+    let mut index = Vec::with_capacity(count);
+    for i in 0..count {
+        let start = ids + i * algorithm.byte_len();
+        let id = match data.get(start..start + algorithm.byte_len()) {
+            Some(bytes) => Oid::from_bytes(bytes)?,
+            None => bail!("unexpected end of data"),
+        };
+
+        let offset = read_be(data, offsets + i * 4, 4)?;
+        let offset = if offset & 0x8000_0000 != 0 {
+            read_be(data, large_offsets + (offset & 0x7fff_ffff) as usize * 8, 8)?
+        } else {
+            offset
+        };
+
+        index.push((id, offset as usize));
+    }
+
+    Ok(index)
+}
+
+// Reads a size as encoded in deltas: little-endian groups of 7 bits, where the
+// most significant bit tells whether there are more bytes to come.
+fn read_delta_size(data: &[u8], pos: &mut usize) -> Result<usize> {
+    let mut size = 0;
+    let mut shift = 0;
+
+    loop {
+        if shift >= usize::BITS {
+            bail!("size in delta is too large");
+        }
+        let byte = next_byte(data, pos)?;
+        size |= ((byte & 0x7f) as usize) << shift;
+        shift += 7;
+
+        if byte & 0x80 == 0 {
+            return Ok(size);
+        }
+    }
+}
+
+// Applies the given delta into the given base object. A delta has the sizes
+// of both the base and the result, and then a list of instructions which either
+// copy a chunk from the base or insert new data.
+fn apply_delta(base: &[u8], delta: &[u8]) -> Result<Vec<u8>> {
+    let mut pos = 0;
+    if read_delta_size(delta, &mut pos)? != base.len() {
+        bail!("delta does not match its base object");
+    }
+    let size = read_delta_size(delta, &mut pos)?;
+
+    // The size comes from the file, so don't trust it for allocating memory.
+    let mut res = vec![];
+    while pos < delta.len() {
+        let instruction = next_byte(delta, &mut pos)?;
+
+        if instruction & 0x80 != 0 {
+            let mut offset = 0;
+            for i in 0..4 {
+                if instruction & (1 << i) != 0 {
+                    offset |= (next_byte(delta, &mut pos)? as usize) << (8 * i);
+                }
+            }
+            let mut length = 0;
+            for i in 0..3 {
+                if instruction & (0x10 << i) != 0 {
+                    length |= (next_byte(delta, &mut pos)? as usize) << (8 * i);
+                }
+            }
+            if length == 0 {
+                length = 0x10000;
+            }
+
+            match base.get(offset..offset + length) {
+                Some(chunk) => res.extend_from_slice(chunk),
+                None => bail!("bad copy instruction in delta"),
+            }
+        } else if instruction != 0 {
+            let length = instruction as usize;
+            match delta.get(pos..pos + length) {
+                Some(chunk) => res.extend_from_slice(chunk),
+                None => bail!("bad insert instruction in delta"),
+            }
+            pos += length;
+        } else {
+            bail!("bad instruction in delta");
+        }
+    }
+
+    if res.len() != size {
+        bail!("delta does not produce the expected size");
+    }
+
+    Ok(res)
+}
+
+impl GitRepository {
+    // Opens the Git repository on the given path, which can be either the
+    // `.git` directory or the directory containing it.
+    fn open(path: &Path) -> Result<GitRepository> {
+        let path = if path.join(".git").is_dir() {
+            path.join(".git")
+        } else {
+            path.to_path_buf()
+        };
+        if !path.join("objects").is_dir() || !path.join("HEAD").is_file() {
+            bail!("'{}' is not a Git repository", path.display());
+        }
+
+        let config = Config::open(&path.join("config"))?;
+        let algorithm = match config.get("extensions.objectformat") {
+            Some(format) => HashAlgorithm::from_str(format)?,
+            None => HashAlgorithm::default(),
+        };
+
+        let mut packs = vec![];
+        let pack_dir = path.join("objects").join("pack");
+        if pack_dir.is_dir() {
+            for entry in fs::read_dir(&pack_dir)? {
+                let index_path = entry?.path();
+                if index_path.extension().and_then(|e| e.to_str()) != Some("idx") {
+                    continue;
+                }
+
+                let index = parse_index(&fs::read(&index_path)?, algorithm)
+                    .with_context(|| format!("while reading {}", index_path.display()))?;
+                let data = fs::read(index_path.with_extension("pack"))?;
+                if !data.starts_with(b"PACK") {
+                    bail!("bad signature for pack {}", index_path.display());
+                }
+
+                packs.push(Pack { data, index });
+            }
+        }
+
+        Ok(GitRepository {
+            path,
+            algorithm,
+            packs,
+        })
+    }
+
+    // Returns the kind and the contents of the object with the given ID.
+    fn read(&self, id: &Oid) -> Result<RawObject> {
+        let hex = id.to_string();
+        let loose = self.path.join("objects").join(&hex[..2]).join(&hex[2..]);
+
+        if loose.is_file() {
+            let mut data = inflate(&fs::read(&loose)?)?;
+            let header = match data.iter().position(|&b| b == b'\0') {
+                Some(pos) => pos,
+                None => bail!("bad format for object {}", id),
+            };
+            let kind = match std::str::from_utf8(&data[..header])?.split_once(' ') {
+                Some((kind, _)) => Kind::from_str(kind).unwrap(),
+                None => bail!("bad format for object {}", id),
+            };
+
+            return Ok((kind, data.split_off(header + 1)));
+        }
+
+        for pack in &self.packs {
+            if let Ok(idx) = pack.index.binary_search_by(|(other, _)| other.cmp(id)) {
+                return self
+                    .read_packed(pack, pack.index[idx].1)
+                    .with_context(|| format!("while reading object {} from a pack", id));
+            }
+        }
+
+        bail!("object {} is missing from the Git repository", id)
+    }
+
+    // Returns the object at the given offset of the given pack. Each object
+    // starts with its type and its size, and then comes the compressed data.
+    // Objects can also be deltas against another object from the same pack
+    // (given by its offset) or against any other object (given by its ID).
+    fn read_packed(&self, pack: &Pack, offset: usize) -> Result<RawObject> {
+        let data = &pack.data;
+        let mut pos = offset;
+
+        let mut byte = next_byte(data, &mut pos)?;
+        let kind = (byte >> 4) & 0x7;
+        while byte & 0x80 != 0 {
+            byte = next_byte(data, &mut pos)?;
+        }
+
+        match kind {
+            1 => Ok((Kind::Commit, inflate(&data[pos..])?)),
+            2 => Ok((Kind::Tree, inflate(&data[pos..])?)),
+            3 => Ok((Kind::Blob, inflate(&data[pos..])?)),
+            4 => Ok((Kind::Tag, inflate(&data[pos..])?)),
+            6 => {
+                let mut byte = next_byte(data, &mut pos)?;
+                let mut distance = (byte & 0x7f) as usize;
+                while byte & 0x80 != 0 {
+                    if distance > usize::MAX >> 8 {
+                        bail!("bad offset for delta");
+                    }
+                    byte = next_byte(data, &mut pos)?;
+                    distance = ((distance + 1) << 7) | (byte & 0x7f) as usize;
+                }
+
+                let base = match offset.checked_sub(distance) {
+                    Some(base) => base,
+                    None => bail!("bad offset for delta"),
+                };
+                let (kind, base) = self.read_packed(pack, base)?;
+
+                Ok((kind, apply_delta(&base, &inflate(&data[pos..])?)?))
+            }
+            7 => {
+                let len = self.algorithm.byte_len();
+                let base = match data.get(pos..pos + len) {
+                    Some(bytes) => Oid::from_bytes(bytes)?,
+                    None => bail!("unexpected end of data"),
+                };
+                let (kind, base) = self.read(&base)?;
+
+                Ok((kind, apply_delta(&base, &inflate(&data[pos + len..])?)?))
+            }
+            _ => bail!("unknown object type {} in pack", kind),
+        }
+    }
+
+    // Returns the branches and the tags of this repository. They can either be
+    // in the `packed-refs` file or in their own file, which takes precedence.
+    fn refs(&self) -> Result<Vec<(String, Oid)>> {
+        let mut refs: HashMap<String, Oid> = HashMap::new();
+
+        if let Ok(packed) = fs::read_to_string(self.path.join("packed-refs")) {
+            for line in packed.lines() {
+                if line.starts_with('#') || line.starts_with('^') {
+                    continue;
+                }
+                if let Some((id, name)) = line.split_once(' ') {
+                    refs.insert(name.to_owned(), Oid::from_hex(id)?);
+                }
+            }
+        }
+
+        let mut pending = vec![String::from("refs/heads"), String::from("refs/tags")];
+        while let Some(current) = pending.pop() {
+            let entries = match fs::read_dir(self.path.join(&current)) {
+                Ok(entries) => entries,
+                Err(_) => continue,
+            };
+
+            for entry in entries {
+                let entry = entry?;
+                let name = match entry.file_name().to_str() {
+                    Some(name) => format!("{}/{}", current, name),
+                    None => continue,
+                };
+
+                if entry.file_type()?.is_dir() {
+                    pending.push(name);
+                } else if let Ok(id) = Oid::from_hex(fs::read_to_string(entry.path())?.trim()) {
+                    refs.insert(name, id);
+                }
+            }
+        }
+
+        let mut res = refs
+            .into_iter()
+            .filter(|(name, _)| name.starts_with("refs/heads/") || name.starts_with("refs/tags/"))
+            .collect::<Vec<_>>();
+        res.sort();
+
+        // References end up being written inside of `.ruc`, so their names
+        // must be valid.
+        for (name, _) in &res {
+            commit::check_ref_name(name).context("bad reference in the Git repository")?;
+        }
+
+        Ok(res)
+    }
+}
+
+// Returns the objects being referred to by the given object.
+fn references(kind: Kind, contents: &[u8], algorithm: HashAlgorithm) -> Result<Vec<Oid>> {
+    match kind {
+        Kind::Blob => Ok(vec![]),
+        Kind::Tree => Ok(parse_tree(contents, algorithm)?
+            .into_iter()
+            .map(|e| e.id)
+            .collect()),
+        Kind::Commit | Kind::Tag => {
+            let (headers, _) = commit::split_headers(std::str::from_utf8(contents)?);
+
+            headers
+                .iter()
+                .filter(|(key, _)| key == "tree" || key == "parent" || key == "object")
+                .map(|(_, value)| Oid::from_hex(value))
+                .collect()
+        }
+        Kind::None => bail!("unknown kind of object"),
+    }
+}
+
+// Parses the entries of a tree, which has the same format on both sides.
+// Git also accepts some modes from old versions (e.g. group writable files),
+// which are treated as regular files.
+fn parse_tree(contents: &[u8], algorithm: HashAlgorithm) -> Result<Vec<TreeEntry>> {
+    let mut entries = vec![];
+    let mut rest = contents;
+
+    while !rest.is_empty() {
+        let pos = match rest.iter().position(|&b| b == b'\0') {
+            Some(pos) => pos,
+            None => bail!("badly formatted tree!"),
+        };
+        let (mode, name) = match std::str::from_utf8(&rest[..pos])?.split_once(' ') {
+            Some((mode, name)) => {
+                tree::check_entry_name(name).context("badly formatted tree!")?;
+                (mode, name.to_owned())
+            }
+            None => bail!("badly formatted tree!"),
+        };
+        let mode = match mode {
+            "100664" => Mode::Regular,
+            "160000" => bail!("submodules are not supported ('{}')", name),
+            _ => Mode::from_str(mode)?,
+        };
+
+        let id = match rest.get(pos + 1..pos + 1 + algorithm.byte_len()) {
+            Some(bytes) => Oid::from_bytes(bytes)?,
+            None => bail!("badly formatted tree!"),
+        };
+        rest = &rest[pos + 1 + algorithm.byte_len()..];
+
+        entries.push(TreeEntry {
+            id,
+            mode,
+            kind: mode.kind(),
+            name,
+        });
+    }
+
+    Ok(entries)
+}
+
+// Returns the given object with the objects it refers to replaced as given by
+// the mapping. Signatures are dropped from commits and tags, since they are
+// no longer valid after rewriting them. Commits made by old versions of ruc do
+// not have an author nor a committer, which Git requires, so a placeholder is
+// used for them.
+fn rewrite_contents(
+    kind: Kind,
+    contents: &[u8],
+    algorithm: HashAlgorithm,
+    map: &HashMap<Oid, Oid>,
+) -> Result<Vec<u8>> {
+    match kind {
+        Kind::Blob => Ok(contents.to_vec()),
+        Kind::Tree => {
+            let mut entries = parse_tree(contents, algorithm)?;
+            for entry in entries.iter_mut() {
+                entry.id = map[&entry.id];
+            }
+
+            Ok(tree::encode_entries(&mut entries))
+        }
+        Kind::Commit | Kind::Tag => {
+            let (mut headers, message) = commit::split_headers(std::str::from_utf8(contents)?);
+
+            headers.retain(|(key, _)| {
+                !["gpgsig", "gpgsig-sha256", "mergetag"].contains(&key.as_str())
+            });
+            for (key, value) in headers.iter_mut() {
+                if key == "tree" || key == "parent" || key == "object" {
+                    *value = map[&Oid::from_hex(value)?].to_string();
+                }
+            }
+
+            if kind == Kind::Commit {
+                for role in ["author", "committer"] {
+                    if !headers.iter().any(|(key, _)| key == role) {
+                        headers.push((role.to_owned(), commit::UNKNOWN_IDENTITY.to_owned()));
+                    }
+                }
+            }
+
+            Ok(commit::join_headers(&headers, message).into_bytes())
+        }
+        Kind::None => bail!("unknown kind of object"),
+    }
+}
+
+// Copies the objects reachable from the given tips from one side to the other
+// by using the given functions for reading and writing objects. Objects are
+// only written after the ones they refer to, since their IDs are needed in
+// order to rewrite them. The given mapping from old to new IDs is updated
+// along the way.
+fn rewrite(
+    tips: &[Oid],
+    algorithm: HashAlgorithm,
+    read: impl Fn(&Oid) -> Result<RawObject>,
+    write: impl Fn(Kind, &[u8]) -> Result<Oid>,
+    map: &mut HashMap<Oid, Oid>,
+) -> Result<()> {
+    let mut pending: Vec<(Oid, Option<RawObject>)> = tips.iter().map(|id| (*id, None)).collect();
+
+    while let Some((id, obj)) = pending.pop() {
+        if map.contains_key(&id) {
+            continue;
+        }
+
+        match obj {
+            Some((kind, contents)) => {
+                let rewritten = rewrite_contents(kind, &contents, algorithm, map)
+                    .with_context(|| format!("while rewriting object {}", id))?;
+                map.insert(id, write(kind, &rewritten)?);
+            }
+            None => {
+                let (kind, contents) = read(&id)?;
+                let children = references(kind, &contents, algorithm)
+                    .with_context(|| format!("while reading object {}", id))?;
+
+                pending.push((id, Some((kind, contents))));
+                for child in children {
+                    if !map.contains_key(&child) {
+                        pending.push((child, None));
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Imports the branches and the tags from the Git repository on the given path
+// into the current repository, along with all the objects reachable from them.
+// If the current repository has no commits yet, then HEAD is set as in the Git
+// repository and its tree is checked out.
+pub fn import(path: &Path) -> Result<()> {
+    let repo = GitRepository::open(path)?;
+    let store = object::store();
+    if repo.algorithm != store.algorithm() {
+        bail!(
+            "the Git repository uses {} object IDs, but this repository uses {}",
+            repo.algorithm,
+            store.algorithm()
+        );
+    }
+
+    let refs = repo.refs()?;
+    let tips = refs.iter().map(|(_, id)| *id).collect::<Vec<_>>();
+
+    let mut map = HashMap::new();
+    rewrite(
+        &tips,
+        repo.algorithm,
+        |id| repo.read(id),
+        |kind, contents| object::hash_contents_into(store.as_ref(), contents, kind),
+        &mut map,
+    )?;
+
+    for (name, id) in &refs {
+        commit::update_ref(name, &map[id])?;
+        println!(" * {}", name);
+    }
+    println!(
+        "Imported {} objects from {}",
+        map.len(),
+        repo.path.display()
+    );
+
+    if commit::get_ref("HEAD")?.is_some() {
+        return Ok(());
+    }
+
+    let head = fs::read_to_string(repo.path.join("HEAD"))?;
+    let head = head.trim();
+    let target = match head.strip_prefix("ref: ") {
+        Some(branch) => match refs.iter().find(|(name, _)| name == branch) {
+            Some((_, id)) => {
+                commit::update_symbolic_ref("HEAD", branch)?;
+                map[id]
+            }
+            None => return Ok(()),
+        },
+        None => match map.get(&Oid::from_hex(head)?) {
+            Some(id) => {
+                commit::detach_head(id)?;
+                *id
+            }
+            None => return Ok(()),
+        },
+    };
+
+    tree::read_tree(&commit::get_commit(&target)?.tree)
+}
+
+// Writes the given object into the `.git` directory on the given path as a
+// loose object, returning its ID in Git.
+fn write_loose(path: &Path, algorithm: HashAlgorithm, kind: Kind, contents: &[u8]) -> Result<Oid> {
+    let mut data = format!("{} {}\0", kind, contents.len()).into_bytes();
+    data.extend_from_slice(contents);
+
+    let id = algorithm.hash(&data);
+    let hex = id.to_string();
+    let dir = path.join("objects").join(&hex[..2]);
+    if !dir.join(&hex[2..]).exists() {
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join(&hex[2..]), deflate(&data)?)?;
+    }
+
+    Ok(id)
+}
+
+// Exports the branches and the tags from the current repository into a new
+// Git repository whose `.git` directory is created inside of the given
+// directory. HEAD is set as in the current repository, but since Git keeps
+// track of the working tree through its index, which is not written, files
+// have to be checked out from Git (e.g. with `git checkout -f`).
+pub fn export(directory: &Path) -> Result<()> {
+    let path = directory.join(".git");
+    if path.exists() {
+        bail!("'{}' already exists", path.display());
+    }
+
+    let store = object::store();
+    let algorithm = store.algorithm();
+    for dir in ["objects", "refs/heads", "refs/tags"] {
+        fs::create_dir_all(path.join(dir))?;
+    }
+
+    let mut config = Config::open(&path.join("config"))?;
+    match algorithm {
+        HashAlgorithm::Sha1 => config.set("core.repositoryformatversion", "0")?,
+        HashAlgorithm::Sha256 => {
+            config.set("core.repositoryformatversion", "1")?;
+            config.set("extensions.objectformat", "sha256")?;
+        }
+    }
+    config.set("core.filemode", "true")?;
+    config.set("core.bare", "false")?;
+    config.save()?;
+
+    let ruc_dir = WORKING_DIR.join(RUC_DIR);
+    let mut refs = commit::list_refs_in(&ruc_dir, "refs/heads/")?;
+    refs.extend(commit::list_refs_in(&ruc_dir, "refs/tags/")?);
+    let head = commit::get_ref("HEAD")?;
+
+    let mut tips = refs.iter().map(|(_, id)| *id).collect::<Vec<_>>();
+    tips.extend(head);
+
+    let mut map = HashMap::new();
+    rewrite(
+        &tips,
+        algorithm,
+        |id| {
+            let obj = object::get_from(store.as_ref(), id)?;
+            Ok((obj.kind, obj.contents))
+        },
+        |kind, contents| write_loose(&path, algorithm, kind, contents),
+        &mut map,
+    )?;
+
+    for (name, id) in &refs {
+        fs::write(path.join(name), format!("{}\n", map[id]))?;
+    }
+
+    let head = match (commit::symbolic_ref("HEAD")?, head) {
+        (Some(branch), _) => format!("ref: {}\n", branch),
+        (None, Some(id)) => format!("{}\n", map[&id]),
+        (None, None) => String::from("ref: refs/heads/main\n"),
+    };
+    fs::write(path.join("HEAD"), head)?;
+
+    println!(
+        "Exported {} objects and {} references to {}",
+        map.len(),
+        refs.len(),
+        path.display()
+    );
+
+    Ok(())
+}
@@ -0,0 +1,184 @@
+use crate::commit;
+use crate::object::{self, ObjectStore};
+use crate::oid::Oid;
+
+use anyhow::{bail, Result};
+use std::collections::{HashSet, VecDeque};
+
+// Returns the parents of the given commit, which are taken from the commit
+// graph when possible.
+pub fn parents(store: &dyn ObjectStore, id: &Oid) -> Result<Vec<Oid>> {
+    if let Some(graph) = store.commit_graph() {
+        if let Some(commit) = graph.get(id)? {
+            return Ok(commit.parents);
+        }
+    }
+
+    Ok(commit::get_commit_from(store, id)?.parents)
+}
+
+// Returns the generation number of the given commit from the commit graph, if
+// it's there.
+fn generation(store: &dyn ObjectStore, id: &Oid) -> Result<Option<u32>> {
+    match store.commit_graph() {
+        Some(graph) => Ok(graph.get(id)?.map(|commit| commit.generation)),
+        None => Ok(None),
+    }
+}
+
+// Returns the IDs of all the commits reachable from the given ones, including
+// themselves.
+pub fn ancestors(store: &dyn ObjectStore, tips: &[Oid]) -> Result<HashSet<Oid>> {
+    let mut seen = HashSet::new();
+    let mut pending = tips.to_vec();
+
+    while let Some(id) = pending.pop() {
+        if seen.insert(id) {
+            pending.extend(parents(store, &id)?);
+        }
+    }
+
+    Ok(seen)
+}
+
+// Returns true if the commit `ancestor` can be reached from `descendant`. With
+// a commit graph, commits with a generation number which is not higher than
+// the one from `ancestor` are not walked, since they cannot reach it.
+pub fn is_ancestor(store: &dyn ObjectStore, ancestor: &Oid, descendant: &Oid) -> Result<bool> {
+    let min_generation = generation(store, ancestor)?;
+    let mut seen = HashSet::new();
+    let mut pending = vec![*descendant];
+
+    while let Some(id) = pending.pop() {
+        if id == *ancestor {
+            return Ok(true);
+        }
+        if let (Some(min), Some(current)) = (min_generation, generation(store, &id)?) {
+            if current <= min {
+                continue;
+            }
+        }
+        if seen.insert(id) {
+            pending.extend(parents(store, &id)?);
+        }
+    }
+
+    Ok(false)
+}
+
+// Returns the best common ancestors between `one` and any of `others`, which
+// is the same as between `one` and a hypothetical merge of all of `others`.
+// Common ancestors which can be reached from another common ancestor are not
+// good enough, so they are left out. Ancestors closer to `one` come first.
+pub fn merge_bases(store: &dyn ObjectStore, one: &Oid, others: &[Oid]) -> Result<Vec<Oid>> {
+    let theirs = ancestors(store, others)?;
+
+    // Walking breadth-first from `one` gives the common ancestors sorted by
+    // their distance to it.
+    let mut common = vec![];
+    let mut seen = HashSet::new();
+    let mut pending = VecDeque::from([*one]);
+    while let Some(id) = pending.pop_front() {
+        if !seen.insert(id) {
+            continue;
+        }
+        if theirs.contains(&id) {
+            common.push(id);
+            continue;
+        }
+        pending.extend(parents(store, &id)?);
+    }
+
+    independent(store, &common)
+}
+
+// Returns the best common ancestors of all the given commits, which are found
+// by taking the merge bases of the first two, then the merge bases of these
+// with the third one, and so on.
+pub fn octopus_merge_bases(store: &dyn ObjectStore, commits: &[Oid]) -> Result<Vec<Oid>> {
+    let (first, rest) = match commits.split_first() {
+        Some(split) => split,
+        None => return Ok(vec![]),
+    };
+
+    let mut bases = vec![*first];
+    for other in rest {
+        let mut next = vec![];
+        for base in &bases {
+            for id in merge_bases(store, base, &[*other])? {
+                if !next.contains(&id) {
+                    next.push(id);
+                }
+            }
+        }
+        bases = independent(store, &next)?;
+    }
+
+    Ok(bases)
+}
+
+// Returns the given commits except for those which can be reached from any of
+// the others, keeping their order.
+pub fn independent(store: &dyn ObjectStore, commits: &[Oid]) -> Result<Vec<Oid>> {
+    let mut tips = vec![];
+    for id in commits {
+        tips.extend(parents(store, id)?);
+    }
+    let reachable = ancestors(store, &tips)?;
+
+    Ok(commits
+        .iter()
+        .filter(|id| !reachable.contains(id))
+        .copied()
+        .collect())
+}
+
+// Returns how many commits can be reached from `one` but not from `other`,
+// and the other way around.
+pub fn ahead_behind(store: &dyn ObjectStore, one: &Oid, other: &Oid) -> Result<(usize, usize)> {
+    let (ours, theirs) = (ancestors(store, &[*one])?, ancestors(store, &[*other])?);
+
+    Ok((
+        ours.difference(&theirs).count(),
+        theirs.difference(&ours).count(),
+    ))
+}
+
+// Returns the commits for the given revisions.
+fn commits_from(revs: &[String]) -> Result<Vec<Oid>> {
+    revs.iter()
+        .map(|rev| Ok(commit::get_commit(&commit::ref_to_oid(rev)?)?.id))
+        .collect()
+}
+
+// Prints the best common ancestor between the first revision and the rest, or
+// all of them if `all` is true. With `octopus`, the common ancestors are the
+// ones from all revisions instead. It returns false if there are none.
+pub fn merge_base(revs: &[String], all: bool, octopus: bool) -> Result<bool> {
+    let commits = commits_from(revs)?;
+    let store = object::store();
+
+    let bases = if octopus {
+        octopus_merge_bases(store.as_ref(), &commits)?
+    } else {
+        if commits.len() < 2 {
+            bail!("at least two commits are needed to find a merge base");
+        }
+        merge_bases(store.as_ref(), &commits[0], &commits[1..])?
+    };
+
+    for id in bases.iter().take(if all { bases.len() } else { 1 }) {
+        println!("{}", id);
+    }
+
+    Ok(!bases.is_empty())
+}
+
+// Returns whether the first of the two given revisions is an ancestor of the
+// second one.
+pub fn is_ancestor_of(revs: &[String]) -> Result<bool> {
+    match &commits_from(revs)?[..] {
+        [ancestor, descendant] => is_ancestor(object::store().as_ref(), ancestor, descendant),
+        _ => bail!("--is-ancestor takes exactly two commits"),
+    }
+}
@@ -0,0 +1,67 @@
+use crate::init::{RUC_DIR, WORKING_DIR};
+use crate::tree;
+
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+// Directory inside of `.ruc` with the hooks, which are executable files named
+// after the point where they are run (e.g. "pre-commit"). As in Git, hooks
+// whose name starts with "pre-" can stop the command from going on by exiting
+// with a non-zero status, while the rest are only notified.
+const HOOKS_DIR: &str = "hooks";
+
+// Returns the path of the hook with the given name if there is an executable
+// file for it.
+fn find(name: &str) -> Option<PathBuf> {
+    let path = WORKING_DIR.join(RUC_DIR).join(HOOKS_DIR).join(name);
+
+    match fs::metadata(&path) {
+        Ok(metadata) if metadata.is_file() && tree::is_executable(&metadata) => Some(path),
+        _ => None,
+    }
+}
+
+// Returns true if there is a hook with the given name.
+pub fn exists(name: &str) -> bool {
+    find(name).is_some()
+}
+
+// Runs the hook with the given name from the root of the working directory,
+// with the given arguments and standard input. It returns whether the hook
+// succeeded, which is always the case when there is no such hook.
+pub fn run(name: &str, args: &[&str], stdin: Option<&str>) -> Result<bool> {
+    let path = match find(name) {
+        Some(path) => path,
+        None => return Ok(true),
+    };
+
+    let mut child = Command::new(&path)
+        .args(args)
+        .current_dir(WORKING_DIR.as_path())
+        .stdin(if stdin.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .spawn()
+        .with_context(|| format!("could not run the {} hook", name))?;
+
+    if let (Some(input), Some(mut pipe)) = (stdin, child.stdin.take()) {
+        // Hooks are free not to read their input, so a broken pipe is fine.
+        let _ = pipe.write_all(input.as_bytes());
+    }
+
+    Ok(child.wait()?.success())
+}
+
+// Same as `run`, but returning an error if the hook fails.
+pub fn run_pre(name: &str, args: &[&str], stdin: Option<&str>) -> Result<()> {
+    if !run(name, args, stdin)? {
+        bail!("the {} hook failed", name);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,481 @@
+use crate::commit;
+use crate::init::{RUC_DIR, WORKING_DIR};
+use crate::object;
+use crate::oid::{HashAlgorithm, Oid};
+use crate::remote::{self, RefUpdate, Transport};
+
+use anyhow::{bail, Context, Result};
+use std::cell::RefCell;
+use std::io::prelude::*;
+use std::io::BufReader;
+use std::net::{TcpListener, TcpStream};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+// Repositories are exposed over HTTP with three endpoints, all of them relative
+// to the URL of the repository (e.g. "/info/refs" or "/repo/info/refs"):
+//
+//   - `GET /info/refs`: advertises the hash algorithm, the target of HEAD and
+//     the references from the repository.
+//   - `POST /upload-pack`: the body contains `want <id>` and `have <id>` lines,
+//     and the response is a pack with what the client is missing.
+//   - `POST /receive-pack`: the body contains `update <old> <new> <name>` lines
+//     (and optionally a `force` line), an empty line and then a pack with the
+//     objects needed for the updates.
+const INFO_REFS: &str = "/info/refs";
+const UPLOAD_PACK: &str = "/upload-pack";
+const RECEIVE_PACK: &str = "/receive-pack";
+
+// Value used instead of an object ID for references that do not exist.
+const NO_ID: &str = "-";
+
+// Limits for what the server accepts from a client, so a single client cannot
+// make it run out of memory or keep it busy forever.
+const MAX_BODY_SIZE: usize = 512 * 1024 * 1024;
+const MAX_LINE_SIZE: usize = 8 * 1024;
+const MAX_HEADERS: usize = 100;
+const MAX_CONNECTIONS: usize = 32;
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+// Advertisement from a repository as returned by the `/info/refs` endpoint.
+struct Advertisement {
+    algorithm: HashAlgorithm,
+    head: Option<String>,
+    refs: Vec<(String, Oid)>,
+}
+
+fn encode_advertisement() -> Result<String> {
+    let ruc_dir = WORKING_DIR.join(RUC_DIR);
+    let mut res = format!("algorithm {}\n", object::store().algorithm());
+
+    if let Some(head) = commit::symbolic_ref_in(&ruc_dir, "HEAD")? {
+        res.push_str(&format!("head {}\n", head));
+    }
+    for (name, id) in remote::advertised_refs(&ruc_dir)? {
+        res.push_str(&format!("ref {} {}\n", id, name));
+    }
+
+    Ok(res)
+}
+
+fn parse_advertisement(text: &str) -> Result<Advertisement> {
+    let mut adv = Advertisement {
+        algorithm: HashAlgorithm::default(),
+        head: None,
+        refs: vec![],
+    };
+
+    for line in text.lines() {
+        match line.split_once(' ') {
+            Some(("algorithm", value)) => adv.algorithm = HashAlgorithm::from_str(value)?,
+            Some(("head", value)) => adv.head = Some(value.to_owned()),
+            Some(("ref", value)) => match value.split_once(' ') {
+                Some((id, name)) => adv.refs.push((name.to_owned(), Oid::from_hex(id)?)),
+                None => bail!("bad reference advertisement: '{}'", line),
+            },
+            _ => bail!("bad reference advertisement: '{}'", line),
+        }
+    }
+
+    Ok(adv)
+}
+
+// Transport for repositories being served over HTTP (e.g. with `ruc serve`).
+pub struct HttpTransport {
+    host: String,
+    path: String,
+    advertisement: RefCell<Option<Advertisement>>,
+}
+
+impl HttpTransport {
+    pub fn new(url: &str) -> Result<HttpTransport> {
+        let rest = match url.strip_prefix("http://") {
+            Some(rest) => rest,
+            None => bail!("unsupported URL '{}'", url),
+        };
+        let (host, path) = match rest.find('/') {
+            Some(pos) => (&rest[..pos], rest[pos..].trim_end_matches('/')),
+            None => (rest, ""),
+        };
+        if host.is_empty() {
+            bail!("no host given in URL '{}'", url);
+        }
+
+        Ok(HttpTransport {
+            host: host.to_owned(),
+            path: path.to_owned(),
+            advertisement: RefCell::new(None),
+        })
+    }
+
+    // Performs a request to the given endpoint and returns the body of the
+    // response. Responses other than "200 OK" are turned into errors.
+    fn request(&self, method: &str, endpoint: &str, body: &[u8]) -> Result<Vec<u8>> {
+        let mut stream = TcpStream::connect(&self.host)
+            .with_context(|| format!("could not connect to {}", self.host))?;
+
+        let header = format!(
+            "{} {}{} HTTP/1.1\r\nHost: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            method,
+            self.path,
+            endpoint,
+            self.host,
+            body.len()
+        );
+        stream.write_all(header.as_bytes())?;
+        stream.write_all(body)?;
+        stream.flush()?;
+
+        let mut reader = BufReader::new(stream);
+        let (status, reason) = {
+            let line = read_line(&mut reader)?;
+            let mut fields = line.splitn(3, ' ');
+            let status = fields
+                .nth(1)
+                .and_then(|s| s.parse::<u16>().ok())
+                .with_context(|| format!("bad response from {}", self.host))?;
+            (status, fields.next().unwrap_or("").to_owned())
+        };
+        let length = read_headers(&mut reader)?;
+
+        let mut body = vec![];
+        match length {
+            Some(length) => {
+                (&mut reader).take(length as u64).read_to_end(&mut body)?;
+                if body.len() != length {
+                    bail!("connection to {} closed unexpectedly", self.host);
+                }
+            }
+            None => {
+                reader.read_to_end(&mut body)?;
+            }
+        }
+
+        if status != 200 {
+            let message = String::from_utf8_lossy(&body);
+            bail!("{} ({} {})", message.trim(), status, reason);
+        }
+
+        Ok(body)
+    }
+
+    // Calls `f` with the advertisement from the remote, which is only
+    // requested once.
+    fn with_advertisement<T>(&self, f: impl FnOnce(&Advertisement) -> T) -> Result<T> {
+        if self.advertisement.borrow().is_none() {
+            let body = self.request("GET", INFO_REFS, &[])?;
+            let adv = parse_advertisement(&String::from_utf8_lossy(&body))?;
+            *self.advertisement.borrow_mut() = Some(adv);
+        }
+
+        Ok(f(self.advertisement.borrow().as_ref().unwrap()))
+    }
+}
+
+impl Transport for HttpTransport {
+    fn algorithm(&self) -> Result<HashAlgorithm> {
+        self.with_advertisement(|adv| adv.algorithm)
+    }
+
+    fn refs(&self) -> Result<Vec<(String, Oid)>> {
+        self.with_advertisement(|adv| adv.refs.clone())
+    }
+
+    fn head(&self) -> Result<Option<String>> {
+        self.with_advertisement(|adv| adv.head.clone())
+    }
+
+    fn fetch(&self, wants: &[Oid], haves: &[Oid]) -> Result<Vec<u8>> {
+        let mut body = String::new();
+        for want in wants {
+            body.push_str(&format!("want {}\n", want));
+        }
+        for have in haves {
+            body.push_str(&format!("have {}\n", have));
+        }
+
+        self.request("POST", UPLOAD_PACK, body.as_bytes())
+    }
+
+    fn push(&self, pack: &[u8], updates: &[RefUpdate], force: bool) -> Result<()> {
+        let mut body = String::new();
+        if force {
+            body.push_str("force\n");
+        }
+        for update in updates {
+            let old = update.old.map_or(NO_ID.to_owned(), |id| id.to_string());
+            body.push_str(&format!("update {} {} {}\n", old, update.new, update.name));
+        }
+        body.push('\n');
+
+        let mut body = body.into_bytes();
+        body.extend_from_slice(pack);
+        self.request("POST", RECEIVE_PACK, &body)?;
+
+        Ok(())
+    }
+}
+
+// Reads a line terminated by CRLF (or just LF) and returns it without the
+// terminator. Lines longer than MAX_LINE_SIZE are rejected.
+fn read_line(reader: &mut impl BufRead) -> Result<String> {
+    let mut line = String::new();
+    if reader.take(MAX_LINE_SIZE as u64).read_line(&mut line)? == 0 {
+        bail!("connection closed unexpectedly");
+    }
+    if !line.ends_with('\n') {
+        bail!("line too long or connection closed unexpectedly");
+    }
+
+    Ok(line.trim_end_matches(['\r', '\n']).to_owned())
+}
+
+// Reads the headers of a request or a response, and returns the value from
+// the Content-Length header, if any.
+fn read_headers(reader: &mut impl BufRead) -> Result<Option<usize>> {
+    let mut length = None;
+
+    for _ in 0..=MAX_HEADERS {
+        let line = read_line(reader)?;
+        if line.is_empty() {
+            return Ok(length);
+        }
+
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                length = Some(
+                    value
+                        .trim()
+                        .parse::<usize>()
+                        .context("bad Content-Length")?,
+                );
+            }
+        }
+    }
+
+    bail!("too many headers")
+}
+
+// Response to be sent back by the server.
+struct Response {
+    status: u16,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn ok(body: Vec<u8>) -> Response {
+        Response { status: 200, body }
+    }
+
+    fn error(status: u16, message: &str) -> Response {
+        Response {
+            status,
+            body: format!("{}\n", message).into_bytes(),
+        }
+    }
+
+    fn reason(&self) -> &str {
+        match self.status {
+            200 => "OK",
+            400 => "Bad Request",
+            403 => "Forbidden",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            413 => "Payload Too Large",
+            _ => "Internal Server Error",
+        }
+    }
+}
+
+fn upload_pack(body: &[u8]) -> Result<Vec<u8>> {
+    let mut wants = vec![];
+    let mut haves = vec![];
+
+    for line in String::from_utf8_lossy(body).lines() {
+        match line.split_once(' ') {
+            Some(("want", id)) => wants.push(Oid::from_hex(id)?),
+            Some(("have", id)) => haves.push(Oid::from_hex(id)?),
+            _ => bail!("bad line '{}'", line),
+        }
+    }
+
+    remote::upload_pack(object::store().as_ref(), &wants, &haves)
+}
+
+fn receive_pack(body: &[u8]) -> Result<Vec<u8>> {
+    let mut force = false;
+    let mut updates = vec![];
+    let mut rest = body;
+
+    loop {
+        let pos = match rest.iter().position(|&b| b == b'\n') {
+            Some(pos) => pos,
+            None => bail!("unexpected end of request"),
+        };
+        let line = std::str::from_utf8(&rest[..pos])?;
+        rest = &rest[pos + 1..];
+
+        if line.is_empty() {
+            break;
+        } else if line == "force" {
+            force = true;
+            continue;
+        }
+
+        let fields = line.split(' ').collect::<Vec<_>>();
+        if fields.len() != 4 || fields[0] != "update" {
+            bail!("bad line '{}'", line);
+        }
+        updates.push(RefUpdate {
+            old: match fields[1] {
+                NO_ID => None,
+                id => Some(Oid::from_hex(id)?),
+            },
+            new: Oid::from_hex(fields[2])?,
+            name: fields[3].to_owned(),
+        });
+    }
+
+    remote::receive_pack(
+        &WORKING_DIR.join(RUC_DIR),
+        object::store().as_ref(),
+        rest,
+        &updates,
+        force,
+    )?;
+
+    Ok(b"ok\n".to_vec())
+}
+
+// State shared by the threads handling connections. Requests are read and
+// answered concurrently, but only one at a time gets to touch the repository,
+// so concurrent pushes cannot step on each other.
+struct Server {
+    allow_push: bool,
+    lock: Mutex<()>,
+    connections: AtomicUsize,
+}
+
+impl Server {
+    // Handles a request on the given path. The repository can be served under
+    // any base path, so only the endpoint at the end of the path matters.
+    fn route(&self, method: &str, path: &str, body: &[u8]) -> Response {
+        let endpoint = match [INFO_REFS, UPLOAD_PACK, RECEIVE_PACK]
+            .into_iter()
+            .find(|endpoint| path.ends_with(endpoint))
+        {
+            Some(endpoint) => endpoint,
+            None => return Response::error(404, "not found"),
+        };
+
+        let _guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        let result = match (method, endpoint) {
+            ("GET", INFO_REFS) => encode_advertisement().map(|a| a.into_bytes()),
+            ("POST", UPLOAD_PACK) => upload_pack(body),
+            ("POST", RECEIVE_PACK) if !self.allow_push => {
+                return Response::error(
+                    403,
+                    "pushing is disabled, start the server with --allow-push to enable it",
+                )
+            }
+            ("POST", RECEIVE_PACK) => receive_pack(body),
+            _ => return Response::error(405, "method not allowed"),
+        };
+
+        match result {
+            Ok(body) => Response::ok(body),
+            Err(e) => Response::error(400, &format!("{:#}", e)),
+        }
+    }
+
+    fn handle(&self, stream: TcpStream) -> Result<()> {
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        stream.set_write_timeout(Some(TIMEOUT))?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+
+        let line = read_line(&mut reader)?;
+        let fields = line.split(' ').collect::<Vec<_>>();
+        if fields.len() != 3 {
+            bail!("bad request line '{}'", line);
+        }
+
+        // The body is read as it comes rather than allocated upfront, since the
+        // length given by the client cannot be trusted.
+        let length = read_headers(&mut reader)?.unwrap_or(0);
+        let response = if length > MAX_BODY_SIZE {
+            Response::error(413, "request is too large")
+        } else {
+            let mut body = vec![];
+            (&mut reader).take(length as u64).read_to_end(&mut body)?;
+            if body.len() != length {
+                bail!("connection closed unexpectedly");
+            }
+
+            // Only the path matters, the query string is ignored.
+            let path = fields[1].split('?').next().unwrap_or("");
+            self.route(fields[0], path, &body)
+        };
+        println!("{} {} {}", fields[0], fields[1], response.status);
+
+        let mut stream = stream;
+        let header = format!(
+            "HTTP/1.1 {} {}\r\nContent-Length: {}\r\nContent-Type: application/octet-stream\r\nConnection: close\r\n\r\n",
+            response.status,
+            response.reason(),
+            response.body.len()
+        );
+        stream.write_all(header.as_bytes())?;
+        stream.write_all(&response.body)?;
+        stream.flush()?;
+
+        Ok(())
+    }
+}
+
+// Serves the current repository over HTTP on the given address. Each
+// connection is handled on its own thread, up to MAX_CONNECTIONS at a time.
+// Since there's no authentication, pushes are only accepted if `allow_push` is
+// true.
+pub fn serve(bind: &str, port: u16, allow_push: bool) -> Result<()> {
+    let listener = TcpListener::bind((bind, port))
+        .with_context(|| format!("could not listen on {}:{}", bind, port))?;
+
+    println!(
+        "Serving {} on http://{}",
+        WORKING_DIR.display(),
+        listener.local_addr()?
+    );
+
+    let server = Arc::new(Server {
+        allow_push,
+        lock: Mutex::new(()),
+        connections: AtomicUsize::new(0),
+    });
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                eprintln!("error: {}", e);
+                continue;
+            }
+        };
+        if server.connections.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+            server.connections.fetch_sub(1, Ordering::SeqCst);
+            eprintln!("error: too many connections, dropping a new one");
+            continue;
+        }
+
+        let server = Arc::clone(&server);
+        thread::spawn(move || {
+            if let Err(e) = server.handle(stream) {
+                eprintln!("error: {:#}", e);
+            }
+            server.connections.fetch_sub(1, Ordering::SeqCst);
+        });
+    }
+
+    Ok(())
+}
@@ -1,5 +1,10 @@
+use crate::config::Config;
+use crate::oid::HashAlgorithm;
+
+use anyhow::{bail, Result};
 use std::path::Path;
 use std::path::PathBuf;
+use std::str::FromStr;
 
 pub const RUC_DIR: &str = ".ruc";
 
@@ -43,13 +48,29 @@ fn iterate_working_dirs(mut dir: PathBuf) -> Result<PathBuf, String> {
     ))
 }
 
-// Initialize the given directory to be a `ruc` project.
-pub fn init(directory: &Path) -> Result<(), std::io::Error> {
+// Initialize the given directory to be a `ruc` project, which will use the
+// given hash algorithm for object IDs (SHA-1 if none was given).
+pub fn init(directory: &Path, format: Option<HashAlgorithm>) -> Result<()> {
     std::fs::create_dir_all(directory.join(RUC_DIR))?;
     std::fs::create_dir_all(directory.join(RUC_DIR).join("objects"))?;
     std::fs::create_dir_all(directory.join(RUC_DIR).join("refs").join("tags"))?;
     std::fs::create_dir_all(directory.join(RUC_DIR).join("refs").join("heads"))?;
 
+    // The object format is picked up when creating the repository and it
+    // cannot be changed afterwards.
+    let mut config = Config::open(&directory.join(RUC_DIR).join("config"))?;
+    match config.get("core.objectformat") {
+        Some(current) => {
+            if format.is_some_and(|f| f != HashAlgorithm::from_str(current).unwrap_or_default()) {
+                bail!("attempt to reinitialize repository with a different object format");
+            }
+        }
+        None => {
+            config.set("core.objectformat", &format.unwrap_or_default().to_string())?;
+            config.save()?;
+        }
+    }
+
     println!(
         "Initialized empty Ruc repository in {}",
         directory.display()
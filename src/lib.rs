@@ -1,7 +1,26 @@
 #[macro_use]
 extern crate lazy_static;
 
+pub mod bisect;
+pub mod blame;
+pub mod bundle;
 pub mod commit;
+pub mod commit_graph;
+pub mod config;
+pub mod diff;
+pub mod fast;
+pub mod git;
+pub mod graph;
+pub mod hooks;
+pub mod http;
 pub mod init;
+pub mod merge;
 pub mod object;
+pub mod oid;
+pub mod pack;
+pub mod rebase;
+pub mod remote;
+pub mod sequencer;
+pub mod stash;
+pub mod status;
 pub mod tree;
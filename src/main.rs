@@ -1,9 +1,15 @@
-use ruc::{commit, init, object, tree};
+use ruc::oid::HashAlgorithm;
+use ruc::{
+    bisect, blame, bundle, commit, commit_graph, fast, git, graph, http, init, object, rebase,
+    remote, sequencer, stash, status, tree,
+};
 
-use anyhow::{bail, Result};
+use anyhow::{bail, Context, Result};
 
 use clap::{arg, Command};
+use std::io::Read;
 use std::path::PathBuf;
+use std::str::FromStr;
 
 fn cli() -> Command {
     Command::new("ruc")
@@ -18,25 +24,50 @@ fn cli() -> Command {
                     arg!([directory] "Directory location")
                         .value_parser(clap::value_parser!(PathBuf))
                         .required(false),
+                )
+                .arg(
+                    arg!(--"object-format" <format> "Hash algorithm for object IDs (sha1 or sha256)")
+                        .value_parser(clap::value_parser!(HashAlgorithm))
+                        .required(false),
                 ),
         )
         .subcommand(
             Command::new("hash-object")
-                .about("Hash a given file into the object database")
+                .about("Compute the object ID of files and optionally store them")
                 .arg(
-                    arg!(<file> "File to store into the object database")
+                    arg!(-t <type> "Kind of the object")
+                        .value_parser(["blob", "tree", "commit", "tag"])
+                        .default_value("blob")
+                        .required(false),
+                )
+                .arg(arg!(w: -w "Write the object into the object database"))
+                .arg(arg!(--stdin "Read the object from the standard input"))
+                .arg(arg!(--"no-filters" "Hash the contents as they are, which is always the case"))
+                .arg(
+                    arg!([file] ... "Files to hash")
                         .value_parser(clap::value_parser!(PathBuf))
-                        .required(true),
+                        .required_unless_present("stdin"),
                 ),
         )
         .subcommand(
-            // TODO: not really size
             Command::new("cat-file")
                 .about("Provide content or type and size information for repository objects")
+                .arg(arg!(t: -t "Show the kind of the object"))
+                .arg(arg!(s: -s "Show the size of the object"))
+                .arg(arg!(p: -p "Pretty-print the contents of the object"))
+                .arg(arg!(e: -e "Exit with a zero status if the object exists and is valid"))
+                .arg(arg!(--batch "Print information and contents of objects from stdin"))
+                .arg(arg!(--"batch-check" "Print information of objects from stdin"))
+                .group(
+                    clap::ArgGroup::new("mode")
+                        .args(["t", "s", "p", "e", "batch", "batch-check"])
+                        .required(false),
+                )
                 .arg(
-                    arg!(<object> "The name of the object to show")
+                    arg!([object] "The name of the object to show")
                         .value_parser(clap::value_parser!(String))
-                        .required(true),
+                        .required_unless_present_any(["batch", "batch-check"])
+                        .conflicts_with_all(["batch", "batch-check"]),
                 ),
         )
         .subcommand(Command::new("write-tree").about("Create a tree object from the current index"))
@@ -56,7 +87,9 @@ fn cli() -> Command {
                     arg!(-m --message <message>)
                         .value_parser(clap::value_parser!(String))
                         .required(false),
-                ),
+                )
+                .arg(arg!(--amend "Replace the last commit with a new one"))
+                .arg(arg!(-n --"no-verify" "Skip the pre-commit and commit-msg hooks")),
         )
         .subcommand(
             Command::new("log").about("Show commit logs").arg(
@@ -65,6 +98,235 @@ fn cli() -> Command {
                     .required(false),
             ),
         )
+        .subcommand(
+            Command::new("show")
+                .about("Show commits, trees, blobs and tags")
+                .arg(
+                    arg!([object] "The object to show, which can also be given as <rev>:<path>")
+                        .value_parser(clap::value_parser!(String))
+                        .default_value("HEAD")
+                        .required(false),
+                ),
+        )
+        .subcommand(
+            Command::new("blame")
+                .about("Show which commit last changed each line of a file")
+                .arg(
+                    arg!(-L <range> "Only blame the given lines (e.g. 10,20 or 10,+5)")
+                        .value_parser(clap::value_parser!(String))
+                        .required(false),
+                )
+                .arg(arg!(--porcelain "Show the output in a format meant for scripts"))
+                .arg(
+                    arg!(<file> "File to blame")
+                        .value_parser(clap::value_parser!(String))
+                        .required(true),
+                )
+                .arg(
+                    arg!([rev] "Revision to blame the file from")
+                        .value_parser(clap::value_parser!(String))
+                        .default_value("HEAD")
+                        .required(false),
+                ),
+        )
+        .subcommand(
+            Command::new("merge-base")
+                .about("Find the best common ancestors of commits")
+                .arg(arg!(-a --all "Show all the best common ancestors"))
+                .arg(arg!(--octopus "Find the common ancestors of all the commits"))
+                .arg(arg!(--"is-ancestor" "Check if the first commit is an ancestor of the second"))
+                .group(
+                    clap::ArgGroup::new("mode")
+                        .args(["octopus", "is-ancestor"])
+                        .required(false),
+                )
+                .arg(
+                    arg!(<commits> ... "Commits to look at")
+                        .value_parser(clap::value_parser!(String))
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            Command::new("commit-graph")
+                .about("Write a cache of the commit history for faster traversals")
+                .subcommand_required(true)
+                .subcommand(Command::new("write").about("Write the commit graph for all commits")),
+        )
+        .subcommand(Command::new("status").about("Show the status of the working directory"))
+        .subcommand(
+            Command::new("rebase")
+                .about("Reapply commits on top of another base")
+                .arg(
+                    arg!(--onto <newbase> "Starting point at which to create the new commits")
+                        .value_parser(clap::value_parser!(String))
+                        .required(false),
+                )
+                .arg(arg!(-i --interactive "Edit the list of commits to rebase"))
+                .arg(arg!(--autosquash "Move commits marked with fixup! or squash! in place"))
+                .arg(arg!(--continue "Continue after resolving a conflict"))
+                .arg(arg!(--skip "Skip the commit that could not be applied"))
+                .arg(arg!(--abort "Abort and go back to the original branch"))
+                .group(
+                    clap::ArgGroup::new("action")
+                        .args(["continue", "skip", "abort"])
+                        .required(false),
+                )
+                .arg(
+                    arg!([upstream] "Upstream branch to compare against")
+                        .value_parser(clap::value_parser!(String))
+                        .required_unless_present("action")
+                        .conflicts_with("action"),
+                ),
+        )
+        .subcommand(
+            Command::new("cherry-pick")
+                .about("Apply the changes introduced by some existing commits")
+                .arg(arg!(--continue "Continue after resolving a conflict"))
+                .arg(arg!(--abort "Abort and go back to the state before starting"))
+                .group(
+                    clap::ArgGroup::new("action")
+                        .args(["continue", "abort"])
+                        .required(false),
+                )
+                .arg(
+                    arg!([commits] ... "Commits or ranges of commits to apply")
+                        .value_parser(clap::value_parser!(String))
+                        .required_unless_present("action")
+                        .conflicts_with("action"),
+                ),
+        )
+        .subcommand(
+            Command::new("revert")
+                .about("Revert some existing commits")
+                .arg(arg!(--continue "Continue after resolving a conflict"))
+                .arg(arg!(--abort "Abort and go back to the state before starting"))
+                .group(
+                    clap::ArgGroup::new("action")
+                        .args(["continue", "abort"])
+                        .required(false),
+                )
+                .arg(
+                    arg!([commits] ... "Commits or ranges of commits to revert")
+                        .value_parser(clap::value_parser!(String))
+                        .required_unless_present("action")
+                        .conflicts_with("action"),
+                ),
+        )
+        .subcommand(
+            Command::new("stash")
+                .about("Stash the changes in a dirty working directory away")
+                .subcommand(
+                    Command::new("push")
+                        .about("Save local changes into a new stash")
+                        .arg(
+                            arg!(-m --message <message> "Description of the stash")
+                                .value_parser(clap::value_parser!(String))
+                                .required(false),
+                        )
+                        .arg(
+                            arg!([paths] ... "Only stash changes beneath these paths")
+                                .value_parser(clap::value_parser!(String))
+                                .required(false),
+                        ),
+                )
+                .subcommand(Command::new("list").about("List the existing stashes"))
+                .subcommand(
+                    Command::new("show").about("Show the changes recorded in a stash").arg(
+                        arg!([stash] "Name of the stash (e.g. stash@{1})")
+                            .value_parser(clap::value_parser!(String))
+                            .required(false),
+                    ),
+                )
+                .subcommand(
+                    Command::new("apply").about("Apply a stash on top of the working directory").arg(
+                        arg!([stash] "Name of the stash (e.g. stash@{1})")
+                            .value_parser(clap::value_parser!(String))
+                            .required(false),
+                    ),
+                )
+                .subcommand(
+                    Command::new("pop").about("Apply a stash and then remove it").arg(
+                        arg!([stash] "Name of the stash (e.g. stash@{1})")
+                            .value_parser(clap::value_parser!(String))
+                            .required(false),
+                    ),
+                )
+                .subcommand(
+                    Command::new("drop").about("Remove a stash").arg(
+                        arg!([stash] "Name of the stash (e.g. stash@{1})")
+                            .value_parser(clap::value_parser!(String))
+                            .required(false),
+                    ),
+                )
+        )
+        .subcommand(
+            Command::new("bisect")
+                .about("Use binary search to find the commit that introduced a bug")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("start")
+                        .about("Start bisecting")
+                        .arg(
+                            arg!([bad] "A commit which is known to be bad")
+                                .value_parser(clap::value_parser!(String))
+                                .required(false),
+                        )
+                        .arg(
+                            arg!([good] ... "Commits which are known to be good")
+                                .value_parser(clap::value_parser!(String))
+                                .required(false),
+                        ),
+                )
+                .subcommand(
+                    Command::new("good").about("Mark a commit as good").arg(
+                        arg!([rev] "Commit to mark (HEAD by default)")
+                            .value_parser(clap::value_parser!(String))
+                            .required(false),
+                    ),
+                )
+                .subcommand(
+                    Command::new("bad").about("Mark a commit as bad").arg(
+                        arg!([rev] "Commit to mark (HEAD by default)")
+                            .value_parser(clap::value_parser!(String))
+                            .required(false),
+                    ),
+                )
+                .subcommand(
+                    Command::new("skip").about("Skip a commit which cannot be tested").arg(
+                        arg!([rev] "Commit to mark (HEAD by default)")
+                            .value_parser(clap::value_parser!(String))
+                            .required(false),
+                    ),
+                )
+                .subcommand(Command::new("reset").about("Stop bisecting and go back"))
+                .subcommand(
+                    Command::new("run")
+                        .about("Bisect automatically by running a command")
+                        .arg(
+                            arg!(<command> ... "Command to run on each commit")
+                                .value_parser(clap::value_parser!(String))
+                                .trailing_var_arg(true)
+                                .allow_hyphen_values(true)
+                                .required(true),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("restore")
+                .about("Restore working tree files")
+                .arg(
+                    arg!(-s --source <revision> "Revision to restore the files from")
+                        .value_parser(clap::value_parser!(String))
+                        .default_value("HEAD")
+                        .required(false),
+                )
+                .arg(arg!(-S --staged "Restore the index (not supported)"))
+                .arg(
+                    arg!(<paths> ... "Files or directories to restore")
+                        .value_parser(clap::value_parser!(String))
+                        .required(true),
+                ),
+        )
         .subcommand(
             // TODO: not branches, right now :)
             Command::new("checkout")
@@ -92,10 +354,241 @@ fn cli() -> Command {
                                 .value_parser(clap::value_parser!(String))
                                 .default_value("HEAD")
                                 .required(false),
+                        )
+                        .arg(
+                            arg!(-m --message <message> "Message for the tag object")
+                                .value_parser(clap::value_parser!(String))
+                                .required(false),
                         ),
                 ),
         )
         .subcommand(Command::new("graph").about("Show a graph with the history of the repository"))
+        .subcommand(
+            Command::new("branch")
+                .about("List or create branches")
+                .arg(
+                    arg!([name] "Name of the branch to be created")
+                        .value_parser(clap::value_parser!(String))
+                        .required(false),
+                )
+                .arg(
+                    arg!([start] "Commit where the new branch will point to")
+                        .value_parser(clap::value_parser!(String))
+                        .default_value("HEAD")
+                        .required(false),
+                ),
+        )
+        .subcommand(
+            Command::new("remote")
+                .about("Manage the set of tracked repositories")
+                .subcommand(
+                    Command::new("add")
+                        .about("Add a remote")
+                        .arg(
+                            arg!(<name> "Name of the remote")
+                                .value_parser(clap::value_parser!(String))
+                                .required(true),
+                        )
+                        .arg(
+                            arg!(<url> "Location of the remote repository")
+                                .value_parser(clap::value_parser!(String))
+                                .required(true),
+                        ),
+                )
+                .subcommand(
+                    Command::new("remove").about("Remove a remote").arg(
+                        arg!(<name> "Name of the remote")
+                            .value_parser(clap::value_parser!(String))
+                            .required(true),
+                    ),
+                ),
+        )
+        .subcommand(
+            Command::new("clone")
+                .about("Clone a repository into a new directory")
+                .arg(
+                    arg!(<repository> "Location of the repository to clone")
+                        .value_parser(clap::value_parser!(String))
+                        .required(true),
+                )
+                .arg(
+                    arg!([directory] "Directory where the repository will be cloned into")
+                        .value_parser(clap::value_parser!(PathBuf))
+                        .required(false),
+                ),
+        )
+        .subcommand(
+            Command::new("fetch")
+                .about("Download objects and refs from another repository")
+                .arg(
+                    arg!([remote] "Name of the remote")
+                        .value_parser(clap::value_parser!(String))
+                        .default_value("origin")
+                        .required(false),
+                ),
+        )
+        .subcommand(
+            Command::new("push")
+                .about("Update remote refs along with associated objects")
+                .arg(arg!(-f --force "Allow updates which are not fast-forward"))
+                .arg(arg!(--"no-verify" "Skip the pre-push hook"))
+                .arg(
+                    arg!([remote] "Name of the remote")
+                        .value_parser(clap::value_parser!(String))
+                        .default_value("origin")
+                        .required(false),
+                )
+                .arg(
+                    arg!([refspec] "What to push as <src>[:<dst>]")
+                        .value_parser(clap::value_parser!(String))
+                        .required(false),
+                ),
+        )
+        .subcommand(
+            Command::new("bundle")
+                .about("Move objects and refs by archive")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("create")
+                        .about("Create a bundle with the given range of revisions")
+                        .arg(
+                            arg!(<file> "Path of the bundle")
+                                .value_parser(clap::value_parser!(PathBuf))
+                                .required(true),
+                        )
+                        .arg(
+                            arg!(<range> ... "Revisions to include (e.g. main or v1..main)")
+                                .value_parser(clap::value_parser!(String))
+                                .allow_hyphen_values(true)
+                                .required(true),
+                        ),
+                )
+                .subcommand(
+                    Command::new("verify")
+                        .about("Check that a bundle is valid and can be applied")
+                        .arg(
+                            arg!(<file> "Path of the bundle")
+                                .value_parser(clap::value_parser!(PathBuf))
+                                .required(true),
+                        ),
+                )
+                .subcommand(
+                    Command::new("unbundle")
+                        .about("Store the objects from a bundle into the repository")
+                        .arg(
+                            arg!(<file> "Path of the bundle")
+                                .value_parser(clap::value_parser!(PathBuf))
+                                .required(true),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("ls-tree")
+                .about("List the contents of a tree object")
+                .arg(arg!(r: -r "Recurse into subtrees"))
+                .arg(
+                    arg!(<tree> "The tree-ish object to list")
+                        .value_parser(clap::value_parser!(String))
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            Command::new("ls-files").about("Show the files that would be recorded by a commit"),
+        )
+        .subcommand(
+            Command::new("show-ref")
+                .about("List references along with the object they point to")
+                .arg(arg!(--heads "Only show branches"))
+                .arg(arg!(--tags "Only show tags")),
+        )
+        .subcommand(
+            Command::new("for-each-ref")
+                .about("Output information on each reference")
+                .arg(
+                    arg!(--format <format> "Format with fields such as %(refname)")
+                        .value_parser(clap::value_parser!(String))
+                        .default_value("%(objectname) %(objecttype)\t%(refname)")
+                        .required(false),
+                )
+                .arg(
+                    arg!([pattern] ... "Only show references starting with these")
+                        .value_parser(clap::value_parser!(String))
+                        .required(false),
+                ),
+        )
+        .subcommand(
+            Command::new("rev-list")
+                .about("List commits in reverse chronological order")
+                .arg(arg!(--objects "Also list the trees and blobs from the commits"))
+                .arg(
+                    arg!(<range> ... "Revisions to include, or exclude with ^rev or rev..rev")
+                        .value_parser(clap::value_parser!(String))
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            Command::new("symbolic-ref")
+                .about("Read or modify symbolic references")
+                .arg(arg!(--short "Shorten the name of the branch being shown"))
+                .arg(
+                    arg!(<name> "Name of the symbolic reference")
+                        .value_parser(clap::value_parser!(String))
+                        .required(true),
+                )
+                .arg(
+                    arg!([target] "Reference to point to")
+                        .value_parser(clap::value_parser!(String))
+                        .required(false),
+                ),
+        )
+        .subcommand(
+            Command::new("import-git")
+                .about("Import branches and tags from a Git repository")
+                .arg(
+                    arg!(<path> "Path to the Git repository or its .git directory")
+                        .value_parser(clap::value_parser!(PathBuf))
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            Command::new("export-git")
+                .about("Export branches and tags into a new Git repository")
+                .arg(
+                    arg!(<directory> "Directory where the .git directory will be created")
+                        .value_parser(clap::value_parser!(PathBuf))
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            Command::new("fast-export")
+                .about("Write the history as a stream for fast-import")
+                .arg(
+                    arg!([refs] ... "References to export (all branches and tags by default)")
+                        .value_parser(clap::value_parser!(String))
+                        .required(false),
+                ),
+        )
+        .subcommand(
+            Command::new("fast-import")
+                .about("Read a stream from fast-export from the standard input"),
+        )
+        .subcommand(
+            Command::new("serve")
+                .about("Serve the repository over HTTP")
+                .arg(
+                    arg!(--bind <address> "Address to listen on")
+                        .value_parser(clap::value_parser!(String))
+                        .default_value("127.0.0.1")
+                        .required(false),
+                )
+                .arg(
+                    arg!(-p --port <port> "Port to listen on")
+                        .value_parser(clap::value_parser!(u16))
+                        .default_value("8080")
+                        .required(false),
+                )
+                .arg(arg!(--"allow-push" "Accept pushes from anyone who can reach the server")),
+        )
 }
 
 fn main() -> Result<()> {
@@ -108,19 +601,47 @@ fn main() -> Result<()> {
                 Some(name) => name,
                 None => &cur,
             };
-            init::init(dir)?;
+            init::init(dir, sm.get_one::<HashAlgorithm>("object-format").copied())?;
         }
         Some(("hash-object", sm)) => {
-            object::hash(
-                sm.get_one::<PathBuf>("file").unwrap(),
-                object::Kind::Blob,
-                true,
-            )?;
+            let kind = object::Kind::from_str(sm.get_one::<String>("type").unwrap()).unwrap();
+            let write = sm.get_flag("w");
+
+            if sm.get_flag("stdin") {
+                let mut contents = vec![];
+                std::io::stdin().read_to_end(&mut contents)?;
+
+                println!("{}", object::hash_object(&contents, kind, write)?);
+            }
+            for path in sm.get_many::<PathBuf>("file").into_iter().flatten() {
+                let contents = std::fs::read(path)
+                    .with_context(|| format!("could not read '{}'", path.display()))?;
+
+                println!("{}", object::hash_object(&contents, kind, write)?);
+            }
         }
         Some(("cat-file", sm)) => {
-            let oid = commit::ref_to_oid(sm.get_one::<String>("object").unwrap())?;
+            if sm.get_flag("batch") || sm.get_flag("batch-check") {
+                object::batch(sm.get_flag("batch"))?;
+                return Ok(());
+            }
+
+            let name = sm.get_one::<String>("object").unwrap();
+            if sm.get_flag("e") {
+                let exists = commit::ref_to_oid(name).and_then(|oid| object::get(&oid));
+                std::process::exit(if exists.is_ok() { 0 } else { 1 });
+            }
 
-            object::cat(&oid)?;
+            let oid = commit::ref_to_oid(name)?;
+            if sm.get_flag("t") {
+                println!("{}", object::get(&oid)?.kind);
+            } else if sm.get_flag("s") {
+                println!("{}", object::get(&oid)?.contents.len());
+            } else if sm.get_flag("p") {
+                object::pretty(&oid)?;
+            } else {
+                object::cat(&oid)?;
+            }
         }
         Some(("write-tree", _sm)) => {
             let cur = std::env::current_dir().unwrap();
@@ -132,39 +653,153 @@ fn main() -> Result<()> {
             tree::read_tree(&oid)?;
         }
         Some(("commit", sm)) => {
-            let message = match sm.get_one::<String>("message") {
-                Some(v) => v.to_owned(),
-                None => commit::editor()?,
-            };
-
-            commit::commit(message)?;
+            commit::commit(
+                sm.get_one::<String>("message"),
+                sm.get_flag("amend"),
+                !sm.get_flag("no-verify"),
+            )?;
         }
         Some(("log", sm)) => {
             let revision = match sm.get_one::<String>("from") {
                 Some(v) => commit::ref_to_oid(v)?,
-                None => {
-                    let revision = commit::get_ref(&String::from("HEAD"))?;
+                None => match commit::get_ref(&String::from("HEAD"))? {
+                    Some(revision) => revision,
+                    None => bail!("current branch has no commit yet"),
+                },
+            };
 
-                    if revision.is_empty() {
-                        bail!("current branch has no commit yet");
-                    }
+            commit::log(&revision)?;
+        }
+        Some(("show", sm)) => {
+            commit::show(sm.get_one::<String>("object").unwrap())?;
+        }
+        Some(("blame", sm)) => {
+            blame::blame(
+                sm.get_one::<String>("file").unwrap(),
+                sm.get_one::<String>("rev").unwrap(),
+                sm.get_one::<String>("range"),
+                sm.get_flag("porcelain"),
+            )?;
+        }
+        Some(("merge-base", sm)) => {
+            let commits = sm
+                .get_many::<String>("commits")
+                .unwrap()
+                .cloned()
+                .collect::<Vec<_>>();
 
-                    revision
-                }
+            let found = if sm.get_flag("is-ancestor") {
+                graph::is_ancestor_of(&commits)?
+            } else {
+                graph::merge_base(&commits, sm.get_flag("all"), sm.get_flag("octopus"))?
+            };
+            if !found {
+                std::process::exit(1);
+            }
+        }
+        Some(("commit-graph", sub)) => match sub.subcommand() {
+            Some(("write", _sm)) => commit_graph::write_command()?,
+            _ => unreachable!(),
+        },
+        Some(("status", _sm)) => status::status()?,
+        Some(("rebase", sm)) => {
+            if sm.get_flag("continue") {
+                rebase::resume()?;
+            } else if sm.get_flag("skip") {
+                rebase::skip()?;
+            } else if sm.get_flag("abort") {
+                rebase::abort()?;
+            } else {
+                rebase::start(
+                    sm.get_one::<String>("upstream").unwrap(),
+                    sm.get_one::<String>("onto"),
+                    sm.get_flag("interactive"),
+                    sm.get_flag("autosquash"),
+                )?;
+            }
+        }
+        Some((name @ ("cherry-pick" | "revert"), sm)) => {
+            let operation = match name {
+                "cherry-pick" => sequencer::Operation::CherryPick,
+                _ => sequencer::Operation::Revert,
             };
 
-            commit::log(&revision)?;
+            if sm.get_flag("continue") {
+                sequencer::resume()?;
+            } else if sm.get_flag("abort") {
+                sequencer::abort()?;
+            } else {
+                let commits = sm
+                    .get_many::<String>("commits")
+                    .unwrap()
+                    .cloned()
+                    .collect::<Vec<_>>();
+                sequencer::start(operation, &commits)?;
+            }
         }
-        Some(("checkout", sm)) => {
-            let oid = commit::ref_to_oid(sm.get_one::<String>("commit").unwrap())?;
+        Some(("stash", sub)) => match sub.subcommand() {
+            Some(("push", sm)) => {
+                let paths = sm
+                    .get_many::<String>("paths")
+                    .map(|paths| paths.cloned().collect::<Vec<_>>())
+                    .unwrap_or_default();
+                stash::push(sm.get_one::<String>("message"), &paths)?;
+            }
+            Some(("list", _sm)) => stash::list()?,
+            Some(("show", sm)) => stash::show(sm.get_one::<String>("stash"))?,
+            Some(("apply", sm)) => stash::apply(sm.get_one::<String>("stash"), false)?,
+            Some(("pop", sm)) => stash::apply(sm.get_one::<String>("stash"), true)?,
+            Some(("drop", sm)) => stash::drop(sm.get_one::<String>("stash"))?,
+            _ => stash::push(None, &[])?,
+        },
+        Some(("bisect", sub)) => match sub.subcommand() {
+            Some(("start", sm)) => {
+                let good = sm
+                    .get_many::<String>("good")
+                    .map(|good| good.cloned().collect::<Vec<_>>())
+                    .unwrap_or_default();
+                bisect::start(sm.get_one::<String>("bad"), &good)?;
+            }
+            Some((kind @ ("good" | "bad" | "skip"), sm)) => {
+                bisect::mark_and_continue(kind, sm.get_one::<String>("rev"))?;
+            }
+            Some(("reset", _sm)) => bisect::reset()?,
+            Some(("run", sm)) => {
+                let command = sm
+                    .get_many::<String>("command")
+                    .unwrap()
+                    .cloned()
+                    .collect::<Vec<_>>();
+                bisect::run(&command)?;
+            }
+            _ => unreachable!(),
+        },
+        Some(("restore", sm)) => {
+            if sm.get_flag("staged") {
+                bail!("there is no index in ruc, so there is nothing staged to restore");
+            }
+
+            let source = commit::ref_to_oid(sm.get_one::<String>("source").unwrap())?;
+            let paths = sm
+                .get_many::<String>("paths")
+                .unwrap()
+                .cloned()
+                .collect::<Vec<_>>();
 
-            commit::checkout(&oid)?;
+            tree::restore(&source, &paths)?;
+        }
+        Some(("checkout", sm)) => {
+            commit::checkout(sm.get_one::<String>("commit").unwrap())?;
         }
         Some(("tag", sub)) => match sub.subcommand() {
             Some(("-a", sm)) => {
                 let commit = commit::ref_to_oid(sm.get_one::<String>("commit").unwrap())?;
 
-                commit::create_tag(sm.get_one::<String>("name").unwrap(), &commit)?;
+                commit::create_tag(
+                    sm.get_one::<String>("name").unwrap(),
+                    &commit,
+                    sm.get_one::<String>("message"),
+                )?;
             }
             Some((command, _)) => {
                 println!("unknown option {} for the 'tag' command", command);
@@ -175,6 +810,122 @@ fn main() -> Result<()> {
         Some(("graph", _sm)) => {
             commit::graph()?;
         }
+        Some(("branch", sm)) => match sm.get_one::<String>("name") {
+            Some(name) => {
+                let start = commit::ref_to_oid(sm.get_one::<String>("start").unwrap())?;
+
+                commit::create_branch(name, &start)?;
+            }
+            None => commit::list_branches()?,
+        },
+        Some(("remote", sub)) => match sub.subcommand() {
+            Some(("add", sm)) => {
+                remote::add(
+                    sm.get_one::<String>("name").unwrap(),
+                    sm.get_one::<String>("url").unwrap(),
+                )?;
+            }
+            Some(("remove", sm)) => {
+                remote::remove(sm.get_one::<String>("name").unwrap())?;
+            }
+            _ => remote::list()?,
+        },
+        Some(("clone", sm)) => {
+            remote::clone(
+                sm.get_one::<String>("repository").unwrap(),
+                sm.get_one::<PathBuf>("directory"),
+            )?;
+        }
+        Some(("fetch", sm)) => {
+            remote::fetch(sm.get_one::<String>("remote").unwrap())?;
+        }
+        Some(("bundle", sub)) => match sub.subcommand() {
+            Some(("create", sm)) => {
+                let revs = sm
+                    .get_many::<String>("range")
+                    .unwrap()
+                    .cloned()
+                    .collect::<Vec<_>>();
+
+                bundle::create(sm.get_one::<PathBuf>("file").unwrap(), &revs)?;
+            }
+            Some(("verify", sm)) => {
+                bundle::verify(sm.get_one::<PathBuf>("file").unwrap())?;
+            }
+            Some(("unbundle", sm)) => {
+                bundle::unbundle(sm.get_one::<PathBuf>("file").unwrap())?;
+            }
+            _ => unreachable!(),
+        },
+        Some(("ls-tree", sm)) => {
+            let oid = commit::ref_to_oid(sm.get_one::<String>("tree").unwrap())?;
+
+            tree::ls_tree(&oid, sm.get_flag("r"))?;
+        }
+        Some(("ls-files", _sm)) => {
+            for path in tree::working_files(&init::WORKING_DIR)? {
+                println!("{}", path);
+            }
+        }
+        Some(("show-ref", sm)) => {
+            commit::show_ref(sm.get_flag("heads"), sm.get_flag("tags"))?;
+        }
+        Some(("for-each-ref", sm)) => {
+            let patterns = sm
+                .get_many::<String>("pattern")
+                .map(|p| p.cloned().collect::<Vec<_>>())
+                .unwrap_or_default();
+
+            commit::for_each_ref(sm.get_one::<String>("format").unwrap(), &patterns)?;
+        }
+        Some(("rev-list", sm)) => {
+            let revs = sm
+                .get_many::<String>("range")
+                .unwrap()
+                .cloned()
+                .collect::<Vec<_>>();
+
+            commit::print_rev_list(&commit::RevRange::parse(&revs)?, sm.get_flag("objects"))?;
+        }
+        Some(("symbolic-ref", sm)) => {
+            commit::symbolic_ref_command(
+                sm.get_one::<String>("name").unwrap(),
+                sm.get_one::<String>("target"),
+                sm.get_flag("short"),
+            )?;
+        }
+        Some(("import-git", sm)) => {
+            git::import(sm.get_one::<PathBuf>("path").unwrap())?;
+        }
+        Some(("export-git", sm)) => {
+            git::export(sm.get_one::<PathBuf>("directory").unwrap())?;
+        }
+        Some(("fast-export", sm)) => {
+            let refs = sm
+                .get_many::<String>("refs")
+                .map(|refs| refs.cloned().collect::<Vec<_>>())
+                .unwrap_or_default();
+
+            fast::export(&refs)?;
+        }
+        Some(("fast-import", _sm)) => {
+            fast::import()?;
+        }
+        Some(("serve", sm)) => {
+            http::serve(
+                sm.get_one::<String>("bind").unwrap(),
+                *sm.get_one::<u16>("port").unwrap(),
+                sm.get_flag("allow-push"),
+            )?;
+        }
+        Some(("push", sm)) => {
+            remote::push(
+                sm.get_one::<String>("remote").unwrap(),
+                sm.get_one::<String>("refspec"),
+                sm.get_flag("force"),
+                !sm.get_flag("no-verify"),
+            )?;
+        }
         Some((command, _)) => {
             println!(
                 "ruc: «{}» is not a valid command. See «ruc --help».",
@@ -0,0 +1,316 @@
+use crate::diff::{self, Edit};
+use crate::init::WORKING_DIR;
+use crate::object::{self, Kind};
+use crate::oid::Oid;
+use crate::tree::{self, Files, Mode};
+
+use anyhow::{bail, Context, Result};
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::fs;
+
+// Result of merging the changes from two sides against their common base. The
+// files contain the merged contents, where conflicting text files have their
+// conflicts marked (and their paths are listed in `marked`). Conflicts are
+// described as they are reported to the user.
+#[derive(Debug, Default)]
+pub struct Merge {
+    pub files: Files,
+    pub conflicts: Vec<String>,
+    pub marked: Vec<String>,
+}
+
+impl Merge {
+    pub fn is_clean(&self) -> bool {
+        self.conflicts.is_empty()
+    }
+}
+
+// Error returned when a merge into the working directory has conflicts. It
+// keeps the paths of the files which were left with conflict markers, so
+// commands that can be resumed are able to check that they were resolved.
+#[derive(Debug)]
+pub struct Conflicts(pub Vec<String>);
+
+impl fmt::Display for Conflicts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "merge conflicts")
+    }
+}
+
+impl std::error::Error for Conflicts {}
+
+// Returns the paths left with conflict markers by the merge that caused the
+// given error, if any.
+pub fn marked_paths(e: &anyhow::Error) -> Vec<String> {
+    match e.downcast_ref::<Conflicts>() {
+        Some(Conflicts(paths)) => paths.to_owned(),
+        None => vec![],
+    }
+}
+
+// Makes sure that none of the given files from the working directory still
+// have conflict markers. There's no index to tell whether a conflict was
+// resolved, so this is what tells resolutions apart from leftovers.
+pub fn check_resolved(paths: &[String]) -> Result<()> {
+    for path in paths {
+        let contents = match fs::read(WORKING_DIR.join(path)) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e).with_context(|| format!("could not read '{}'", path)),
+        };
+
+        let marked = String::from_utf8_lossy(&contents)
+            .lines()
+            .any(|line| line.starts_with("<<<<<<<") || line.starts_with(">>>>>>>"));
+        if marked {
+            bail!(
+                "'{}' still has conflict markers, resolve them before continuing",
+                path
+            );
+        }
+    }
+
+    Ok(())
+}
+
+// Piece of a merged text: either lines where both sides agree, or lines where
+// each side made a different change.
+enum Chunk<'a> {
+    Clean(&'a [&'a str]),
+    Conflict(&'a [&'a str], &'a [&'a str]),
+}
+
+// Returns the lines from `other` which are matched with lines from `base`,
+// indexed by the position of the line in `base`.
+fn matches(base: &[&str], other: &[&str]) -> HashMap<usize, usize> {
+    diff::diff(base, other)
+        .into_iter()
+        .filter_map(|edit| match edit {
+            Edit::Equal(x, y) => Some((x, y)),
+            _ => None,
+        })
+        .collect()
+}
+
+// Splits the changes from `ours` and `theirs` into chunks following the diff3
+// algorithm: stable regions are those where the three versions have the same
+// lines, and everything in between is taken from the side that changed it, or
+// it's a conflict if both did.
+fn chunks<'a>(base: &'a [&'a str], ours: &'a [&'a str], theirs: &'a [&'a str]) -> Vec<Chunk<'a>> {
+    let (match_ours, match_theirs) = (matches(base, ours), matches(base, theirs));
+    let (mut o, mut a, mut b) = (0, 0, 0);
+    let mut res = vec![];
+
+    let mut push = |o_end: usize, a_end: usize, b_end: usize, o: usize, a: usize, b: usize| {
+        let (base, ours, theirs) = (&base[o..o_end], &ours[a..a_end], &theirs[b..b_end]);
+
+        if ours == base || ours == theirs {
+            res.push(Chunk::Clean(theirs));
+        } else if theirs == base {
+            res.push(Chunk::Clean(ours));
+        } else {
+            res.push(Chunk::Conflict(ours, theirs));
+        }
+    };
+
+    loop {
+        // Count how many lines from here are the same in all three versions.
+        let mut i = 0;
+        while o + i < base.len()
+            && match_ours.get(&(o + i)) == Some(&(a + i))
+            && match_theirs.get(&(o + i)) == Some(&(b + i))
+        {
+            i += 1;
+        }
+
+        if i > 0 {
+            push(o + i, a + i, b + i, o, a, b);
+            (o, a, b) = (o + i, a + i, b + i);
+            continue;
+        }
+
+        // Otherwise look for the next line from the base that both sides kept,
+        // which is where the changes end.
+        let next =
+            (o..base.len()).find(|x| match_ours.contains_key(x) && match_theirs.contains_key(x));
+        match next {
+            Some(x) => {
+                let (a_end, b_end) = (match_ours[&x], match_theirs[&x]);
+                push(x, a_end, b_end, o, a, b);
+                (o, a, b) = (x, a_end, b_end);
+            }
+            None => {
+                push(base.len(), ours.len(), theirs.len(), o, a, b);
+                break;
+            }
+        }
+    }
+
+    res
+}
+
+// Pushes the given lines into `out`, making sure that the last one ends with a
+// newline so conflict markers always start on their own line.
+fn push_lines(out: &mut String, lines: &[&str]) {
+    for line in lines {
+        out.push_str(line);
+    }
+    if !out.is_empty() && !out.ends_with('\n') {
+        out.push('\n');
+    }
+}
+
+// Merges the changes from `ours` and `theirs` into `base`, line by line. It
+// returns the merged text, and whether there were conflicts, which are marked
+// in the text with the given labels.
+pub fn merge_text(base: &str, ours: &str, theirs: &str, labels: (&str, &str)) -> (String, bool) {
+    let (base, ours, theirs) = (diff::lines(base), diff::lines(ours), diff::lines(theirs));
+    let mut out = String::new();
+    let mut conflict = false;
+
+    for chunk in chunks(&base, &ours, &theirs) {
+        match chunk {
+            Chunk::Clean(lines) => lines.iter().for_each(|line| out.push_str(line)),
+            Chunk::Conflict(a, b) => {
+                conflict = true;
+                if !out.is_empty() && !out.ends_with('\n') {
+                    out.push('\n');
+                }
+                out.push_str(&format!("<<<<<<< {}\n", labels.0));
+                push_lines(&mut out, a);
+                out.push_str("=======\n");
+                push_lines(&mut out, b);
+                out.push_str(&format!(">>>>>>> {}\n", labels.1));
+            }
+        }
+    }
+
+    (out, conflict)
+}
+
+// Returns the contents of the given entry as text, or None if it's binary.
+fn text(entry: Option<&(Mode, Oid)>) -> Result<Option<String>> {
+    let contents = match entry {
+        Some((_, id)) => object::get(id)?.contents,
+        None => vec![],
+    };
+
+    if diff::is_binary(&contents) {
+        return Ok(None);
+    }
+    Ok(String::from_utf8(contents).ok())
+}
+
+// Merges the changes from the trees `ours` and `theirs` against the tree from
+// `base` (None meaning an empty tree), path by path. Paths changed on just one
+// side take that change, and text files changed on both sides are merged line
+// by line. Conflicting files are stored with their conflict markers, and for
+// the rest of conflicts our version is kept.
+pub fn merge_trees(
+    base: Option<&Oid>,
+    ours: &Oid,
+    theirs: &Oid,
+    labels: (&str, &str),
+) -> Result<Merge> {
+    let store = object::store();
+    let base = match base {
+        Some(id) => tree::flatten(store.as_ref(), id)?,
+        None => Files::new(),
+    };
+    let ours = tree::flatten(store.as_ref(), ours)?;
+    let theirs = tree::flatten(store.as_ref(), theirs)?;
+
+    let mut res = Merge::default();
+    let paths = base.keys().chain(ours.keys()).chain(theirs.keys());
+
+    for path in paths.collect::<BTreeSet<_>>() {
+        let (b, o, t) = (base.get(path), ours.get(path), theirs.get(path));
+
+        let merged = if o == t || b == t {
+            o.copied()
+        } else if b == o {
+            t.copied()
+        } else {
+            match (o, t) {
+                (Some(o), Some(t)) => Some(merge_file(path, b, o, t, labels, &mut res)?),
+                (Some(entry), None) | (None, Some(entry)) => {
+                    let deleted_by = if o.is_none() { labels.0 } else { labels.1 };
+                    res.conflicts.push(format!(
+                        "CONFLICT (modify/delete): {} deleted in {} and modified in the other side",
+                        path, deleted_by
+                    ));
+                    Some(*entry)
+                }
+                (None, None) => None,
+            }
+        };
+
+        if let Some(entry) = merged {
+            res.files.insert(path.to_owned(), entry);
+        }
+    }
+
+    Ok(res)
+}
+
+// Merges the changes from `base` to `theirs` into the tree from `ours`, which
+// is what the working directory is expected to contain, and writes the result
+// into the working directory. Conflicts are reported and left marked in the
+// working directory, in which case an error is returned. Otherwise, the ID of
+// the merged tree is returned.
+pub fn merge_into_working_dir(
+    ours: &Oid,
+    base: Option<&Oid>,
+    theirs: &Oid,
+    labels: (&str, &str),
+) -> Result<Oid> {
+    let store = object::store();
+    let merged = merge_trees(base, ours, theirs, labels)?;
+    tree::update_working_dir(&tree::flatten(store.as_ref(), ours)?, &merged.files)?;
+
+    if !merged.is_clean() {
+        for conflict in &merged.conflicts {
+            println!("{}", conflict);
+        }
+        return Err(Conflicts(merged.marked).into());
+    }
+
+    tree::build(store.as_ref(), &merged.files)
+}
+
+// Merges a file which was changed on both sides.
+fn merge_file(
+    path: &str,
+    base: Option<&(Mode, Oid)>,
+    ours: &(Mode, Oid),
+    theirs: &(Mode, Oid),
+    labels: (&str, &str),
+    res: &mut Merge,
+) -> Result<(Mode, Oid)> {
+    let mode = match base {
+        Some((mode, _)) if *mode == ours.0 => theirs.0,
+        _ => ours.0,
+    };
+    if ours.1 == theirs.1 {
+        return Ok((mode, ours.1));
+    }
+
+    let kind = if base.is_some() { "content" } else { "add/add" };
+    match (text(base)?, text(Some(ours))?, text(Some(theirs))?) {
+        (Some(b), Some(o), Some(t)) if mode != Mode::Symlink => {
+            let (merged, conflict) = merge_text(&b, &o, &t, labels);
+            if conflict {
+                res.conflicts
+                    .push(format!("CONFLICT ({}): Merge conflict in {}", kind, path));
+                res.marked.push(path.to_owned());
+            }
+            Ok((mode, object::hash_contents(merged.as_bytes(), Kind::Blob)?))
+        }
+        _ => {
+            res.conflicts
+                .push(format!("CONFLICT ({}): Merge conflict in {}", kind, path));
+            Ok(*ours)
+        }
+    }
+}
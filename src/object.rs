@@ -1,18 +1,209 @@
+use crate::commit_graph::{CommitGraph, COMMIT_GRAPH};
+use crate::config::Config;
 use crate::init::{RUC_DIR, WORKING_DIR};
+use crate::oid::{self, HashAlgorithm, Oid};
+use crate::tree;
 use anyhow::{bail, Context, Result};
-use sha1::{Digest, Sha1};
+use std::collections::HashMap;
 use std::fmt;
 use std::fs::File;
 use std::io::prelude::*;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
 
-#[derive(Debug, PartialEq)]
+// Backend where objects are saved into and fetched from. Objects are handed
+// over already encoded (that is, with the header and the contents), so stores
+// only need to care about keeping bytes around for a given ID.
+pub trait ObjectStore: Send + Sync {
+    // Returns the hash algorithm being used for the objects of this store.
+    fn algorithm(&self) -> HashAlgorithm;
+
+    // Returns the raw bytes for the object with the given ID.
+    fn read(&self, id: &Oid) -> Result<Vec<u8>>;
+
+    // Saves the given raw bytes under the given ID.
+    fn write(&self, id: &Oid, data: &[u8]) -> Result<()>;
+
+    // Returns true if there is an object with the given ID.
+    fn contains(&self, id: &Oid) -> bool;
+
+    // Returns the IDs of all the objects being stored.
+    fn ids(&self) -> Result<Vec<Oid>>;
+
+    // Returns the commit graph for the objects of this store, if there is one.
+    fn commit_graph(&self) -> Option<&CommitGraph> {
+        None
+    }
+}
+
+// Store that saves each object in its own file inside of a directory (i.e. the
+// `.ruc/objects` directory of a repository).
+pub struct LooseStore {
+    path: PathBuf,
+    algorithm: HashAlgorithm,
+    // The commit graph from `info/commit-graph`, which is only read when it's
+    // first needed. A missing or bad commit graph is simply not used.
+    commit_graph: OnceLock<Option<CommitGraph>>,
+}
+
+impl LooseStore {
+    pub fn new(path: PathBuf, algorithm: HashAlgorithm) -> LooseStore {
+        LooseStore {
+            path,
+            algorithm,
+            commit_graph: OnceLock::new(),
+        }
+    }
+
+    // Returns the store for the repository whose `.ruc` directory is given,
+    // honoring the object format from its configuration.
+    pub fn open(ruc_dir: &Path) -> Result<LooseStore> {
+        let config = Config::open(&ruc_dir.join("config"))?;
+        let algorithm = match config.get("core.objectformat") {
+            Some(format) => HashAlgorithm::from_str(format)?,
+            None => HashAlgorithm::default(),
+        };
+
+        Ok(LooseStore::new(ruc_dir.join("objects"), algorithm))
+    }
+}
+
+impl ObjectStore for LooseStore {
+    fn algorithm(&self) -> HashAlgorithm {
+        self.algorithm
+    }
+
+    fn read(&self, id: &Oid) -> Result<Vec<u8>> {
+        std::fs::read(self.path.join(id.to_string()))
+            .context(format!("while reading 'objects/{}'", id))
+    }
+
+    fn write(&self, id: &Oid, data: &[u8]) -> Result<()> {
+        // Store it in plain text, no compressing nor fancy splitting like Git
+        // does.
+        let mut file = File::create(self.path.join(id.to_string()))
+            .with_context(|| format!("while creating object {} in store", id))?;
+        file.write_all(data)
+            .with_context(|| format!("while saving object {} in store", id))?;
+
+        Ok(())
+    }
+
+    fn contains(&self, id: &Oid) -> bool {
+        self.path.join(id.to_string()).is_file()
+    }
+
+    fn ids(&self) -> Result<Vec<Oid>> {
+        let mut res = vec![];
+
+        for entry in std::fs::read_dir(&self.path)? {
+            let entry = entry?;
+
+            if entry.file_type()?.is_file() {
+                // Skip anything that does not look like an object.
+                if let Some(id) = entry
+                    .file_name()
+                    .to_str()
+                    .and_then(|n| Oid::from_hex(n).ok())
+                {
+                    res.push(id);
+                }
+            }
+        }
+
+        Ok(res)
+    }
+
+    fn commit_graph(&self) -> Option<&CommitGraph> {
+        self.commit_graph
+            .get_or_init(|| {
+                let path = self.path.join(COMMIT_GRAPH);
+                match path.is_file() {
+                    true => CommitGraph::open(&path, self.algorithm).ok(),
+                    false => None,
+                }
+            })
+            .as_ref()
+    }
+}
+
+// Store that keeps everything in memory. Useful for throwaway repositories and
+// for testing code without touching the filesystem.
+#[derive(Default)]
+pub struct MemoryStore {
+    algorithm: HashAlgorithm,
+    objects: Mutex<HashMap<Oid, Vec<u8>>>,
+}
+
+impl MemoryStore {
+    pub fn new(algorithm: HashAlgorithm) -> MemoryStore {
+        MemoryStore {
+            algorithm,
+            objects: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl ObjectStore for MemoryStore {
+    fn algorithm(&self) -> HashAlgorithm {
+        self.algorithm
+    }
+
+    fn read(&self, id: &Oid) -> Result<Vec<u8>> {
+        match self.objects.lock().unwrap().get(id) {
+            Some(data) => Ok(data.clone()),
+            None => bail!("object '{}' does not exist", id),
+        }
+    }
+
+    fn write(&self, id: &Oid, data: &[u8]) -> Result<()> {
+        self.objects.lock().unwrap().insert(*id, data.to_vec());
+
+        Ok(())
+    }
+
+    fn contains(&self, id: &Oid) -> bool {
+        self.objects.lock().unwrap().contains_key(id)
+    }
+
+    fn ids(&self) -> Result<Vec<Oid>> {
+        Ok(self.objects.lock().unwrap().keys().copied().collect())
+    }
+}
+
+lazy_static! {
+    // Store being used by default. Unless it's replaced via `set_store`, this
+    // is the loose store from the current repository.
+    static ref STORE: RwLock<Arc<dyn ObjectStore>> = RwLock::new(
+        match LooseStore::open(&WORKING_DIR.join(RUC_DIR)) {
+            Ok(store) => Arc::new(store),
+            Err(e) => {
+                println!("fatal: {}", e);
+                std::process::exit(1);
+            }
+        }
+    );
+}
+
+// Returns the store currently in use.
+pub fn store() -> Arc<dyn ObjectStore> {
+    STORE.read().unwrap().clone()
+}
+
+// Replaces the store to be used from now on by all the functions that don't
+// take a store explicitly.
+pub fn set_store(store: Arc<dyn ObjectStore>) {
+    *STORE.write().unwrap() = store;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Kind {
     None,
     Blob,
     Tree,
     Commit,
+    Tag,
 }
 
 impl fmt::Display for Kind {
@@ -22,6 +213,7 @@ impl fmt::Display for Kind {
             Kind::Blob => write!(f, "blob"),
             Kind::Tree => write!(f, "tree"),
             Kind::Commit => write!(f, "commit"),
+            Kind::Tag => write!(f, "tag"),
         }
     }
 }
@@ -35,6 +227,7 @@ impl std::str::FromStr for Kind {
             "blob" => Ok(Kind::Blob),
             "tree" => Ok(Kind::Tree),
             "commit" => Ok(Kind::Commit),
+            "tag" => Ok(Kind::Tag),
             _ => Ok(Kind::None),
         }
     }
@@ -43,67 +236,227 @@ impl std::str::FromStr for Kind {
 #[derive(Debug)]
 pub struct Object {
     pub kind: Kind,
-    pub contents: String,
+    pub contents: Vec<u8>,
 }
 
-pub fn hash_contents(contents: &String, kind: Kind) -> Result<String> {
-    let text = kind.to_string() + std::str::from_utf8(&[b'\x00']).unwrap() + contents;
+impl Object {
+    // Returns the contents of this object as text, which is what is expected
+    // from commits.
+    pub fn text(&self) -> Result<&str> {
+        std::str::from_utf8(&self.contents).context("bad encoding for object")
+    }
+}
+
+pub fn hash_contents(contents: &[u8], kind: Kind) -> Result<Oid> {
+    hash_contents_into(store().as_ref(), contents, kind)
+}
+
+// Returns the object as it's stored: a header with the kind of the object,
+// delimited by a \x00 byte, and then the contents. Note that the object is
+// saved in plain text out of simplicity.
+fn encode(contents: &[u8], kind: Kind) -> Vec<u8> {
+    let mut text = kind.to_string().into_bytes();
+    text.push(b'\0');
+    text.extend_from_slice(contents);
+
+    text
+}
 
-    // Hash it with SHA1 as in Git.
-    let mut hasher = Sha1::new();
-    hasher.update(&text);
-    let hashed = format!("{:x}", hasher.finalize());
+// Same as `hash_contents` but saving the object into the given store.
+pub fn hash_contents_into(store: &dyn ObjectStore, contents: &[u8], kind: Kind) -> Result<Oid> {
+    let text = encode(contents, kind);
 
-    // And store it in plain text, no compressing nor fancy splitting like Git does.
-    let op = WORKING_DIR.join(RUC_DIR).join("objects").join(&hashed);
-    let mut file =
-        File::create(op).with_context(|| format!("while creating object {} in store", &hashed))?;
-    file.write_all(text.as_bytes())
-        .with_context(|| format!("while saving object {} in store", &hashed))?;
+    // Hash it as in Git, with the algorithm picked up for the store.
+    let hashed = store.algorithm().hash(&text);
+
+    store.write(&hashed, &text)?;
 
     Ok(hashed)
 }
 
-pub fn hash(path: &Path, kind: Kind, verbose: bool) -> Result<String> {
-    // NOTE: The object will be saved in plain text out of simplicity, but there
-    // is a header beforehand to store stuff like the type of the object. The
-    // header is then delimited by a \x00 byte.
-    let contents = &std::fs::read_to_string(path)?;
+pub fn hash(path: &Path, kind: Kind) -> Result<Oid> {
+    hash_contents(&std::fs::read(path)?, kind)
+}
+
+// Returns an error if the given contents are not a valid object of the given
+// kind (e.g. a tree with a bad format or a commit without a tree).
+pub fn validate(contents: &[u8], kind: Kind) -> Result<()> {
+    let algorithm = store().algorithm();
+    let check_id = |id: &str| match Oid::from_hex(id) {
+        Ok(id) if id.algorithm() == algorithm => Ok(()),
+        _ => bail!("bad object ID '{}'", id),
+    };
 
-    let res = hash_contents(contents, kind)?;
-    if verbose {
-        println!(
-            "stored given file {} into the object database",
-            path.display()
-        );
+    match kind {
+        Kind::Blob => {}
+        Kind::Tree => {
+            tree::get_entries(contents, algorithm)?;
+        }
+        Kind::Commit | Kind::Tag => {
+            let text = std::str::from_utf8(contents).context("bad encoding for object")?;
+            let (headers, _) = crate::commit::split_headers(text);
+            let value = |name: &str| {
+                headers
+                    .iter()
+                    .find(|(key, _)| key == name)
+                    .map(|(_, value)| value.as_str())
+            };
+
+            if kind == Kind::Commit {
+                match headers.first() {
+                    Some((key, id)) if key == "tree" => check_id(id)?,
+                    _ => bail!("commits must start with a tree"),
+                }
+                for (_, id) in headers.iter().filter(|(key, _)| key == "parent") {
+                    check_id(id)?;
+                }
+            } else {
+                match value("object") {
+                    Some(id) => check_id(id)?,
+                    None => bail!("tags must have an object"),
+                }
+                match value("type").map(Kind::from_str) {
+                    Some(Ok(Kind::None)) | None => bail!("tags must have a valid type"),
+                    _ => {}
+                }
+                if value("tag").unwrap_or_default().is_empty() {
+                    bail!("tags must have a name");
+                }
+            }
+        }
+        Kind::None => bail!("unknown kind of object"),
     }
 
-    Ok(res)
+    Ok(())
 }
 
-pub fn get(object: &String) -> Result<Object> {
-    let path = WORKING_DIR.join(RUC_DIR).join("objects").join(object);
-    let contents =
-        std::fs::read_to_string(path).context(format!("while reading 'objects/{}'", object))?;
+// Computes the ID for the given contents as an object of the given kind,
+// making sure that they are valid first. The object is only saved into the
+// store if `write` is true.
+pub fn hash_object(contents: &[u8], kind: Kind, write: bool) -> Result<Oid> {
+    validate(contents, kind)?;
 
-    let v: Vec<&str> = contents
-        .splitn(2, std::str::from_utf8(&[b'\x00']).unwrap())
-        .collect();
-
-    if v.len() != 2 {
-        bail!("bad format for object {}", object);
+    if write {
+        hash_contents(contents, kind)
+    } else {
+        Ok(store().algorithm().hash(&encode(contents, kind)))
     }
+}
+
+pub fn get(object: &Oid) -> Result<Object> {
+    get_from(store().as_ref(), object)
+}
+
+// Same as `get` but fetching the object from the given store.
+pub fn get_from(store: &dyn ObjectStore, object: &Oid) -> Result<Object> {
+    let mut contents = store.read(object)?;
+
+    let header = match contents.iter().position(|&b| b == b'\0') {
+        Some(pos) => pos,
+        None => bail!("bad format for object {}", object),
+    };
+    let kind = match std::str::from_utf8(&contents[..header]) {
+        Ok(kind) => Kind::from_str(kind).unwrap(),
+        Err(_) => bail!("bad format for object {}", object),
+    };
 
     Ok(Object {
-        kind: Kind::from_str(v[0]).unwrap(),
-        contents: v[1].to_string(),
+        kind,
+        contents: contents.split_off(header + 1),
     })
 }
 
-pub fn cat(object: &String) -> Result<()> {
+// Returns the full object ID for the given prefix of hexadecimal digits. It
+// errors out if no object matches or if the prefix is ambiguous.
+pub fn expand_prefix(prefix: &str) -> Result<Oid> {
+    if !oid::is_hex_prefix(prefix) {
+        bail!("'{}' is not a valid object name", prefix);
+    }
+
+    // Full IDs are looked up directly, since going through all the objects is
+    // only needed for actual prefixes.
+    let store = store();
+    let prefix = prefix.to_lowercase();
+    if prefix.len() >= store.algorithm().hex_len() {
+        return match Oid::from_hex(&prefix) {
+            Ok(id) if store.contains(&id) => Ok(id),
+            _ => bail!("'{}' is not a valid object name", prefix),
+        };
+    }
+
+    let matches = store
+        .ids()?
+        .into_iter()
+        .filter(|id| id.to_string().starts_with(&prefix))
+        .collect::<Vec<_>>();
+
+    match matches.len() {
+        0 => bail!("'{}' is not a valid object name", prefix),
+        1 => Ok(matches[0]),
+        _ => bail!("short object ID {} is ambiguous", prefix),
+    }
+}
+
+pub fn cat(object: &Oid) -> Result<()> {
+    let res = get(object)?;
+
+    print!("Kind: {}\nContents:\n", res.kind);
+    if res.kind == Kind::Tree {
+        print!("{}", tree::describe(&res.contents)?);
+    } else {
+        std::io::stdout().write_all(&res.contents)?;
+    }
+    println!();
+
+    Ok(())
+}
+
+// Prints the contents of the given object in a human readable way. That is,
+// trees are described entry by entry and the rest are printed as they are
+// (commits and tags made by ruc don't end with a newline, so it's added).
+pub fn pretty(object: &Oid) -> Result<()> {
     let res = get(object)?;
 
-    print!("Kind: {}\nContents:\n{}\n", res.kind, res.contents);
+    match res.kind {
+        Kind::Tree => print!("{}", tree::describe(&res.contents)?),
+        Kind::Commit | Kind::Tag if !res.contents.ends_with(b"\n") => {
+            println!("{}", res.text()?)
+        }
+        _ => std::io::stdout().write_all(&res.contents)?,
+    }
+
+    Ok(())
+}
+
+// Reads object names from the standard input, one per line, and prints the
+// ID, the kind and the size of each object. If `contents` is true, then the
+// contents of the object are printed right after. Unknown objects are reported
+// as missing, so the caller can keep going.
+pub fn batch(contents: bool) -> Result<()> {
+    let stdin = std::io::stdin();
+    let stdout = std::io::stdout();
+    let mut out = stdout.lock();
+
+    for line in stdin.lock().lines() {
+        let name = line?;
+        let name = name.trim();
+        if name.is_empty() {
+            continue;
+        }
+
+        let found = crate::commit::ref_to_oid(name).and_then(|id| Ok((id, get(&id)?)));
+        match found {
+            Ok((id, obj)) => {
+                writeln!(out, "{} {} {}", id, obj.kind, obj.contents.len())?;
+                if contents {
+                    out.write_all(&obj.contents)?;
+                    writeln!(out)?;
+                }
+            }
+            Err(_) => writeln!(out, "{} missing", name)?,
+        }
+        out.flush()?;
+    }
 
     Ok(())
 }
@@ -0,0 +1,183 @@
+use anyhow::{bail, Result};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use std::fmt;
+
+// Maximum length in bytes of an object ID, which is the one from SHA-256.
+const MAX_LEN: usize = 32;
+
+// Hash algorithms that can be used in order to compute object IDs. Note that a
+// repository sticks with the same algorithm during all its life, as it's
+// picked up when initializing it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HashAlgorithm {
+    #[default]
+    Sha1,
+    Sha256,
+}
+
+impl HashAlgorithm {
+    // Returns the length in bytes of the IDs produced by this algorithm.
+    pub fn byte_len(&self) -> usize {
+        match self {
+            HashAlgorithm::Sha1 => 20,
+            HashAlgorithm::Sha256 => 32,
+        }
+    }
+
+    // Returns the length of IDs produced by this algorithm when formatted as
+    // hexadecimal strings.
+    pub fn hex_len(&self) -> usize {
+        self.byte_len() * 2
+    }
+
+    // Computes the object ID for the given data.
+    pub fn hash(&self, data: &[u8]) -> Oid {
+        match self {
+            HashAlgorithm::Sha1 => Oid::from_bytes(&Sha1::digest(data)).unwrap(),
+            HashAlgorithm::Sha256 => Oid::from_bytes(&Sha256::digest(data)).unwrap(),
+        }
+    }
+}
+
+impl fmt::Display for HashAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HashAlgorithm::Sha1 => write!(f, "sha1"),
+            HashAlgorithm::Sha256 => write!(f, "sha256"),
+        }
+    }
+}
+
+impl std::str::FromStr for HashAlgorithm {
+    type Err = anyhow::Error;
+
+    fn from_str(input: &str) -> Result<HashAlgorithm, Self::Err> {
+        match input.to_lowercase().as_str() {
+            "sha1" => Ok(HashAlgorithm::Sha1),
+            "sha256" => Ok(HashAlgorithm::Sha256),
+            _ => bail!("unknown object format '{}'", input),
+        }
+    }
+}
+
+// Identifier of an object inside of the object database. It's just the raw
+// bytes from the hash of the object, which can be either a SHA-1 or a SHA-256
+// one depending on the length.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Oid {
+    bytes: [u8; MAX_LEN],
+    len: u8,
+}
+
+impl Oid {
+    // Returns the object ID with all bytes set to zero for the given
+    // algorithm. This is never a valid ID for an object.
+    pub fn null(algorithm: HashAlgorithm) -> Oid {
+        Oid {
+            bytes: [0; MAX_LEN],
+            len: algorithm.byte_len() as u8,
+        }
+    }
+
+    // Builds an object ID from the raw bytes of a hash.
+    pub fn from_bytes(raw: &[u8]) -> Result<Oid> {
+        if raw.len() != HashAlgorithm::Sha1.byte_len()
+            && raw.len() != HashAlgorithm::Sha256.byte_len()
+        {
+            bail!("bad length for object ID: {} bytes", raw.len());
+        }
+
+        let mut bytes = [0; MAX_LEN];
+        bytes[..raw.len()].copy_from_slice(raw);
+
+        Ok(Oid {
+            bytes,
+            len: raw.len() as u8,
+        })
+    }
+
+    // Parses the given hexadecimal string as a full object ID.
+    pub fn from_hex(hex: &str) -> Result<Oid> {
+        // Checking the digits first matters, since `from_str_radix` also
+        // accepts a leading sign (e.g. "+a").
+        if (hex.len() != HashAlgorithm::Sha1.hex_len()
+            && hex.len() != HashAlgorithm::Sha256.hex_len())
+            || !hex.chars().all(|c| c.is_ascii_hexdigit())
+        {
+            bail!("'{}' is not a valid object ID", hex);
+        }
+
+        let mut raw = vec![];
+        for i in (0..hex.len()).step_by(2) {
+            match hex
+                .get(i..i + 2)
+                .and_then(|s| u8::from_str_radix(s, 16).ok())
+            {
+                Some(b) => raw.push(b),
+                None => bail!("'{}' is not a valid object ID", hex),
+            }
+        }
+
+        Oid::from_bytes(&raw)
+    }
+
+    // Returns the raw bytes of this object ID.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.len as usize]
+    }
+
+    // Returns the algorithm that produced this object ID.
+    pub fn algorithm(&self) -> HashAlgorithm {
+        if self.len as usize == HashAlgorithm::Sha1.byte_len() {
+            HashAlgorithm::Sha1
+        } else {
+            HashAlgorithm::Sha256
+        }
+    }
+
+    // Returns true if this is the null object ID.
+    pub fn is_null(&self) -> bool {
+        self.as_bytes().iter().all(|&b| b == 0)
+    }
+
+    // Returns the first `len` characters of the hexadecimal representation of
+    // this object ID.
+    pub fn abbrev(&self, len: usize) -> String {
+        let mut hex = self.to_string();
+        hex.truncate(len);
+        hex
+    }
+}
+
+impl fmt::Display for Oid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for b in self.as_bytes() {
+            write!(f, "{:02x}", b)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Oid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Oid({})", self)
+    }
+}
+
+impl std::str::FromStr for Oid {
+    type Err = anyhow::Error;
+
+    fn from_str(input: &str) -> Result<Oid, Self::Err> {
+        Oid::from_hex(input)
+    }
+}
+
+// Returns true if the given string looks like a (possibly abbreviated) object
+// ID. That is, it only contains hexadecimal digits and it's not too short to be
+// ambiguous nor too long to be an ID.
+pub fn is_hex_prefix(input: &str) -> bool {
+    input.len() >= 4
+        && input.len() <= HashAlgorithm::Sha256.hex_len()
+        && input.chars().all(|c| c.is_ascii_hexdigit())
+}
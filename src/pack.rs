@@ -0,0 +1,164 @@
+use crate::commit;
+use crate::object::{self, Kind, ObjectStore};
+use crate::oid::{HashAlgorithm, Oid};
+use crate::tree;
+
+use anyhow::{bail, Context, Result};
+use std::collections::HashSet;
+use std::str::FromStr;
+
+// Packs are the way objects are transferred between repositories. Their format
+// is pretty simple: a header line with the signature, the version, the hash
+// algorithm and the number of objects; followed by each object as a line with
+// its ID and its length, and then the raw bytes of the object as stored in the
+// object database.
+const SIGNATURE: &str = "RUCPACK";
+const VERSION: &str = "1";
+
+// Returns the IDs of all the objects reachable from the given tips (tags,
+// commits, trees and blobs), skipping the ones from the given exclusion set.
+pub fn reachable(
+    store: &dyn ObjectStore,
+    tips: &[Oid],
+    exclude: &HashSet<Oid>,
+) -> Result<Vec<Oid>> {
+    let mut res = vec![];
+    let mut seen: HashSet<Oid> = HashSet::new();
+    let mut pending = tips.to_vec();
+
+    while let Some(id) = pending.pop() {
+        if exclude.contains(&id) || !seen.insert(id) {
+            continue;
+        }
+
+        let obj = object::get_from(store, &id)?;
+        match obj.kind {
+            Kind::Commit => {
+                let commit = commit::get_commit_from(store, &id)?;
+                pending.push(commit.tree);
+                pending.extend(commit.parents);
+            }
+            Kind::Tag => pending.push(commit::tag_target(&obj)?),
+            Kind::Tree => {
+                for entry in tree::get_entries(&obj.contents, store.algorithm())? {
+                    pending.push(entry.id);
+                }
+            }
+            Kind::Blob => {}
+            Kind::None => bail!("unknown kind for object {}", id),
+        }
+
+        res.push(id);
+    }
+
+    Ok(res)
+}
+
+// Returns the IDs of the objects reachable from `wants` which are not
+// reachable from `haves`. Objects from `haves` which are not in the given store
+// are ignored, since they are unknown to it.
+pub fn missing(store: &dyn ObjectStore, wants: &[Oid], haves: &[Oid]) -> Result<Vec<Oid>> {
+    let known = haves
+        .iter()
+        .filter(|id| store.contains(id))
+        .copied()
+        .collect::<Vec<_>>();
+    let exclude = reachable(store, &known, &HashSet::new())?
+        .into_iter()
+        .collect::<HashSet<_>>();
+
+    reachable(store, wants, &exclude)
+}
+
+// Makes sure that all the objects reachable from the given tips are in the
+// given store. The walk stops at the objects from `known`, whose history is
+// assumed to be complete (e.g. the ones pointed to by references).
+pub fn check_connected(store: &dyn ObjectStore, tips: &[Oid], known: &[Oid]) -> Result<()> {
+    let known = known.iter().copied().collect::<HashSet<_>>();
+
+    reachable(store, tips, &known).context("some objects are missing")?;
+    Ok(())
+}
+
+// Returns a pack with the given objects from the given store.
+pub fn write(store: &dyn ObjectStore, ids: &[Oid]) -> Result<Vec<u8>> {
+    let mut res = format!(
+        "{} {} {} {}\n",
+        SIGNATURE,
+        VERSION,
+        store.algorithm(),
+        ids.len()
+    )
+    .into_bytes();
+
+    for id in ids {
+        let raw = store.read(id)?;
+
+        res.extend_from_slice(format!("{} {}\n", id, raw.len()).as_bytes());
+        res.extend_from_slice(&raw);
+    }
+
+    Ok(res)
+}
+
+// Returns the next line from the given data, and moves the data past it.
+fn next_line<'a>(data: &mut &'a [u8]) -> Result<&'a str> {
+    match data.iter().position(|&b| b == b'\n') {
+        Some(pos) => {
+            let line = std::str::from_utf8(&data[..pos]).context("bad pack")?;
+            *data = &data[pos + 1..];
+            Ok(line)
+        }
+        None => bail!("bad pack: unexpected end of data"),
+    }
+}
+
+// Unpacks the objects from the given pack into the given store, and returns
+// their IDs. The contents of each object are checked against its ID, so
+// corrupted packs are rejected.
+pub fn read(mut data: &[u8], store: &dyn ObjectStore) -> Result<Vec<Oid>> {
+    let header = next_line(&mut data)?.split_whitespace().collect::<Vec<_>>();
+    if header.len() != 4 || header[0] != SIGNATURE || header[1] != VERSION {
+        bail!("bad pack: unknown header");
+    }
+    if HashAlgorithm::from_str(header[2])? != store.algorithm() {
+        bail!(
+            "bad pack: it uses {} but the repository uses {}",
+            header[2],
+            store.algorithm()
+        );
+    }
+    let count = header[3].parse::<usize>().context("bad pack")?;
+
+    let mut res = vec![];
+    for _ in 0..count {
+        let line = next_line(&mut data)?;
+        let (id, len) = match line.split_once(' ') {
+            Some((id, len)) => (
+                Oid::from_hex(id)?,
+                len.parse::<usize>().context("bad pack")?,
+            ),
+            None => bail!("bad pack: unknown object header"),
+        };
+
+        if data.len() < len {
+            bail!("bad pack: unexpected end of data");
+        }
+        let (raw, rest) = data.split_at(len);
+        data = rest;
+
+        if store.algorithm().hash(raw) != id {
+            bail!("bad pack: object {} is corrupted", id);
+        }
+        if !store.contains(&id) {
+            store.write(&id, raw)?;
+        }
+        res.push(id);
+    }
+
+    if !data.is_empty() {
+        bail!("bad pack: trailing data");
+    }
+
+    Ok(res)
+}
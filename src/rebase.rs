@@ -0,0 +1,594 @@
+use crate::commit::{self, Commit};
+use crate::graph;
+use crate::hooks;
+use crate::init::{RUC_DIR, WORKING_DIR};
+use crate::merge;
+use crate::object;
+use crate::oid::Oid;
+use crate::tree;
+
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+// Directory inside of `.ruc` where the state of the rebase in progress is
+// kept. It contains:
+//   - head-name: the branch being rebased (missing if HEAD was detached).
+//   - orig-head: the commit where HEAD was before starting.
+//   - onto: the commit where commits are being replayed.
+//   - todo: the remaining steps, one per line (e.g. "pick <id> <subject>").
+//     Commits are given by their full ID, since abbreviated ones could become
+//     ambiguous or be shadowed by references while rebasing.
+//   - stopped: the step which could not be applied, if any.
+//   - conflicts: the files left with conflict markers by that step, if any.
+//   - amend: present if the rebase stopped on an "edit" step, so changes
+//     from the working directory are amended into HEAD when continuing.
+const REBASE_DIR: &str = "rebase-merge";
+
+// Help shown at the bottom of the todo list on interactive rebases.
+const TODO_HELP: &str = "
+# Commands:
+# p, pick <commit> = use commit
+# r, reword <commit> = use commit, but edit the commit message
+# e, edit <commit> = use commit, but stop for amending
+# s, squash <commit> = use commit, but meld into previous commit
+# f, fixup <commit> = like \"squash\", but discard this commit's log message
+# x, exec <command> = run command (the rest of the line) using shell
+# d, drop <commit> = remove commit
+#
+# These lines can be re-ordered; they are executed from top to bottom.
+#
+# If you remove a line here THAT COMMIT WILL BE LOST.
+#
+# However, if you remove everything, the rebase will be aborted.
+";
+
+// What to do with a commit from the todo list.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Action {
+    Pick,
+    Reword,
+    Edit,
+    Squash,
+    Fixup,
+    Drop,
+}
+
+// Single line from the todo list.
+enum Step {
+    Commit(Action, Oid),
+    Exec(String),
+}
+
+// Parses a line from the todo list, where commits are given by their full ID.
+fn parse_step(line: &str) -> Result<Step> {
+    parse_step_with(line, Oid::from_hex)
+}
+
+// Same as `parse_step` but resolving commits with the given function.
+fn parse_step_with(line: &str, resolve: impl Fn(&str) -> Result<Oid>) -> Result<Step> {
+    let (word, rest) = line.split_once(' ').unwrap_or((line, ""));
+    let action = match word {
+        "p" | "pick" => Action::Pick,
+        "r" | "reword" => Action::Reword,
+        "e" | "edit" => Action::Edit,
+        "s" | "squash" => Action::Squash,
+        "f" | "fixup" => Action::Fixup,
+        "d" | "drop" => Action::Drop,
+        "x" | "exec" if !rest.trim().is_empty() => return Ok(Step::Exec(rest.trim().to_owned())),
+        _ => bail!("bad step '{}' in the rebase todo list", line),
+    };
+
+    match rest.split_whitespace().next() {
+        Some(id) => Ok(Step::Commit(action, resolve(id)?)),
+        None => bail!("missing commit for step '{}' in the rebase todo list", line),
+    }
+}
+
+fn state_dir() -> PathBuf {
+    WORKING_DIR.join(RUC_DIR).join(REBASE_DIR)
+}
+
+// Returns true if there's a rebase in progress.
+pub fn in_progress() -> bool {
+    state_dir().is_dir()
+}
+
+fn read_state(name: &str) -> Result<Option<String>> {
+    match fs::read_to_string(state_dir().join(name)) {
+        Ok(contents) => Ok(Some(contents.trim_end().to_owned())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("could not read the rebase state '{}'", name)),
+    }
+}
+
+fn write_state(name: &str, contents: &str) -> Result<()> {
+    fs::write(state_dir().join(name), contents)
+        .with_context(|| format!("could not save the rebase state '{}'", name))
+}
+
+fn remove_state(name: &str) -> Result<()> {
+    match fs::remove_file(state_dir().join(name)) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+fn read_todo() -> Result<Vec<String>> {
+    Ok(read_state("todo")?
+        .unwrap_or_default()
+        .lines()
+        .map(|line| line.to_owned())
+        .collect())
+}
+
+fn write_todo(todo: &[String]) -> Result<()> {
+    let mut contents = todo.join("\n");
+    if !contents.is_empty() {
+        contents.push('\n');
+    }
+
+    write_state("todo", &contents)
+}
+
+// Moves commits whose subject starts with "fixup! " or "squash! " right after
+// the commit they refer to (by subject or by ID), turning them into the
+// corresponding action.
+fn autosquash(steps: &mut Vec<(Action, Commit)>) {
+    let mut idx = 0;
+
+    while idx < steps.len() {
+        let (mut target, mut action) = (steps[idx].1.subject(), None);
+        loop {
+            if let Some(rest) = target.strip_prefix("fixup! ") {
+                action = action.or(Some(Action::Fixup));
+                target = rest;
+            } else if let Some(rest) = target.strip_prefix("squash! ") {
+                action = action.or(Some(Action::Squash));
+                target = rest;
+            } else {
+                break;
+            }
+        }
+
+        let position = steps[..idx]
+            .iter()
+            .position(|(_, c)| c.subject() == target || c.id.to_string().starts_with(target));
+        if let (Some(action), Some(position)) = (action, position) {
+            // Go past other fixups already attached to the same commit.
+            let mut to = position + 1;
+            while to < idx && matches!(steps[to].0, Action::Fixup | Action::Squash) {
+                to += 1;
+            }
+
+            let (_, commit) = steps.remove(idx);
+            steps.insert(to, (action, commit));
+        }
+        idx += 1;
+    }
+}
+
+// Returns the line for the todo list for the given step. Commits are only
+// abbreviated for showing them to the user.
+fn format_step(action: Action, commit: &Commit, abbrev: bool) -> String {
+    let word = match action {
+        Action::Pick => "pick",
+        Action::Reword => "reword",
+        Action::Edit => "edit",
+        Action::Squash => "squash",
+        Action::Fixup => "fixup",
+        Action::Drop => "drop",
+    };
+
+    let id = if abbrev {
+        commit.id.abbrev(7)
+    } else {
+        commit.id.to_string()
+    };
+    format!("{} {} {}", word, id, commit.subject())
+}
+
+// Removes comments and empty lines from the given text as edited by the user.
+fn strip_comments(text: &str) -> String {
+    text.lines()
+        .filter(|line| !line.starts_with('#'))
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_owned()
+}
+
+// Lets the user edit the given commit message.
+fn edit_message(message: &str) -> Result<String> {
+    let text = format!(
+        "{}\n\n# Please enter the commit message for your changes. Lines starting\n\
+         # with '#' will be ignored, and an empty message aborts the commit.\n",
+        message.trim_end()
+    );
+
+    let message = strip_comments(&commit::edit_file("COMMIT_EDITMSG", &text)?);
+    if message.is_empty() {
+        bail!("aborting commit due to empty commit message");
+    }
+
+    Ok(message)
+}
+
+// Replays the commits from the current branch which are not reachable from
+// `upstream` on top of `onto` (or `upstream` itself if not given). Commits are
+// taken by following first parents from HEAD, and merge commits are dropped.
+// If `interactive` is true, the list of steps is given to the user for editing
+// before starting, and with `autosquash` the commits marked as fixups are moved
+// in place.
+pub fn start(
+    upstream: &str,
+    onto: Option<&String>,
+    interactive: bool,
+    autosquash_commits: bool,
+) -> Result<()> {
+    if in_progress() {
+        bail!("a rebase is already in progress, use --continue, --skip or --abort");
+    }
+
+    let head = commit::head_commit()?;
+    commit::ensure_clean(&head, "rebase")?;
+
+    let upstream = commit::get_commit(&commit::ref_to_oid(upstream)?)?.id;
+    let onto = match onto {
+        Some(name) => commit::get_commit(&commit::ref_to_oid(name)?)?,
+        None => commit::get_commit(&upstream)?,
+    };
+
+    let upstream_commits = graph::ancestors(object::store().as_ref(), &[upstream])?;
+
+    let mut commits = vec![];
+    let mut fork_point = None;
+    for commit in Commit::iter_as_parent(&head.id) {
+        if upstream_commits.contains(&commit.id) {
+            fork_point = Some(commit.id);
+            break;
+        }
+        if commit.parents.len() < 2 {
+            commits.push(commit);
+        }
+    }
+
+    let head_name = commit::symbolic_ref("HEAD")?;
+    if fork_point == Some(onto.id) && !interactive && !autosquash_commits {
+        let name = head_name.as_deref().unwrap_or("HEAD");
+        println!(
+            "Current branch {} is up to date.",
+            name.strip_prefix("refs/heads/").unwrap_or(name)
+        );
+        return Ok(());
+    }
+
+    let mut steps = commits
+        .into_iter()
+        .rev()
+        .map(|c| (Action::Pick, c))
+        .collect::<Vec<_>>();
+    if autosquash_commits {
+        autosquash(&mut steps);
+    }
+    let mut todo = steps
+        .iter()
+        .map(|(action, c)| format_step(*action, c, false))
+        .collect::<Vec<_>>();
+
+    fs::create_dir_all(state_dir()).context("could not create the rebase state")?;
+    if interactive {
+        let shown = steps
+            .iter()
+            .map(|(action, c)| format_step(*action, c, true))
+            .collect::<Vec<_>>();
+        let text = format!(
+            "{}\n\n# Rebase {} onto {} ({} commands)\n#{}",
+            shown.join("\n"),
+            head.id.abbrev(7),
+            onto.id.abbrev(7),
+            todo.len(),
+            TODO_HELP
+        );
+        let edited = commit::edit_file(&format!("{}/todo", REBASE_DIR), &text);
+        todo = match edited.and_then(|text| check_todo(&text)) {
+            Ok(todo) if !todo.is_empty() => todo,
+            Ok(_) => {
+                fs::remove_dir_all(state_dir())?;
+                bail!("nothing to do");
+            }
+            Err(e) => {
+                fs::remove_dir_all(state_dir())?;
+                return Err(e);
+            }
+        };
+    }
+
+    if let Some(name) = &head_name {
+        write_state("head-name", name)?;
+    }
+    write_state("orig-head", &head.id.to_string())?;
+    write_state("onto", &onto.id.to_string())?;
+    write_todo(&todo)?;
+
+    let store = object::store();
+    tree::update_working_dir(
+        &tree::flatten(store.as_ref(), &head.tree)?,
+        &tree::flatten(store.as_ref(), &onto.tree)?,
+    )?;
+    commit::detach_head(&onto.id)?;
+
+    run()
+}
+
+// Returns the steps from the given todo list as edited by the user, making
+// sure that all of them are valid.
+fn check_todo(text: &str) -> Result<Vec<String>> {
+    let todo = strip_comments(text)
+        .lines()
+        .map(|line| line.trim().to_owned())
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>();
+
+    // Commits can be given as any revision, which are turned into full IDs
+    // for the todo list that is saved.
+    let mut picked = false;
+    let mut res = vec![];
+    for line in todo {
+        let step = parse_step_with(&line, commit::ref_to_oid)?;
+        match step {
+            Step::Commit(Action::Squash | Action::Fixup, _) if !picked => {
+                bail!("cannot '{}' without a previous commit", line)
+            }
+            Step::Commit(Action::Drop, _) | Step::Exec(_) => {}
+            Step::Commit(_, _) => picked = true,
+        }
+
+        res.push(match step {
+            Step::Commit(_, id) => {
+                let (word, rest) = line.split_once(' ').unwrap_or((&line, ""));
+                let subject = rest.trim_start().split_once(' ').map_or("", |(_, s)| s);
+                format!("{} {} {}", word, id, subject).trim_end().to_owned()
+            }
+            Step::Exec(_) => line,
+        });
+    }
+
+    Ok(res)
+}
+
+// Commits the resolution for the step that stopped the rebase, if any, and
+// then keeps going with the remaining steps.
+pub fn resume() -> Result<()> {
+    if !in_progress() {
+        bail!("there is no rebase in progress");
+    }
+
+    if let Some(stopped) = read_state("stopped")? {
+        if let Some(conflicts) = read_state("conflicts")? {
+            let paths = conflicts
+                .lines()
+                .map(|line| line.to_owned())
+                .collect::<Vec<_>>();
+            merge::check_resolved(&paths)?;
+        }
+
+        if let Step::Commit(action, id) = parse_step(&stopped)? {
+            let tree = tree::traverse_write_tree(&WORKING_DIR)?;
+
+            // The commit was already going to be edited, so there's no need to
+            // stop again.
+            let action = if action == Action::Edit {
+                Action::Pick
+            } else {
+                action
+            };
+            commit_step(action, &commit::get_commit(&id)?, &tree)?;
+        }
+        remove_state("stopped")?;
+        remove_state("conflicts")?;
+    }
+
+    if read_state("amend")?.is_some() {
+        let head = commit::head_commit()?;
+        let tree = tree::traverse_write_tree(&WORKING_DIR)?;
+
+        if tree != head.tree {
+            let id = commit::write_commit(
+                &tree,
+                &head.parents,
+                &commit::author_of(&head)?,
+                &head.contents,
+            )?;
+            commit::detach_head(&id)?;
+            hooks::run("post-commit", &[], None)?;
+        }
+        remove_state("amend")?;
+    }
+
+    run()
+}
+
+// Drops the commit that stopped the rebase and keeps going with the remaining
+// steps.
+pub fn skip() -> Result<()> {
+    if !in_progress() {
+        bail!("there is no rebase in progress");
+    }
+
+    let head = commit::head_commit()?;
+    reset_working_dir(&head.tree)?;
+    remove_state("stopped")?;
+    remove_state("conflicts")?;
+    remove_state("amend")?;
+
+    run()
+}
+
+// Stops the rebase in progress, bringing back the branch and the working
+// directory to where they were before starting it.
+pub fn abort() -> Result<()> {
+    let orig_head = match read_state("orig-head")? {
+        Some(id) => commit::get_commit(&Oid::from_hex(&id)?)?,
+        None => bail!("there is no rebase in progress"),
+    };
+
+    reset_working_dir(&orig_head.tree)?;
+    match read_state("head-name")? {
+        Some(name) => {
+            commit::update_ref(&name, &orig_head.id)?;
+            commit::update_symbolic_ref("HEAD", &name)?;
+        }
+        None => commit::detach_head(&orig_head.id)?,
+    }
+
+    fs::remove_dir_all(state_dir()).context("could not remove the rebase state")
+}
+
+// Brings the working directory back to the given tree, regardless of what's in
+// there right now.
+fn reset_working_dir(tree: &Oid) -> Result<()> {
+    let store = object::store();
+
+    tree::update_working_dir(
+        &tree::working_dir_files()?,
+        &tree::flatten(store.as_ref(), tree)?,
+    )
+}
+
+// Runs the remaining steps from the todo list, stopping at the first one that
+// fails or at "edit" steps.
+fn run() -> Result<()> {
+    let mut todo = read_todo()?;
+
+    while !todo.is_empty() {
+        let line = todo.remove(0);
+        write_todo(&todo)?;
+
+        match parse_step(&line)? {
+            Step::Exec(command) => {
+                println!("Executing: {}", command);
+
+                let status = Command::new("sh")
+                    .arg("-c")
+                    .arg(&command)
+                    .current_dir(WORKING_DIR.as_path())
+                    .status()?;
+                if !status.success() {
+                    println!("You can fix the problem, and then run \"ruc rebase --continue\".");
+                    bail!("execution failed: {}", command);
+                }
+            }
+            Step::Commit(Action::Drop, _) => {}
+            Step::Commit(action, id) => {
+                let commit = commit::get_commit(&id)?;
+
+                if let Err(e) = apply(action, &commit) {
+                    write_state("stopped", &line)?;
+                    write_state("conflicts", &merge::marked_paths(&e).join("\n"))?;
+
+                    println!("Resolve all conflicts manually, then run \"ruc rebase --continue\".");
+                    println!("You can instead skip this commit with \"ruc rebase --skip\".");
+                    println!(
+                        "To abort and get back to the state before \"ruc rebase\", run \"ruc rebase --abort\"."
+                    );
+                    return Err(e);
+                }
+
+                if action == Action::Edit {
+                    write_state("amend", "")?;
+                    println!("Stopped at {}... {}", commit.id.abbrev(7), commit.subject());
+                    println!("You can amend the commit by changing the working directory, and");
+                    println!(
+                        "once you are satisfied with your changes, run \"ruc rebase --continue\"."
+                    );
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    finish()
+}
+
+// Applies the changes from the given commit on top of HEAD, and then commits
+// them as given by the action. On conflicts, the working directory is left
+// with the conflicting files marked, and an error is returned.
+fn apply(action: Action, commit: &Commit) -> Result<()> {
+    let head = commit::head_commit()?;
+    let base = match commit.parents.first() {
+        Some(parent) => Some(commit::get_commit(parent)?.tree),
+        None => None,
+    };
+
+    let label = format!("{} ({})", commit.id.abbrev(7), commit.subject());
+    let tree =
+        merge::merge_into_working_dir(&head.tree, base.as_ref(), &commit.tree, ("HEAD", &label))
+            .with_context(|| {
+                format!(
+                    "could not apply {}... {}",
+                    commit.id.abbrev(7),
+                    commit.subject()
+                )
+            })?;
+
+    commit_step(action, commit, &tree)
+}
+
+// Commits the given tree as the result of applying the given commit with the
+// given action. Picked commits keep their author and message (unless they are
+// reworded), and they are dropped if they end up introducing no changes (e.g.
+// because they were already applied upstream). Squashed commits are melded
+// into HEAD instead.
+fn commit_step(action: Action, commit: &Commit, tree: &Oid) -> Result<()> {
+    let head = commit::head_commit()?;
+
+    let id = match action {
+        Action::Squash | Action::Fixup => {
+            let message = if action == Action::Fixup {
+                head.contents.to_owned()
+            } else {
+                edit_message(&format!(
+                    "# This is a combination of 2 commits.\n{}\n\n{}",
+                    head.contents.trim_end(),
+                    commit.contents.trim_end()
+                ))?
+            };
+
+            commit::write_commit(tree, &head.parents, &commit::author_of(&head)?, &message)?
+        }
+        _ if *tree == head.tree => return Ok(()),
+        _ => {
+            let message = if action == Action::Reword {
+                edit_message(&commit.contents)?
+            } else {
+                commit.contents.to_owned()
+            };
+
+            commit::write_commit(tree, &[head.id], &commit::author_of(commit)?, &message)?
+        }
+    };
+
+    commit::detach_head(&id)?;
+    hooks::run("post-commit", &[], None)?;
+
+    Ok(())
+}
+
+// Points the branch being rebased to the current HEAD, and removes the state
+// of the rebase.
+fn finish() -> Result<()> {
+    let head = commit::head_commit()?;
+
+    match read_state("head-name")? {
+        Some(name) => {
+            commit::update_ref(&name, &head.id)?;
+            commit::update_symbolic_ref("HEAD", &name)?;
+            println!("Successfully rebased and updated {}.", name);
+        }
+        None => println!("Successfully rebased the detached HEAD."),
+    }
+
+    fs::remove_dir_all(state_dir()).context("could not remove the rebase state")
+}
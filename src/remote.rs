@@ -0,0 +1,522 @@
+use crate::bundle::BundleTransport;
+use crate::commit;
+use crate::config;
+use crate::graph;
+use crate::hooks;
+use crate::http::HttpTransport;
+use crate::init;
+use crate::init::RUC_DIR;
+use crate::object::{self, LooseStore, ObjectStore};
+use crate::oid::{HashAlgorithm, Oid};
+use crate::pack;
+use crate::tree;
+
+use anyhow::{bail, Context, Result};
+use std::path::{Path, PathBuf};
+
+// Update of a reference being requested when pushing into a remote. The old
+// value is the one the client expects the reference to have before the update,
+// so concurrent updates are not silently lost.
+#[derive(Debug)]
+pub struct RefUpdate {
+    pub name: String,
+    pub old: Option<Oid>,
+    pub new: Oid,
+}
+
+// Way to talk to a remote repository. All the operations on remotes (cloning,
+// fetching and pushing) are implemented on top of this, regardless of where the
+// remote repository is.
+pub trait Transport {
+    // Returns the hash algorithm used by the remote repository.
+    fn algorithm(&self) -> Result<HashAlgorithm>;
+
+    // Returns the references from the remote (HEAD, branches and tags) along
+    // with the objects they point to.
+    fn refs(&self) -> Result<Vec<(String, Oid)>>;
+
+    // Returns the reference being pointed to by HEAD on the remote, or None if
+    // it's detached.
+    fn head(&self) -> Result<Option<String>>;
+
+    // Returns a pack with the objects reachable from `wants` but not from
+    // `haves`.
+    fn fetch(&self, wants: &[Oid], haves: &[Oid]) -> Result<Vec<u8>>;
+
+    // Sends the given pack to the remote and applies the given updates.
+    fn push(&self, pack: &[u8], updates: &[RefUpdate], force: bool) -> Result<()>;
+}
+
+// Transport for repositories living on the local filesystem (e.g. on a shared
+// drive).
+pub struct LocalTransport {
+    ruc_dir: PathBuf,
+    store: LooseStore,
+}
+
+impl LocalTransport {
+    pub fn new(path: &Path) -> Result<LocalTransport> {
+        let ruc_dir = path.join(RUC_DIR);
+        if !ruc_dir.is_dir() {
+            bail!("'{}' is not a ruc repository", path.display());
+        }
+
+        Ok(LocalTransport {
+            store: LooseStore::open(&ruc_dir)?,
+            ruc_dir,
+        })
+    }
+}
+
+impl Transport for LocalTransport {
+    fn algorithm(&self) -> Result<HashAlgorithm> {
+        Ok(self.store.algorithm())
+    }
+
+    fn refs(&self) -> Result<Vec<(String, Oid)>> {
+        advertised_refs(&self.ruc_dir)
+    }
+
+    fn head(&self) -> Result<Option<String>> {
+        commit::symbolic_ref_in(&self.ruc_dir, "HEAD")
+    }
+
+    fn fetch(&self, wants: &[Oid], haves: &[Oid]) -> Result<Vec<u8>> {
+        upload_pack(&self.store, wants, haves)
+    }
+
+    fn push(&self, pack: &[u8], updates: &[RefUpdate], force: bool) -> Result<()> {
+        receive_pack(&self.ruc_dir, &self.store, pack, updates, force)
+    }
+}
+
+// Returns true if the given URL points to a repository being served over
+// HTTP rather than to a local path.
+fn is_http(url: &str) -> bool {
+    url.starts_with("http://") || url.starts_with("https://")
+}
+
+// Returns the transport to be used for the given URL.
+pub fn open(url: &str) -> Result<Box<dyn Transport>> {
+    if url.starts_with("https://") {
+        bail!("HTTPS is not supported, use plain HTTP instead");
+    } else if is_http(url) {
+        return Ok(Box::new(HttpTransport::new(url)?));
+    } else if Path::new(url).is_file() {
+        return Ok(Box::new(BundleTransport::new(Path::new(url))?));
+    }
+
+    Ok(Box::new(LocalTransport::new(Path::new(url))?))
+}
+
+// Returns the references being advertised by the repository on the given
+// `.ruc` directory: HEAD, branches and tags.
+pub fn advertised_refs(ruc_dir: &Path) -> Result<Vec<(String, Oid)>> {
+    let mut res = vec![];
+
+    if let Some(id) = commit::get_ref_in(ruc_dir, "HEAD")? {
+        res.push((String::from("HEAD"), id));
+    }
+    res.extend(commit::list_refs_in(ruc_dir, "refs/heads/")?);
+    res.extend(commit::list_refs_in(ruc_dir, "refs/tags/")?);
+
+    Ok(res)
+}
+
+// Server side of fetching: returns a pack from the given store with everything
+// needed by a client that wants `wants` and already has `haves`.
+pub fn upload_pack(store: &dyn ObjectStore, wants: &[Oid], haves: &[Oid]) -> Result<Vec<u8>> {
+    for want in wants {
+        if !store.contains(want) {
+            bail!("remote does not have object {}", want);
+        }
+    }
+
+    pack::write(store, &pack::missing(store, wants, haves)?)
+}
+
+// Server side of pushing: unpacks the given pack into the store and then
+// applies the given updates to the references on the given `.ruc` directory.
+// Updates which are not fast-forward are rejected unless `force` is given.
+pub fn receive_pack(
+    ruc_dir: &Path,
+    store: &dyn ObjectStore,
+    data: &[u8],
+    updates: &[RefUpdate],
+    force: bool,
+) -> Result<()> {
+    pack::read(data, store)?;
+
+    // Check every update before applying any of them, so a bad one does not
+    // leave the rest half applied.
+    let known = advertised_refs(ruc_dir)?
+        .into_iter()
+        .map(|(_, id)| id)
+        .collect::<Vec<_>>();
+    for update in updates {
+        commit::check_ref_name(&update.name)?;
+        if !update.name.starts_with("refs/heads/") && !update.name.starts_with("refs/tags/") {
+            bail!("refusing to update '{}'", update.name);
+        }
+
+        let current = commit::get_ref_in(ruc_dir, &update.name)?;
+        if current != update.old {
+            bail!(
+                "'{}' has been updated in the meantime, fetch first",
+                update.name
+            );
+        }
+        pack::check_connected(store, &[update.new], &known)
+            .with_context(|| format!("cannot update '{}'", update.name))?;
+        if let Some(current) = current {
+            if !force && !graph::is_ancestor(store, &current, &update.new)? {
+                bail!("non-fast-forward update of '{}'", update.name);
+            }
+        }
+    }
+
+    for update in updates {
+        commit::update_ref_in(ruc_dir, &update.name, &update.new)?;
+    }
+
+    Ok(())
+}
+
+// Returns the URL configured for the given remote.
+fn remote_url(name: &str) -> Result<String> {
+    match config::load()?.get(&format!("remote.{}.url", name)) {
+        Some(url) => Ok(url.to_owned()),
+        None => bail!("'{}' does not appear to be a ruc remote", name),
+    }
+}
+
+// Returns the transport for the given remote, making sure that it's
+// compatible with the current repository.
+fn open_remote(url: &str) -> Result<Box<dyn Transport>> {
+    let transport = open(url)?;
+
+    let algorithm = transport.algorithm()?;
+    if algorithm != object::store().algorithm() {
+        bail!(
+            "the remote uses {} but the repository uses {}",
+            algorithm,
+            object::store().algorithm()
+        );
+    }
+
+    Ok(transport)
+}
+
+// Returns the given URL as it should be stored for a remote. Local paths are
+// stored as absolute paths, so the remote can still be reached regardless of
+// the directory from which ruc is run.
+fn absolute_url(url: &str) -> String {
+    match Path::new(url).canonicalize() {
+        Ok(path) if !is_http(url) => path.to_string_lossy().into_owned(),
+        _ => url.to_owned(),
+    }
+}
+
+// Adds a new remote with the given name and URL.
+pub fn add(name: &str, url: &str) -> Result<()> {
+    commit::check_ref_name(name)?;
+
+    let mut config = config::load()?;
+    if config.subsections("remote").iter().any(|r| r == name) {
+        bail!("remote {} already exists", name);
+    }
+
+    config.set(&format!("remote.{}.url", name), &absolute_url(url))?;
+    config.save()
+}
+
+// Removes the remote with the given name along with its remote-tracking
+// branches.
+pub fn remove(name: &str) -> Result<()> {
+    let mut config = config::load()?;
+    if !config.subsections("remote").iter().any(|r| r == name) {
+        bail!("no such remote: '{}'", name);
+    }
+
+    config.remove_section(&format!("remote.{}", name));
+    config.save()?;
+
+    for (refname, _) in commit::list_refs(&format!("refs/remotes/{}/", name))? {
+        commit::delete_ref(&refname)?;
+    }
+
+    Ok(())
+}
+
+// Prints the configured remotes.
+pub fn list() -> Result<()> {
+    let config = config::load()?;
+
+    for name in config.subsections("remote") {
+        println!(
+            "{}\t{}",
+            name,
+            config.get(&format!("remote.{}.url", name)).unwrap_or("")
+        );
+    }
+
+    Ok(())
+}
+
+// Returns the IDs from the local references, which are the objects that we
+// can tell a remote that we already have.
+fn local_tips() -> Result<Vec<Oid>> {
+    let mut res = commit::list_refs("refs/")?
+        .into_iter()
+        .map(|(_, id)| id)
+        .collect::<Vec<_>>();
+
+    if let Some(head) = commit::get_ref(&String::from("HEAD"))? {
+        res.push(head);
+    }
+
+    Ok(res)
+}
+
+// Prints how the given local reference has been updated.
+fn print_update(old: Option<Oid>, new: &Oid, from: &str, to: &str) {
+    match old {
+        Some(old) => println!(
+            "   {}..{}  {} -> {}",
+            old.abbrev(7),
+            new.abbrev(7),
+            from,
+            to
+        ),
+        None => println!(" * [new]           {} -> {}", from, to),
+    }
+}
+
+// Returns the references advertised by the given transport. Their names come
+// from the remote and are used as paths inside of `.ruc`, so they have to be
+// valid.
+fn remote_refs(transport: &dyn Transport) -> Result<Vec<(String, Oid)>> {
+    let refs = transport.refs()?;
+    for (name, _) in &refs {
+        commit::check_ref_name(name).context("bad reference advertised by the remote")?;
+    }
+
+    Ok(refs)
+}
+
+// Returns the branch being pointed to by HEAD on the remote, or None if it's
+// detached. HEAD can only point to a valid branch.
+fn remote_head(transport: &dyn Transport) -> Result<Option<String>> {
+    let target = match transport.head()? {
+        Some(target) => target,
+        None => return Ok(None),
+    };
+
+    commit::check_ref_name(&target).context("bad HEAD advertised by the remote")?;
+    match target.strip_prefix("refs/heads/") {
+        Some(branch) => Ok(Some(branch.to_owned())),
+        None => bail!(
+            "HEAD on the remote points to '{}', which is not a branch",
+            target
+        ),
+    }
+}
+
+// Fetches branches and tags from the given remote. Branches are stored as
+// remote-tracking branches under `refs/remotes/<name>/`.
+pub fn fetch(name: &str) -> Result<()> {
+    let url = remote_url(name)?;
+    let transport = open_remote(&url)?;
+    let refs = remote_refs(transport.as_ref())?;
+    let store = object::store();
+
+    let mut wants = vec![];
+    for (_, id) in &refs {
+        if !store.contains(id) && !wants.contains(id) {
+            wants.push(*id);
+        }
+    }
+    if !wants.is_empty() {
+        let haves = local_tips()?;
+        let data = transport.fetch(&wants, &haves)?;
+        pack::read(&data, store.as_ref())?;
+
+        for want in &wants {
+            if !store.contains(want) {
+                bail!("the remote did not send object {}", want);
+            }
+        }
+        pack::check_connected(store.as_ref(), &wants, &haves)?;
+    }
+
+    println!("From {}", url);
+    for (refname, id) in &refs {
+        if let Some(branch) = refname.strip_prefix("refs/heads/") {
+            let local = format!("refs/remotes/{}/{}", name, branch);
+            let old = commit::get_ref(&local)?;
+
+            if old != Some(*id) {
+                commit::update_ref(&local, id)?;
+                print_update(old, id, branch, &format!("{}/{}", name, branch));
+            }
+        } else if let Some(tag) = refname.strip_prefix("refs/tags/") {
+            if commit::get_ref(refname)?.is_none() {
+                commit::update_ref(refname, id)?;
+                print_update(None, id, tag, tag);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Clones the repository from the given URL into the given directory. If no
+// directory is given, then the last component of the URL is used.
+pub fn clone(url: &str, directory: Option<&PathBuf>) -> Result<()> {
+    let url = absolute_url(url);
+    let transport = open(&url)?;
+    let remote_head = remote_head(transport.as_ref())?;
+
+    // For HTTP URLs the name is taken from the path, unless the repository is
+    // served at the root, in which case the host is used (without the port).
+    let path = match url.split_once("://") {
+        Some((_, rest)) => match rest.split_once('/') {
+            Some((_, path)) if !path.trim_end_matches('/').is_empty() => path,
+            Some((host, _)) => host.split(':').next().unwrap_or(host),
+            None => rest.split(':').next().unwrap_or(rest),
+        },
+        None => url.as_str(),
+    };
+    let directory = match directory {
+        Some(dir) => dir.to_owned(),
+        None => match Path::new(path.trim_end_matches('/').trim_end_matches(".bundle")).file_name()
+        {
+            Some(name) => PathBuf::from(name),
+            None => bail!("could not guess a directory name for '{}'", url),
+        },
+    };
+    if directory.exists() && directory.read_dir()?.next().is_some() {
+        bail!(
+            "destination '{}' is not an empty directory",
+            directory.display()
+        );
+    }
+
+    std::fs::create_dir_all(&directory)?;
+    init::init(&directory, Some(transport.algorithm()?))?;
+    std::env::set_current_dir(&directory)
+        .with_context(|| format!("could not move into '{}'", directory.display()))?;
+
+    add("origin", &url)?;
+    fetch("origin")?;
+
+    // Mimic HEAD from the remote: if it points to a branch, then create a
+    // local branch for it, otherwise just detach HEAD into the same commit.
+    let tracking = remote_head
+        .as_ref()
+        .map(|branch| format!("refs/remotes/origin/{}", branch));
+
+    match (remote_head, tracking) {
+        (Some(branch), Some(tracking)) if commit::get_ref(&tracking)?.is_some() => {
+            let id = commit::get_ref(&tracking)?.unwrap();
+            commit::update_ref(&format!("refs/heads/{}", branch), &id)?;
+            commit::update_symbolic_ref("HEAD", &format!("refs/heads/{}", branch))?;
+            tree::read_tree(&commit::get_commit(&id)?.tree)?;
+        }
+        _ => {
+            let head = remote_refs(transport.as_ref())?
+                .into_iter()
+                .find(|(name, _)| name == "HEAD");
+            if let Some((_, id)) = head {
+                commit::detach_head(&id)?;
+                tree::read_tree(&commit::get_commit(&id)?.tree)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Pushes into the given remote. The refspec has the form `<src>[:<dst>]`,
+// where `src` is any revision and `dst` is the name of the remote branch. If
+// no refspec is given, then the current branch is pushed into a remote branch
+// with the same name. Updates which are not fast-forward are rejected unless
+// `force` is given.
+pub fn push(name: &str, refspec: Option<&String>, force: bool, verify: bool) -> Result<()> {
+    let (src, dst) = match refspec {
+        Some(spec) => match spec.split_once(':') {
+            Some((src, dst)) => (src.to_owned(), dst.to_owned()),
+            None => (spec.to_owned(), spec.to_owned()),
+        },
+        None => match commit::current_branch()? {
+            Some(branch) => (branch.clone(), branch),
+            None => bail!("HEAD is detached, specify what to push"),
+        },
+    };
+
+    let new = commit::ref_to_oid(&src)?;
+    let dst = if dst.starts_with("refs/") {
+        dst
+    } else {
+        format!("refs/heads/{}", dst)
+    };
+    commit::check_ref_name(&dst)?;
+
+    let url = remote_url(name)?;
+    let transport = open_remote(&url)?;
+    let refs = transport.refs()?;
+    let store = object::store();
+
+    println!("To {}", url);
+
+    let old = refs.iter().find(|(n, _)| *n == dst).map(|(_, id)| *id);
+    let short = dst.strip_prefix("refs/heads/").unwrap_or(&dst);
+    if old == Some(new) {
+        println!("Everything up-to-date");
+        return Ok(());
+    }
+
+    if let Some(old) = old {
+        if !force && (!store.contains(&old) || !graph::is_ancestor(store.as_ref(), &old, &new)?) {
+            bail!(
+                "rejected {} -> {} (non-fast-forward), fetch first or use --force",
+                src,
+                short
+            );
+        }
+    }
+
+    // The pre-push hook gets a line for each reference being pushed, with the
+    // local reference and its commit, and then the remote reference and the
+    // commit it had (or the null ID if it's new).
+    if verify {
+        let local = commit::full_ref_name(&src)?.unwrap_or_else(|| src.clone());
+        let input = format!(
+            "{} {} {} {}\n",
+            local,
+            new,
+            dst,
+            old.unwrap_or_else(|| Oid::null(new.algorithm()))
+        );
+        hooks::run_pre("pre-push", &[name, &url], Some(&input))?;
+    }
+
+    let haves = refs.iter().map(|(_, id)| *id).collect::<Vec<_>>();
+    let data = pack::write(
+        store.as_ref(),
+        &pack::missing(store.as_ref(), &[new], &haves)?,
+    )?;
+    let update = RefUpdate {
+        name: dst.clone(),
+        old,
+        new,
+    };
+    transport.push(&data, &[update], force)?;
+
+    if let Some(branch) = dst.strip_prefix("refs/heads/") {
+        commit::update_ref(&format!("refs/remotes/{}/{}", name, branch), &new)?;
+    }
+    print_update(old, &new, &src, short);
+
+    Ok(())
+}
@@ -0,0 +1,297 @@
+use crate::commit::{self, Commit, RevRange};
+use crate::hooks;
+use crate::init::{RUC_DIR, WORKING_DIR};
+use crate::merge;
+use crate::object;
+use crate::oid::Oid;
+use crate::tree;
+
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::path::PathBuf;
+
+// Directory inside of `.ruc` where the state of a cherry-pick or a revert in
+// progress is kept. It contains:
+//   - orig-head: the commit where HEAD was before starting.
+//   - todo: the remaining steps, one per line (e.g. "revert <id> <subject>"),
+//     where commits are given by their full ID.
+//   - stopped: the step which could not be applied, if any.
+//   - conflicts: the files left with conflict markers by that step, if any.
+const SEQUENCER_DIR: &str = "sequencer";
+
+// Whether changes from commits are applied or undone.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operation {
+    CherryPick,
+    Revert,
+}
+
+impl Operation {
+    fn word(&self) -> &'static str {
+        match self {
+            Operation::CherryPick => "pick",
+            Operation::Revert => "revert",
+        }
+    }
+
+    fn command(&self) -> &'static str {
+        match self {
+            Operation::CherryPick => "cherry-pick",
+            Operation::Revert => "revert",
+        }
+    }
+}
+
+fn state_dir() -> PathBuf {
+    WORKING_DIR.join(RUC_DIR).join(SEQUENCER_DIR)
+}
+
+fn read_state(name: &str) -> Result<Option<String>> {
+    match fs::read_to_string(state_dir().join(name)) {
+        Ok(contents) => Ok(Some(contents.trim_end().to_owned())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("could not read the sequencer state '{}'", name)),
+    }
+}
+
+fn write_state(name: &str, contents: &str) -> Result<()> {
+    fs::write(state_dir().join(name), contents)
+        .with_context(|| format!("could not save the sequencer state '{}'", name))
+}
+
+fn write_todo(todo: &[String]) -> Result<()> {
+    write_state(
+        "todo",
+        &todo.iter().map(|l| format!("{}\n", l)).collect::<String>(),
+    )
+}
+
+// Returns the operation and the commit from the given line of the todo list.
+fn parse_step(line: &str) -> Result<(Operation, Commit)> {
+    let mut words = line.split_whitespace();
+
+    let operation = match words.next() {
+        Some("pick") => Operation::CherryPick,
+        Some("revert") => Operation::Revert,
+        _ => bail!("bad step '{}' in the sequencer todo list", line),
+    };
+    match words.next() {
+        Some(id) => Ok((operation, commit::get_commit(&Oid::from_hex(id)?)?)),
+        None => bail!("bad step '{}' in the sequencer todo list", line),
+    }
+}
+
+// Returns the commits given by the list of revisions, oldest first. Ranges
+// (e.g. "A..B" or "^A B") are expanded into the commits they contain, while
+// plain revisions refer to a single commit.
+fn commits_from(revs: &[String]) -> Result<Vec<Commit>> {
+    if revs
+        .iter()
+        .any(|rev| rev.contains("..") || rev.starts_with('^'))
+    {
+        let mut commits = commit::rev_list(&RevRange::parse(revs)?)?;
+        commits.reverse();
+        return Ok(commits);
+    }
+
+    revs.iter()
+        .map(|rev| commit::get_commit(&commit::ref_to_oid(rev)?))
+        .collect()
+}
+
+// Applies the changes from the given revisions onto HEAD (or the inverse of
+// them if reverting), creating a new commit for each one.
+pub fn start(operation: Operation, revs: &[String]) -> Result<()> {
+    if state_dir().is_dir() {
+        bail!("a cherry-pick or revert is already in progress, use --continue or --abort");
+    }
+
+    let head = commit::head_commit()?;
+    commit::ensure_clean(&head, operation.command())?;
+
+    let commits = commits_from(revs)?;
+    if let Some(merge) = commits.iter().find(|c| c.parents.len() > 1) {
+        bail!("commit {} is a merge, which is not supported", merge.id);
+    }
+
+    let todo = commits
+        .iter()
+        .map(|c| format!("{} {} {}", operation.word(), c.id, c.subject()))
+        .collect::<Vec<_>>();
+
+    fs::create_dir_all(state_dir()).context("could not create the sequencer state")?;
+    write_state("orig-head", &head.id.to_string())?;
+    write_todo(&todo)?;
+
+    run()
+}
+
+// Commits the resolution for the step that stopped the sequence, and keeps
+// going with the remaining ones.
+pub fn resume() -> Result<()> {
+    let stopped = match read_state("stopped")? {
+        Some(line) => line,
+        None if state_dir().is_dir() => return run(),
+        None => bail!("no cherry-pick or revert in progress"),
+    };
+
+    if let Some(conflicts) = read_state("conflicts")? {
+        let paths = conflicts
+            .lines()
+            .map(|line| line.to_owned())
+            .collect::<Vec<_>>();
+        merge::check_resolved(&paths)?;
+    }
+
+    let (operation, commit) = parse_step(&stopped)?;
+    let tree = tree::traverse_write_tree(&WORKING_DIR)?;
+    commit_step(operation, &commit, &tree)?;
+    fs::remove_file(state_dir().join("stopped"))?;
+    fs::remove_file(state_dir().join("conflicts"))?;
+
+    run()
+}
+
+// Stops the sequence in progress, bringing back HEAD and the working directory
+// to where they were before starting it.
+pub fn abort() -> Result<()> {
+    let orig_head = match read_state("orig-head")? {
+        Some(id) => commit::get_commit(&Oid::from_hex(&id)?)?,
+        None => bail!("no cherry-pick or revert in progress"),
+    };
+
+    let store = object::store();
+    tree::update_working_dir(
+        &tree::working_dir_files()?,
+        &tree::flatten(store.as_ref(), &orig_head.tree)?,
+    )?;
+    commit::update_ref("HEAD", &orig_head.id)?;
+
+    fs::remove_dir_all(state_dir()).context("could not remove the sequencer state")
+}
+
+// Runs the remaining steps from the todo list, stopping at the first one
+// which cannot be applied cleanly.
+fn run() -> Result<()> {
+    let mut todo = read_state("todo")?
+        .unwrap_or_default()
+        .lines()
+        .map(|line| line.to_owned())
+        .collect::<Vec<_>>();
+
+    while !todo.is_empty() {
+        let line = todo.remove(0);
+        write_todo(&todo)?;
+
+        let (operation, commit) = parse_step(&line)?;
+        if let Err(e) = apply(operation, &commit) {
+            write_state("stopped", &line)?;
+            write_state("conflicts", &merge::marked_paths(&e).join("\n"))?;
+
+            let command = operation.command();
+            println!(
+                "After resolving the conflicts, run \"ruc {} --continue\".",
+                command
+            );
+            println!(
+                "To abort and get back to the state before \"ruc {}\", run \"ruc {} --abort\".",
+                command, command
+            );
+            return Err(e);
+        }
+    }
+
+    fs::remove_dir_all(state_dir()).context("could not remove the sequencer state")
+}
+
+// Merges the changes from the given commit into HEAD (or the inverse of them
+// when reverting) and commits the result.
+fn apply(operation: Operation, commit: &Commit) -> Result<()> {
+    let head = commit::head_commit()?;
+    let parent = match commit.parents.first() {
+        Some(parent) => Some(commit::get_commit(parent)?.tree),
+        None => None,
+    };
+    let label = format!("{} ({})", commit.id.abbrev(7), commit.subject());
+
+    let tree = match operation {
+        Operation::CherryPick => merge::merge_into_working_dir(
+            &head.tree,
+            parent.as_ref(),
+            &commit.tree,
+            ("HEAD", &label),
+        )
+        .with_context(|| {
+            format!(
+                "could not apply {}... {}",
+                commit.id.abbrev(7),
+                commit.subject()
+            )
+        })?,
+        Operation::Revert => {
+            // Reverting a root commit means going to an empty tree.
+            let parent = match parent {
+                Some(tree) => tree,
+                None => tree::build(object::store().as_ref(), &tree::Files::new())?,
+            };
+            merge::merge_into_working_dir(&head.tree, Some(&commit.tree), &parent, ("HEAD", &label))
+                .with_context(|| {
+                    format!(
+                        "could not revert {}... {}",
+                        commit.id.abbrev(7),
+                        commit.subject()
+                    )
+                })?
+        }
+    };
+
+    commit_step(operation, commit, &tree)
+}
+
+// Commits the given tree on top of HEAD as the result of applying or reverting
+// the given commit. Cherry-picked commits keep their author and message, with
+// a note on where they come from, while reverts get a message saying which
+// commit is being reverted.
+fn commit_step(operation: Operation, commit: &Commit, tree: &Oid) -> Result<()> {
+    let head = commit::head_commit()?;
+    if *tree == head.tree {
+        println!(
+            "Nothing to commit for {}, skipping it.",
+            commit.id.abbrev(7)
+        );
+        return Ok(());
+    }
+
+    let (author, message) = match operation {
+        Operation::CherryPick => (
+            match &commit.author {
+                Some(author) => author.to_owned(),
+                None => commit::signature("author")?,
+            },
+            format!(
+                "{}\n\n(cherry picked from commit {})",
+                commit.contents.trim_end(),
+                commit.id
+            ),
+        ),
+        Operation::Revert => (
+            commit::signature("author")?,
+            format!(
+                "Revert \"{}\"\n\nThis reverts commit {}.",
+                commit.subject(),
+                commit.id
+            ),
+        ),
+    };
+
+    let id = commit::write_commit(tree, &[head.id], &author, &message)?;
+    commit::update_ref("HEAD", &id)?;
+    hooks::run("post-commit", &[], None)?;
+    println!(
+        "[{}] {}",
+        id.abbrev(7),
+        message.lines().next().unwrap_or_default()
+    );
+
+    Ok(())
+}
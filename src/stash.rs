@@ -0,0 +1,249 @@
+use crate::commit;
+use crate::diff;
+use crate::init::{RUC_DIR, WORKING_DIR};
+use crate::merge;
+use crate::object;
+use crate::oid::Oid;
+use crate::tree::{self, Files};
+
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::path::PathBuf;
+
+// Reference pointing to the latest stash.
+const STASH_REF: &str = "refs/stash";
+
+// Entry from the log of `refs/stash`, which keeps track of all stashes. The
+// log is stored in the same format as Git's reflogs: one line per entry, from
+// oldest to newest, with the previous and the new value of the reference, the
+// identity of whoever made the change, and a message after a tab.
+struct Entry {
+    old: Oid,
+    new: Oid,
+    identity: String,
+    message: String,
+}
+
+fn log_path() -> PathBuf {
+    WORKING_DIR.join(RUC_DIR).join("logs").join(STASH_REF)
+}
+
+fn read_log() -> Result<Vec<Entry>> {
+    let contents = match fs::read_to_string(log_path()) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e).context("could not read the log for the stash"),
+    };
+
+    let mut entries = vec![];
+    for line in contents.lines() {
+        let (header, message) = line.split_once('\t').unwrap_or((line, ""));
+        let mut parts = header.splitn(3, ' ');
+
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some(old), Some(new), Some(identity)) => entries.push(Entry {
+                old: Oid::from_hex(old)?,
+                new: Oid::from_hex(new)?,
+                identity: identity.to_owned(),
+                message: message.to_owned(),
+            }),
+            _ => bail!("bad entry '{}' in the log for the stash", line),
+        }
+    }
+
+    Ok(entries)
+}
+
+// Writes the given entries into the log and points `refs/stash` to the newest
+// one. If there are no entries left, both the reference and the log are
+// removed.
+fn write_log(entries: &[Entry]) -> Result<()> {
+    let path = log_path();
+
+    match entries.last() {
+        Some(last) => {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            let contents = entries
+                .iter()
+                .map(|e| format!("{} {} {}\t{}\n", e.old, e.new, e.identity, e.message))
+                .collect::<String>();
+            fs::write(&path, contents).context("could not write the log for the stash")?;
+
+            commit::update_ref(STASH_REF, &last.new)
+        }
+        None => {
+            if path.exists() {
+                fs::remove_file(&path)?;
+            }
+            if commit::get_ref(STASH_REF)?.is_some() {
+                commit::delete_ref(STASH_REF)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+// Returns the position in the log for the given stash name, which can be
+// either "stash@{n}" or just "n", where 0 is the newest stash.
+fn find(entries: &[Entry], name: Option<&String>) -> Result<usize> {
+    let n = match name {
+        Some(name) => {
+            let number = name
+                .strip_prefix("stash@{")
+                .and_then(|rest| rest.strip_suffix('}'))
+                .unwrap_or(name);
+            match number.parse::<usize>() {
+                Ok(n) => n,
+                Err(_) => bail!("'{}' is not a valid stash name", name),
+            }
+        }
+        None => 0,
+    };
+
+    if entries.is_empty() {
+        bail!("no stash entries found");
+    }
+    if n >= entries.len() {
+        bail!("stash@{{{}}} does not exist", n);
+    }
+
+    Ok(entries.len() - 1 - n)
+}
+
+// Saves the changes from the working directory into a new stash, and brings
+// the working directory back to HEAD. If paths are given (relative to the
+// current directory), only changes beneath them are stashed. Since there's no
+// index, new files count as changes as well.
+pub fn push(message: Option<&String>, paths: &[String]) -> Result<()> {
+    let store = object::store();
+    let head = commit::head_commit()?;
+    let head_files = tree::flatten(store.as_ref(), &head.tree)?;
+    let working_files = tree::working_dir_files()?;
+    let paths = paths
+        .iter()
+        .map(|path| tree::normalize_pathspec(path))
+        .collect::<Result<Vec<_>>>()?;
+
+    let selected = |files: &Files| -> Files {
+        files
+            .iter()
+            .filter(|(file, _)| {
+                paths.is_empty() || paths.iter().any(|p| tree::matches_path(p, file))
+            })
+            .map(|(file, entry)| (file.to_owned(), *entry))
+            .collect()
+    };
+    let (old, new) = (selected(&working_files), selected(&head_files));
+    if old == new {
+        println!("No local changes to save");
+        return Ok(());
+    }
+
+    // The stashed tree is HEAD with the selected changes on top.
+    let mut stashed = head_files.clone();
+    stashed.retain(|file, _| !new.contains_key(file));
+    stashed.extend(old.clone());
+    let tree = tree::build(store.as_ref(), &stashed)?;
+
+    let branch = commit::current_branch()?.unwrap_or_else(|| String::from("(no branch)"));
+    let message = match message {
+        Some(message) => format!("On {}: {}", branch, message),
+        None => format!(
+            "WIP on {}: {} {}",
+            branch,
+            head.id.abbrev(7),
+            head.subject()
+        ),
+    };
+    let signature = commit::signature("committer")?;
+    let id = commit::write_commit(&tree, &[head.id], &signature, &message)?;
+
+    let mut entries = read_log()?;
+    entries.push(Entry {
+        old: commit::get_ref(STASH_REF)?.unwrap_or_else(|| Oid::null(id.algorithm())),
+        new: id,
+        identity: signature,
+        message: message.to_owned(),
+    });
+    write_log(&entries)?;
+
+    tree::update_working_dir(&old, &new)?;
+    println!("Saved working directory state {}", message);
+
+    Ok(())
+}
+
+// Lists all the stashes, newest first.
+pub fn list() -> Result<()> {
+    for (n, entry) in read_log()?.iter().rev().enumerate() {
+        println!("stash@{{{}}}: {}", n, entry.message);
+    }
+
+    Ok(())
+}
+
+// Shows the changes recorded in the given stash as a patch.
+pub fn show(name: Option<&String>) -> Result<()> {
+    let entries = read_log()?;
+    let stash = commit::get_commit(&entries[find(&entries, name)?].new)?;
+    let store = object::store();
+
+    let old = match stash.parents.first() {
+        Some(parent) => tree::flatten(store.as_ref(), &commit::get_commit(parent)?.tree)?,
+        None => Files::new(),
+    };
+    print!(
+        "{}",
+        diff::diff_files(&old, &tree::flatten(store.as_ref(), &stash.tree)?)?
+    );
+
+    Ok(())
+}
+
+// Applies the changes from the given stash on top of the working directory,
+// which can have local changes as long as they don't conflict. If `pop` is
+// true, the stash is dropped afterwards unless there were conflicts.
+pub fn apply(name: Option<&String>, pop: bool) -> Result<()> {
+    let entries = read_log()?;
+    let idx = find(&entries, name)?;
+    let stash = commit::get_commit(&entries[idx].new)?;
+    let base = match stash.parents.first() {
+        Some(parent) => Some(commit::get_commit(parent)?.tree),
+        None => None,
+    };
+
+    let ours = tree::traverse_write_tree(&WORKING_DIR)?;
+    let res = merge::merge_into_working_dir(
+        &ours,
+        base.as_ref(),
+        &stash.tree,
+        ("Updated upstream", "Stashed changes"),
+    );
+    if let Err(e) = res {
+        if pop {
+            println!("The stash entry is kept in case you need it again.");
+        }
+        return Err(e.context("could not apply the stash"));
+    }
+
+    if pop {
+        drop(name)?;
+    }
+
+    Ok(())
+}
+
+// Removes the given stash.
+pub fn drop(name: Option<&String>) -> Result<()> {
+    let mut entries = read_log()?;
+    let idx = find(&entries, name)?;
+    let n = entries.len() - 1 - idx;
+
+    let removed = entries.remove(idx);
+    write_log(&entries)?;
+    println!("Dropped stash@{{{}}} ({})", n, removed.new);
+
+    Ok(())
+}
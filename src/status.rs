@@ -0,0 +1,111 @@
+use crate::commit;
+use crate::config;
+use crate::graph;
+use crate::object;
+use crate::oid::Oid;
+use crate::tree::{self, Files};
+
+use anyhow::Result;
+use std::collections::BTreeSet;
+
+// Returns the short name (e.g. "origin/main") and the commit of the
+// remote-tracking branch for the given branch, if there is one. The remote is
+// taken from the `branch.<name>.remote` configuration value, which defaults to
+// "origin".
+fn upstream(branch: &str) -> Result<Option<(String, Oid)>> {
+    let remote = config::load()?
+        .get(&format!("branch.{}.remote", branch))
+        .unwrap_or("origin")
+        .to_owned();
+    let name = format!("{}/{}", remote, branch);
+
+    Ok(commit::get_ref(&format!("refs/remotes/{}", name))?.map(|id| (name, id)))
+}
+
+fn plural(count: usize) -> &'static str {
+    if count == 1 {
+        "commit"
+    } else {
+        "commits"
+    }
+}
+
+// Prints how far the given branch diverged from its upstream.
+fn print_divergence(branch: &str, id: &Oid) -> Result<()> {
+    let (name, upstream) = match upstream(branch)? {
+        Some(upstream) => upstream,
+        None => return Ok(()),
+    };
+
+    let (ahead, behind) = graph::ahead_behind(object::store().as_ref(), id, &upstream)?;
+    match (ahead, behind) {
+        (0, 0) => println!("Your branch is up to date with '{}'.", name),
+        (ahead, 0) => {
+            println!(
+                "Your branch is ahead of '{}' by {} {}.",
+                name,
+                ahead,
+                plural(ahead)
+            );
+            println!("  (use \"ruc push\" to publish your local commits)");
+        }
+        (0, behind) => println!(
+            "Your branch is behind '{}' by {} {}, and can be fast-forwarded.",
+            name,
+            behind,
+            plural(behind)
+        ),
+        (ahead, behind) => {
+            println!("Your branch and '{}' have diverged,", name);
+            println!(
+                "and have {} and {} different commits each, respectively.",
+                ahead, behind
+            );
+        }
+    }
+
+    Ok(())
+}
+
+// Shows the current branch, how far it diverged from its upstream, and the
+// changes from the working directory. Since there's no index, changes are just
+// the differences between the working directory and HEAD.
+pub fn status() -> Result<()> {
+    let head = commit::get_ref("HEAD")?;
+    let branch = commit::current_branch()?;
+
+    match (&branch, &head) {
+        (Some(branch), _) => println!("On branch {}", branch),
+        (None, Some(id)) => println!("HEAD detached at {}", id.abbrev(7)),
+        (None, None) => {}
+    }
+    let old = match (&branch, &head) {
+        (Some(branch), Some(id)) => {
+            print_divergence(branch, id)?;
+            tree::flatten(object::store().as_ref(), &commit::get_commit(id)?.tree)?
+        }
+        (None, Some(id)) => tree::flatten(object::store().as_ref(), &commit::get_commit(id)?.tree)?,
+        (_, None) => {
+            println!("\nNo commits yet");
+            Files::new()
+        }
+    };
+
+    let new = tree::working_dir_files()?;
+    if old == new {
+        println!("\nnothing to commit, working tree clean");
+        return Ok(());
+    }
+
+    println!("\nChanges to be committed:");
+    for path in old.keys().chain(new.keys()).collect::<BTreeSet<_>>() {
+        match (old.get(path), new.get(path)) {
+            (Some(a), Some(b)) if a != b => println!("\tmodified:   {}", path),
+            (Some(_), None) => println!("\tdeleted:    {}", path),
+            (None, Some(_)) => println!("\tnew file:   {}", path),
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
@@ -1,10 +1,15 @@
-use crate::init::WORKING_DIR;
-use crate::object;
+use crate::init::{RUC_DIR, WORKING_DIR};
+use crate::object::{self, ObjectStore};
+use crate::oid::{HashAlgorithm, Oid};
 
 use anyhow::{bail, Context, Result};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
 use std::fs;
 use std::fs::{DirEntry, File};
 use std::io::prelude::*;
+use std::path::Component;
 use std::path::Path;
 use std::path::PathBuf;
 use std::str::FromStr;
@@ -35,14 +40,115 @@ fn should_be_ignored(entry: &DirEntry) -> bool {
     }
 }
 
+// Mode of an entry in a tree. These are the same ones supported by Git: regular
+// files, executable files, symbolic links and directories (i.e. other trees).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Regular,
+    Executable,
+    Symlink,
+    Directory,
+}
+
+impl Mode {
+    // Returns the kind of object being pointed to by entries with this mode.
+    pub fn kind(&self) -> object::Kind {
+        match self {
+            Mode::Directory => object::Kind::Tree,
+            _ => object::Kind::Blob,
+        }
+    }
+
+    // Returns the mode as it's stored inside of tree objects. It's the same as
+    // the displayed one, but Git strips the leading zero for directories.
+    fn as_stored(&self) -> String {
+        match self {
+            Mode::Directory => String::from("40000"),
+            _ => self.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Mode::Regular => write!(f, "100644"),
+            Mode::Executable => write!(f, "100755"),
+            Mode::Symlink => write!(f, "120000"),
+            Mode::Directory => write!(f, "040000"),
+        }
+    }
+}
+
+impl std::str::FromStr for Mode {
+    type Err = anyhow::Error;
+
+    fn from_str(input: &str) -> Result<Mode, Self::Err> {
+        match input {
+            "100644" => Ok(Mode::Regular),
+            "100755" => Ok(Mode::Executable),
+            "120000" => Ok(Mode::Symlink),
+            "040000" | "40000" => Ok(Mode::Directory),
+            _ => bail!("unknown mode '{}'", input),
+        }
+    }
+}
+
+// Entry inside of a tree object. Note that the name is relative to the tree
+// containing it, so the full path has to be reconstructed by walking the tree
+// from its root.
 #[derive(Debug)]
 pub struct TreeEntry {
-    id: String,
-    kind: object::Kind,
-    path: String,
+    pub id: Oid,
+    pub mode: Mode,
+    pub kind: object::Kind,
+    pub name: String,
+}
+
+#[cfg(unix)]
+pub fn is_executable(metadata: &fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    metadata.permissions().mode() & 0o111 != 0
+}
+
+#[cfg(not(unix))]
+pub fn is_executable(_metadata: &fs::Metadata) -> bool {
+    false
 }
 
-pub fn traverse_write_tree(path: &Path) -> Result<String> {
+#[cfg(unix)]
+fn set_executable(path: &Path, executable: bool) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = if executable { 0o755 } else { 0o644 };
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn set_executable(_path: &Path, _executable: bool) -> Result<()> {
+    Ok(())
+}
+
+#[cfg(unix)]
+fn create_symlink(target: &str, path: &Path) -> Result<()> {
+    std::os::unix::fs::symlink(target, path)?;
+
+    Ok(())
+}
+
+// Platforms without symbolic links get a regular file with the target as its
+// contents, which is what Git does as well.
+#[cfg(not(unix))]
+fn create_symlink(target: &str, path: &Path) -> Result<()> {
+    fs::write(path, target)?;
+
+    Ok(())
+}
+
+pub fn traverse_write_tree(path: &Path) -> Result<Oid> {
     let mut entries: Vec<TreeEntry> = vec![];
 
     for entry in fs::read_dir(path)? {
@@ -53,87 +159,372 @@ pub fn traverse_write_tree(path: &Path) -> Result<String> {
             continue;
         }
 
-        // Try to get the path relative to the working directory. This way it's
-        // easier to migrate from different databases.
-        let entry_path_full = entry.path();
-        let entry_path = match entry_path_full.strip_prefix(WORKING_DIR.to_owned()) {
-            Ok(ep) => ep,
-            Err(_) => entry_path_full.as_path(),
-        }
-        .to_str();
+        // Only the name of the entry is stored, the full path is given by the
+        // trees containing it.
+        let name = match entry.file_name().to_str() {
+            Some(name) => name.to_string(),
+            None => bail!("bad file name {}", entry.path().display()),
+        };
 
         // If it's a directory, then we have to traverse the tree one level
-        // below, otherwise we can just hash the file.
-        if file_type.is_dir() {
-            entries.push(TreeEntry {
-                id: traverse_write_tree(&entry.path())?,
-                kind: object::Kind::Tree,
-                path: entry_path.unwrap().to_string(),
-            });
+        // below. Symbolic links are not followed, but rather their target is
+        // stored as a blob. Otherwise we can just hash the file.
+        let (id, mode) = if file_type.is_dir() {
+            (traverse_write_tree(&entry.path())?, Mode::Directory)
+        } else if file_type.is_symlink() {
+            let target = fs::read_link(entry.path())?;
+            let target = match target.to_str() {
+                Some(t) => t.to_string(),
+                None => bail!("bad target for symbolic link {}", entry.path().display()),
+            };
+
+            (
+                object::hash_contents(target.as_bytes(), object::Kind::Blob)?,
+                Mode::Symlink,
+            )
         } else {
-            entries.push(TreeEntry {
-                id: object::hash(&entry.path(), object::Kind::Blob, false)?,
-                kind: object::Kind::Blob,
-                path: entry_path.unwrap().to_string(),
-            });
-        }
+            let mode = if is_executable(&entry.metadata()?) {
+                Mode::Executable
+            } else {
+                Mode::Regular
+            };
+
+            (object::hash(&entry.path(), object::Kind::Blob)?, mode)
+        };
+
+        entries.push(TreeEntry {
+            id,
+            mode,
+            kind: mode.kind(),
+            name,
+        });
     }
 
     // Bundle all the entries that have been found (both trees and blobs), and
     // store it into a tree kind. The return value on success for this function
     // will be the ID for this newly generated tree file.
-    let contents = entries.iter().fold(String::new(), |a, b| {
-        a + &format!("{} {} {}", b.kind, b.id, b.path) + "\n"
-    });
-
-    object::hash_contents(&contents, object::Kind::Tree)
+    object::hash_contents(&encode_entries(&mut entries), object::Kind::Tree)
 }
 
 pub fn write_tree(path: &Path) -> Result<()> {
-    traverse_write_tree(path)?;
+    let id = traverse_write_tree(path)?;
+
+    println!("{}", id);
+
+    Ok(())
+}
+
+// Compares tree entries following the canonical ordering from Git: entries
+// are sorted by name, but directories are compared as if their name had a
+// trailing slash. Hence, "b.txt" goes before the "b" directory.
+fn canonical_cmp(a: &TreeEntry, b: &TreeEntry) -> Ordering {
+    let key = |entry: &TreeEntry| {
+        let mut name = entry.name.as_bytes().to_vec();
+        if entry.mode == Mode::Directory {
+            name.push(b'/');
+        }
+        name
+    };
+
+    key(a).cmp(&key(b))
+}
+
+// Encodes the given entries as in Git. That is, entries are sorted in the
+// canonical order and then each one is written as the mode and the name
+// separated by a space, followed by a NUL byte and the raw bytes of the object
+// ID. This way the same contents always produce the same tree regardless of
+// the order in which the filesystem lists them.
+pub fn encode_entries(entries: &mut [TreeEntry]) -> Vec<u8> {
+    entries.sort_by(canonical_cmp);
+
+    let mut contents = vec![];
+    for entry in entries.iter() {
+        contents.extend_from_slice(format!("{} {}", entry.mode.as_stored(), entry.name).as_bytes());
+        contents.push(b'\0');
+        contents.extend_from_slice(entry.id.as_bytes());
+    }
+
+    contents
+}
 
-    println!("Stored tree from {}", path.display());
+// Returns an error if the given name cannot be used for an entry of a tree.
+// Entries are written into the working directory when checking out, so, as in
+// the fsck from Git, names which would point anywhere else than to a new file
+// inside of the directory for the tree are rejected. This also includes `.ruc`,
+// since it would overwrite the repository itself.
+pub fn check_entry_name(name: &str) -> Result<()> {
+    if name.is_empty()
+        || name == "."
+        || name == ".."
+        || name.eq_ignore_ascii_case(RUC_DIR)
+        || name.contains(['/', '\0'])
+    {
+        bail!("'{}' is not a valid name for a tree entry", name);
+    }
 
     Ok(())
 }
 
-fn get_entries(contents: &str) -> Result<Vec<TreeEntry>> {
-    let mut error = false;
+// Parses the entries from the given contents of a tree object. Entries must
+// come in the canonical order and with unique names, as otherwise one entry
+// could shadow another one when checking out (e.g. a symbolic link "d" and
+// then a directory "d" whose files would be written through the link).
+pub fn get_entries(contents: &[u8], algorithm: HashAlgorithm) -> Result<Vec<TreeEntry>> {
+    let mut entries: Vec<TreeEntry> = vec![];
+    let mut names = HashSet::new();
+    let mut rest = contents;
+
+    while !rest.is_empty() {
+        let (mode, name) = match rest.iter().position(|&b| b == b'\0') {
+            Some(pos) => match std::str::from_utf8(&rest[..pos])?.split_once(' ') {
+                Some((mode, name)) => {
+                    check_entry_name(name).context("badly formatted tree!")?;
+                    rest = &rest[pos + 1..];
+                    (Mode::from_str(mode)?, name.to_string())
+                }
+                _ => bail!("badly formatted tree!"),
+            },
+            None => bail!("badly formatted tree!"),
+        };
 
-    let res = contents
-        .lines()
-        .map(|line| {
-            let fields = line.split_whitespace().collect::<Vec<_>>();
-            if fields.len() != 3 {
-                error = true;
+        if rest.len() < algorithm.byte_len() {
+            bail!("badly formatted tree!");
+        }
+        let (id, tail) = rest.split_at(algorithm.byte_len());
+        rest = tail;
+
+        let entry = TreeEntry {
+            id: Oid::from_bytes(id)?,
+            mode,
+            kind: mode.kind(),
+            name,
+        };
+        if !names.insert(entry.name.clone()) {
+            bail!("duplicate entry '{}' in tree", entry.name);
+        }
+        if let Some(previous) = entries.last() {
+            if canonical_cmp(previous, &entry) != Ordering::Less {
+                bail!("entry '{}' is out of order in tree", entry.name);
             }
+        }
+        entries.push(entry);
+    }
+
+    Ok(entries)
+}
+
+// Returns the entries of the tree with the given ID from the given store.
+pub fn entries_from(store: &dyn ObjectStore, tree: &Oid) -> Result<Vec<TreeEntry>> {
+    let obj = object::get_from(store, tree)?;
+    if obj.kind != object::Kind::Tree {
+        bail!("object '{}' is not a tree!", tree);
+    }
+
+    get_entries(&obj.contents, store.algorithm())
+        .with_context(|| format!("while fetching entries for tree '{}'", tree))
+}
+
+// Files from a tree, indexed by their full path (e.g. "b/b.txt"), along with
+// their mode and the ID of their blob.
+pub type Files = BTreeMap<String, (Mode, Oid)>;
+
+// Returns all the files from the tree with the given ID, recursively.
+pub fn flatten(store: &dyn ObjectStore, tree: &Oid) -> Result<Files> {
+    let mut files = Files::new();
+    let mut pending = vec![(String::new(), *tree)];
 
-            TreeEntry {
-                kind: object::Kind::from_str(fields[0]).unwrap(),
-                id: fields[1].to_string(),
-                path: fields[2].to_string(),
+    while let Some((prefix, id)) = pending.pop() {
+        for entry in entries_from(store, &id)? {
+            let path = format!("{}{}", prefix, entry.name);
+
+            if entry.mode == Mode::Directory {
+                pending.push((format!("{}/", path), entry.id));
+            } else {
+                files.insert(path, (entry.mode, entry.id));
             }
-        })
-        .collect::<Vec<_>>();
+        }
+    }
+
+    Ok(files)
+}
+
+// Returns the ID of the object found at the given path (e.g. "b/b.txt") inside
+// of the tree with the given ID, or None if there's no such path. An empty path
+// refers to the tree itself.
+pub fn lookup(store: &dyn ObjectStore, tree: &Oid, path: &str) -> Result<Option<Oid>> {
+    let mut id = *tree;
 
-    if error {
-        bail!("badly formatted tree!");
+    for component in path.split('/').filter(|c| !c.is_empty()) {
+        if object::get_from(store, &id)?.kind != object::Kind::Tree {
+            return Ok(None);
+        }
+
+        id = match entries_from(store, &id)?
+            .into_iter()
+            .find(|e| e.name == component)
+        {
+            Some(entry) => entry.id,
+            None => return Ok(None),
+        };
     }
 
+    Ok(Some(id))
+}
+
+// The reverse of `flatten`: stores the trees needed for the given files and
+// returns the ID of the root one.
+pub fn build(store: &dyn ObjectStore, files: &Files) -> Result<Oid> {
+    let mut entries = vec![];
+    let mut dirs: BTreeMap<&str, Files> = BTreeMap::new();
+
+    for (path, (mode, id)) in files {
+        check_entry_name(path.split('/').next().unwrap_or_default())?;
+
+        match path.split_once('/') {
+            Some((dir, rest)) => {
+                dirs.entry(dir)
+                    .or_default()
+                    .insert(rest.to_owned(), (*mode, *id));
+            }
+            None => entries.push(TreeEntry {
+                id: *id,
+                mode: *mode,
+                kind: mode.kind(),
+                name: path.to_owned(),
+            }),
+        }
+    }
+
+    for (dir, files) in dirs {
+        entries.push(TreeEntry {
+            id: build(store, &files)?,
+            mode: Mode::Directory,
+            kind: object::Kind::Tree,
+            name: dir.to_owned(),
+        });
+    }
+
+    object::hash_contents_into(store, &encode_entries(&mut entries), object::Kind::Tree)
+}
+
+// Returns a human readable version of the given contents from a tree object,
+// with one line per entry.
+pub fn describe(contents: &[u8]) -> Result<String> {
+    let entries = get_entries(contents, object::store().algorithm())?;
+
+    Ok(entries.iter().fold(String::new(), |a, b| {
+        a + &format!("{} {} {}\t{}", b.mode, b.kind, b.id, b.name) + "\n"
+    }))
+}
+
+// Returns the ID of the tree for the given tree-ish object, which can be either
+// a tree, a commit or a tag pointing to any of them.
+pub fn peel_to_tree(id: &Oid) -> Result<Oid> {
+    let obj = object::get(id)?;
+
+    match obj.kind {
+        object::Kind::Tree => Ok(*id),
+        object::Kind::Commit | object::Kind::Tag => Ok(crate::commit::get_commit(id)?.tree),
+        _ => bail!("object '{}' is not a tree-ish", id),
+    }
+}
+
+// Prints the entries from the given tree. If `recursive` is true, then subtrees
+// are expanded and files are printed with their full path instead.
+pub fn ls_tree(id: &Oid, recursive: bool) -> Result<()> {
+    let store = object::store();
+    let tree = peel_to_tree(id)?;
+
+    if recursive {
+        for (path, (mode, id)) in flatten(store.as_ref(), &tree)? {
+            println!("{} {} {}\t{}", mode, mode.kind(), id, path);
+        }
+    } else {
+        for entry in entries_from(store.as_ref(), &tree)? {
+            println!("{} {} {}\t{}", entry.mode, entry.kind, entry.id, entry.name);
+        }
+    }
+
+    Ok(())
+}
+
+// Returns the paths of the files from the given directory that would be
+// recorded by `write-tree`, relative to the directory and sorted by path.
+pub fn working_files(path: &Path) -> Result<Vec<String>> {
+    let mut res = vec![];
+    let mut pending = vec![(path.to_path_buf(), String::new())];
+
+    while let Some((dir, prefix)) = pending.pop() {
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            if should_be_ignored(&entry) {
+                continue;
+            }
+
+            let name = match entry.file_name().to_str() {
+                Some(name) => format!("{}{}", prefix, name),
+                None => bail!("bad file name {}", entry.path().display()),
+            };
+
+            if entry.file_type()?.is_dir() {
+                pending.push((entry.path(), format!("{}/", name)));
+            } else {
+                res.push(name);
+            }
+        }
+    }
+
+    res.sort();
     Ok(res)
 }
 
-pub fn read_blob(blob: &TreeEntry) -> Result<()> {
+// Returns an error if the given path from the working directory is a symbolic
+// link. Writing files beneath it would follow the link, which could point
+// anywhere on the filesystem.
+fn check_not_symlink(path: &Path) -> Result<()> {
+    if let Ok(metadata) = path.symlink_metadata() {
+        if metadata.file_type().is_symlink() {
+            let relative = path.strip_prefix(WORKING_DIR.as_path()).unwrap_or(path);
+            bail!(
+                "'{}' is a symbolic link, refusing to write through it",
+                relative.display()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+pub fn read_blob(blob: &TreeEntry, path: &Path) -> Result<()> {
     let obj = object::get(&blob.id)?;
 
-    if let Some(dir) = Path::new(&blob.path).parent() {
+    for dir in path.ancestors().skip(1) {
+        if !dir.starts_with(WORKING_DIR.as_path()) || dir == WORKING_DIR.as_path() {
+            break;
+        }
+        check_not_symlink(dir)?;
+    }
+
+    if let Some(dir) = path.parent() {
         if !dir.as_os_str().is_empty() {
             std::fs::create_dir_all(dir)?;
         }
     }
 
-    let mut file = File::create(&blob.path)?;
-    file.write_all(obj.contents.as_bytes())?;
+    // Make sure that there's nothing in the way, since neither creating a
+    // symbolic link nor changing permissions play nice with whatever was there
+    // before.
+    if path.symlink_metadata().is_ok() {
+        fs::remove_file(path)?;
+    }
+
+    if blob.mode == Mode::Symlink {
+        return create_symlink(obj.text()?, path);
+    }
+
+    let mut file = File::create(path)?;
+    file.write_all(&obj.contents)?;
+    set_executable(path, blob.mode == Mode::Executable)?;
 
     Ok(())
 }
@@ -157,19 +548,28 @@ fn empty_directory(dir: &PathBuf) -> Result<()> {
     Ok(())
 }
 
-pub fn traverse_read_tree(tree: &String) -> Result<()> {
+// Writes the contents of the given tree into the given directory, which is
+// where the tree is rooted.
+pub fn traverse_read_tree(tree: &Oid, base: &Path) -> Result<()> {
     let obj = object::get(tree)?;
     if obj.kind != object::Kind::Tree {
         bail!("object '{}' is not a tree!", tree);
     }
 
-    let entries = get_entries(&obj.contents)
+    let entries = get_entries(&obj.contents, tree.algorithm())
         .with_context(|| format!("while fetching entries for tree '{}'", tree))?;
 
     for parsed in entries {
+        let path = base.join(&parsed.name);
+
         match parsed.kind {
-            object::Kind::Tree => traverse_read_tree(&parsed.id)?,
-            object::Kind::Blob => read_blob(&parsed).with_context(|| "while reading blob")?,
+            object::Kind::Tree => {
+                check_not_symlink(&path)?;
+                traverse_read_tree(&parsed.id, &path)?
+            }
+            object::Kind::Blob => {
+                read_blob(&parsed, &path).with_context(|| "while reading blob")?
+            }
             _ => bail!("unknown error!"),
         };
     }
@@ -177,10 +577,144 @@ pub fn traverse_read_tree(tree: &String) -> Result<()> {
     Ok(())
 }
 
-pub fn read_tree(tree: &String) -> Result<()> {
+pub fn read_tree(tree: &Oid) -> Result<()> {
     empty_directory(&WORKING_DIR).ok();
 
-    traverse_read_tree(tree)?;
+    traverse_read_tree(tree, &WORKING_DIR)?;
+
+    Ok(())
+}
+
+// Returns the given pathspec as a path relative to the root of the working
+// directory, which is how files from trees are named. Pathspecs are relative to
+// the current directory, so running from "sub" turns "a.txt" into "sub/a.txt"
+// and ".." into "" (i.e. everything). Paths outside of the working directory
+// are rejected.
+pub fn normalize_pathspec(path: &str) -> Result<String> {
+    let full = std::env::current_dir()?.join(path);
+
+    let mut normalized = PathBuf::new();
+    for component in full.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+
+    match normalized.strip_prefix(WORKING_DIR.as_path()) {
+        Ok(relative) => Ok(relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/")),
+        Err(_) => bail!("'{}' is outside of the repository", path),
+    }
+}
+
+// Returns true if the given file is the given path or if it's beneath it, in
+// case the path refers to a directory. The path "." matches everything.
+pub fn matches_path(path: &str, file: &str) -> bool {
+    let prefix = path.trim_start_matches("./").trim_end_matches('/');
+
+    prefix.is_empty()
+        || prefix == "."
+        || file == prefix
+        || file.starts_with(&format!("{}/", prefix))
+}
+
+// Restores the given paths (relative to the current directory) in the working
+// directory from the tree-ish object with the given ID, leaving everything else
+// untouched. Directories are restored with all the files beneath them. Since
+// there's no index, files which are in the tree from HEAD but not in the source
+// are considered tracked and get removed, while untracked files are kept.
+pub fn restore(source: &Oid, paths: &[String]) -> Result<()> {
+    let store = object::store();
+    let wanted = flatten(store.as_ref(), &peel_to_tree(source)?)?;
+    let tracked = match crate::commit::get_ref("HEAD")? {
+        Some(head) => flatten(store.as_ref(), &peel_to_tree(&head)?)?,
+        None => Files::new(),
+    };
+
+    for path in paths {
+        let pathspec = normalize_pathspec(path)?;
+        let matches = |file: &str| matches_path(&pathspec, file);
+        let mut found = false;
+
+        for (file, (mode, id)) in wanted.iter().filter(|(file, _)| matches(file)) {
+            let entry = TreeEntry {
+                id: *id,
+                mode: *mode,
+                kind: mode.kind(),
+                name: file.to_owned(),
+            };
+            read_blob(&entry, &WORKING_DIR.join(file))
+                .with_context(|| format!("while restoring '{}'", file))?;
+            found = true;
+        }
+
+        for file in tracked.keys().filter(|file| matches(file)) {
+            if !wanted.contains_key(file) {
+                let full_path = WORKING_DIR.join(file);
+                if full_path.symlink_metadata().is_ok() {
+                    fs::remove_file(full_path)?;
+                }
+            }
+            found = true;
+        }
+
+        if !found {
+            bail!("pathspec '{}' did not match any file known to ruc", path);
+        }
+    }
+
+    Ok(())
+}
+
+// Updates the working directory from the files in `old` to the ones in `new`:
+// files which are gone are removed (along with the directories left empty),
+// and files which are new or changed are written. Everything else is left
+// untouched.
+pub fn update_working_dir(old: &Files, new: &Files) -> Result<()> {
+    for path in old.keys().filter(|path| !new.contains_key(*path)) {
+        let full_path = WORKING_DIR.join(path);
+        if full_path.symlink_metadata().is_ok() {
+            fs::remove_file(&full_path)?;
+        }
+
+        let mut dir = full_path.parent();
+        while let Some(parent) = dir {
+            if parent == WORKING_DIR.as_path() || fs::remove_dir(parent).is_err() {
+                break;
+            }
+            dir = parent.parent();
+        }
+    }
+
+    for (path, (mode, id)) in new {
+        if old.get(path) == Some(&(*mode, *id)) {
+            continue;
+        }
+
+        let entry = TreeEntry {
+            id: *id,
+            mode: *mode,
+            kind: mode.kind(),
+            name: path.to_owned(),
+        };
+        read_blob(&entry, &WORKING_DIR.join(path))
+            .with_context(|| format!("while writing '{}'", path))?;
+    }
 
     Ok(())
 }
+
+// Returns the files from the working directory, as they would be recorded by
+// `write-tree`.
+pub fn working_dir_files() -> Result<Files> {
+    let id = traverse_write_tree(&WORKING_DIR)?;
+
+    flatten(object::store().as_ref(), &id)
+}
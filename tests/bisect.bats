@@ -0,0 +1,226 @@
+#!/usr/bin/env bats
+
+load "helpers.bats"
+
+setup() {
+    ##
+    # On setup, we will initialize a repository under "repo" with eight commits
+    # on "main", where the sixth one introduces the "bug".
+
+    clean_cd "repo"
+    ruc init
+
+    for n in $(seq 1 8); do
+        echo "$n" > number.txt
+        if [ "$n" -eq 6 ]; then
+            echo "bug" > bug.txt
+        fi
+        ruc commit -m "Commit $n"
+        if [ "$n" -eq 1 ]; then
+            ruc branch main
+            ruc checkout main
+        fi
+    done
+}
+
+@test "bisect finds the first bad commit by marking commits" {
+    ruc bisect start main main~7
+    [ "$status" -eq 0 ]
+    [ "${lines[0]}" = "Bisecting: 3 revisions left to test after this (roughly 2 steps)" ]
+    [[ "${lines[1]}" == *"] Commit 4" ]]
+    [ "$(cat number.txt)" = "4" ]
+
+    ruc bisect good
+    [ "$status" -eq 0 ]
+    [[ "${lines[1]}" == *"] Commit 6" ]]
+
+    ruc bisect bad
+    [ "$status" -eq 0 ]
+    [[ "${lines[1]}" == *"] Commit 5" ]]
+
+    ruc bisect good
+    [ "$status" -eq 0 ]
+    [ "${lines[0]}" = "$(cat .ruc/refs/bisect/bad) is the first bad commit" ]
+    [[ "$output" == *"    Commit 6"* ]]
+}
+
+@test "bisect waits for both good and bad commits" {
+    ruc bisect start
+    [ "$status" -eq 0 ]
+    [ "${lines[0]}" = "Waiting for both good and bad commits" ]
+
+    ruc bisect bad
+    [ "${lines[0]}" = "Waiting for both good and bad commits" ]
+
+    ruc bisect good main~7
+    [ "$status" -eq 0 ]
+    [[ "${lines[1]}" == *"] Commit 4" ]]
+
+    ruc bisect start
+    [ "$status" -ne 0 ]
+    [[ "$output" == *"already bisecting"* ]]
+}
+
+@test "bisect reset goes back to where HEAD was" {
+    ruc bisect start main main~7
+    [ "$(cat number.txt)" = "4" ]
+    [ "$(cat .ruc/HEAD)" != "ref: refs/heads/main" ]
+
+    ruc bisect reset
+    [ "$status" -eq 0 ]
+    [ "$(cat .ruc/HEAD)" = "ref: refs/heads/main" ]
+    [ "$(cat number.txt)" = "8" ]
+    [ ! -f .ruc/BISECT_START ]
+    [ ! -d .ruc/refs/bisect ]
+
+    ruc bisect good
+    [ "$status" -ne 0 ]
+    [[ "$output" == *"not bisecting"* ]]
+}
+
+@test "bisect skip reports all candidates when only skipped commits are left" {
+    ruc bisect start main~2 main~4
+    [[ "${lines[1]}" == *"] Commit 5" ]]
+
+    ruc bisect skip
+    [ "$status" -ne 0 ]
+    [ "${lines[0]}" = "There are only 'skip'ped commits left to test." ]
+    [ "${lines[2]}" = "$(cat .ruc/HEAD)" ]
+    [ "${lines[3]}" = "$(cat .ruc/refs/bisect/bad)" ]
+    [[ "$output" == *"we cannot bisect more"* ]]
+}
+
+@test "bisect run marks commits from the exit code of a command" {
+    cat > ../test.sh <<'EOF'
+#!/bin/sh
+[ "$(cat number.txt)" = "4" ] && exit 125
+[ ! -f bug.txt ]
+EOF
+    chmod +x ../test.sh
+
+    ruc bisect start main main~7
+    ruc bisect run ../test.sh
+    [ "$status" -eq 0 ]
+    [[ "$output" == *"running '../test.sh'"* ]]
+    [[ "$output" == *"is the first bad commit"* ]]
+    [[ "$output" == *"    Commit 6"* ]]
+    rm ../test.sh
+}
+
+@test "bisect run goes through merges" {
+    cat > ../stream <<'EOF'
+commit refs/heads/merged
+mark :1
+committer C O Mitter <committer@example.com> 1112911993 -0700
+data 4
+Base
+M 644 inline base.txt
+data 5
+base
+
+commit refs/heads/merged
+mark :2
+committer C O Mitter <committer@example.com> 1112911994 -0700
+data 6
+Side 1
+M 644 inline side.txt
+data 3
+ok
+
+commit refs/heads/merged
+mark :3
+committer C O Mitter <committer@example.com> 1112911995 -0700
+data 6
+Side 2
+M 644 inline bug.txt
+data 4
+bug
+
+commit refs/heads/merged
+mark :4
+committer C O Mitter <committer@example.com> 1112911996 -0700
+data 6
+Main 1
+from :1
+M 644 inline main.txt
+data 3
+ok
+
+commit refs/heads/merged
+mark :5
+committer C O Mitter <committer@example.com> 1112911997 -0700
+data 5
+Merge
+from :4
+merge :3
+M 644 inline side.txt
+data 3
+ok
+M 644 inline bug.txt
+data 4
+bug
+EOF
+    run sh -c "cargo -q run -- fast-import < ../stream"
+    [ "$status" -eq 0 ]
+    rm ../stream
+
+    ruc bisect start merged merged~2
+    ruc bisect run sh -c '[ ! -f bug.txt ]'
+    [ "$status" -eq 0 ]
+    [[ "$output" == *"    Side 2"* ]]
+
+    ruc bisect reset
+    [ "$(cat number.txt)" = "8" ]
+}
+
+# Writes a commit for fast-import to ../stream, with the given mark, subject
+# and parents (given as marks), adding a file named after the subject.
+stream_commit() {
+    local mark="$1" subject="$2" from="$3" merge="$4"
+    {
+        echo "commit refs/heads/merged"
+        echo "mark :$mark"
+        echo "committer C O Mitter <committer@example.com> $((1112911993 + mark)) -0700"
+        echo "data ${#subject}"
+        echo "$subject"
+        [ -n "$from" ] && echo "from :$from"
+        [ -n "$merge" ] && echo "merge :$merge"
+        echo "M 644 inline $subject.txt"
+        echo "data 3"
+        echo "ok"
+    } >> ../stream
+}
+
+@test "bisect counts the commits reached through merges only once" {
+    ##
+    # Base is the only good commit, and M1 is reached twice from Merge, through
+    # both of its parents. Testing Merge splits the nine candidates best:
+    #
+    #   Base - M1 - M2 - Merge - A1 - A2 - A3 - A4
+    #            \       /
+    #             S1 - S2
+
+    rm -f ../stream
+    stream_commit 1 Base
+    stream_commit 2 M1 1
+    stream_commit 3 M2 2
+    stream_commit 4 S1 2
+    stream_commit 5 S2 4
+    stream_commit 6 Merge 3 5
+    for n in $(seq 1 4); do
+        stream_commit $((6 + n)) "A$n" $((5 + n))
+    done
+    run sh -c "cargo -q run -- fast-import < ../stream"
+    [ "$status" -eq 0 ]
+    rm ../stream
+
+    ruc bisect start merged merged~7
+    [ "$status" -eq 0 ]
+    [[ "${lines[1]}" == *"] Merge" ]]
+
+    ruc bisect good
+    [ "$status" -eq 0 ]
+    [[ "${lines[1]}" == *"] A2" ]]
+
+    ruc bisect reset
+}
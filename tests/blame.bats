@@ -0,0 +1,107 @@
+#!/usr/bin/env bats
+
+load "helpers.bats"
+
+setup() {
+    ##
+    # On setup, we will initialize a repository under "repo" with three commits
+    # changing "numbers.txt", where the second one is from another author.
+
+    clean_cd "repo"
+    ruc init
+    fixed_identity
+
+    seq 1 5 > numbers.txt
+    ruc commit -m "First"
+    sed -i 's/^3$/three/' numbers.txt
+    RUC_AUTHOR_NAME="Other Author" ruc commit -m "Second"
+    echo "6" >> numbers.txt
+    ruc commit -m "Third"
+}
+
+@test "blame shows the commit which last changed each line" {
+    ruc blame numbers.txt
+    [ "$status" -eq 0 ]
+    [ "${#lines[@]}" -eq 6 ]
+
+    [[ "${lines[0]}" == "^"*" (A U Thor     2005-04-07 15:13:13 -0700 1) 1" ]]
+    [[ "${lines[2]}" == *" (Other Author 2005-04-07 15:13:13 -0700 3) three" ]]
+    [[ "${lines[5]}" == *" (A U Thor     2005-04-07 15:13:13 -0700 6) 6" ]]
+
+    second="$(cargo -q run -- rev-list HEAD | sed -n 2p)"
+    [[ "${lines[2]}" == "${second:0:8} "* ]]
+}
+
+@test "blame accepts a revision and a range of lines" {
+    ruc blame -L 2,+2 numbers.txt HEAD~1
+    [ "$status" -eq 0 ]
+    [ "${#lines[@]}" -eq 2 ]
+    [[ "${lines[0]}" == *" 2) 2" ]]
+    [[ "${lines[1]}" == *" 3) three" ]]
+
+    ruc blame -L 5, numbers.txt
+    [ "${#lines[@]}" -eq 2 ]
+
+    ruc blame -L 10 numbers.txt
+    [ "$status" -ne 0 ]
+    [[ "$output" == *"file numbers.txt has only 6 lines"* ]]
+
+    ruc blame -L 3,1 numbers.txt
+    [ "$status" -ne 0 ]
+    [[ "$output" == *"invalid -L range '3,1'"* ]]
+
+    for range in 0,+0 1,+0 0 2,+18446744073709551615; do
+        ruc blame -L "$range" numbers.txt
+        [ "$status" -ne 0 ]
+        [[ "$output" == *"invalid -L range '$range'"* ]]
+    done
+}
+
+@test "blame shows details about commits in porcelain mode" {
+    ruc blame --porcelain -L 2,4 numbers.txt
+    [ "$status" -eq 0 ]
+
+    first="$(cargo -q run -- rev-list HEAD | tail -n 1)"
+    second="$(cargo -q run -- rev-list HEAD | sed -n 2p)"
+    [ "${lines[0]}" = "$first 2 2 1" ]
+    [ "${lines[1]}" = "author A U Thor" ]
+    [ "${lines[2]}" = "author-mail <author@example.com>" ]
+    [ "${lines[3]}" = "author-time 1112911993" ]
+    [ "${lines[4]}" = "author-tz -0700" ]
+    [ "${lines[9]}" = "summary First" ]
+    [ "${lines[10]}" = "boundary" ]
+    [ "${lines[11]}" = "filename numbers.txt" ]
+    [ "${lines[12]}" = "$(printf '\t2')" ]
+    [ "${lines[13]}" = "$second 3 3 1" ]
+    [ "${lines[14]}" = "author Other Author" ]
+    [ "${lines[24]}" = "$(printf '\tthree')" ]
+
+    # Commits are only described the first time.
+    [ "${lines[25]}" = "$first 4 4 1" ]
+    [ "${lines[26]}" = "$(printf '\t4')" ]
+}
+
+@test "blame fails on unknown files" {
+    ruc blame unknown.txt
+    [ "$status" -ne 0 ]
+    [[ "$output" == *"no such path 'unknown.txt' in HEAD"* ]]
+}
+
+@test "blame takes paths relative to the current directory" {
+    mkdir dir
+    echo "a" > dir/a.txt
+    ruc commit -m "Fourth"
+    cd dir
+
+    ruc blame a.txt
+    [ "$status" -eq 0 ]
+    [[ "${lines[0]}" == *" (A U Thor 2005-04-07 15:13:13 -0700 1) a" ]]
+
+    ruc blame --porcelain ../numbers.txt
+    [ "$status" -eq 0 ]
+    [[ "$output" == *"filename numbers.txt"* ]]
+
+    ruc blame ../../outside.txt
+    [ "$status" -ne 0 ]
+    [[ "$output" == *"'../../outside.txt' is outside of the repository"* ]]
+}
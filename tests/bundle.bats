@@ -0,0 +1,154 @@
+#!/usr/bin/env bats
+
+load "helpers.bats"
+
+setup() {
+    ##
+    # On setup, we will initialize a repository under "origin" with a couple of
+    # commits on the "main" branch.
+
+    clean_cd "origin"
+    ruc init
+
+    echo "a" > a.txt
+    ruc commit -m "First"
+    ruc branch main
+    ruc checkout main
+
+    echo "b" > b.txt
+    ruc commit -m "Second"
+
+    rm -rf ../clone ../other ../*.bundle
+}
+
+@test "bundle create and verify a complete history" {
+    ruc bundle create ../full.bundle main
+
+    [ "$status" -eq 0 ]
+    [ "$(head -n 1 ../full.bundle)" = "# ruc bundle v1" ]
+
+    ruc bundle verify ../full.bundle
+
+    [ "$status" -eq 0 ]
+    [ "${lines[1]}" = "$(cat .ruc/refs/heads/main) refs/heads/main" ]
+    [ "${lines[2]}" = "The bundle records a complete history." ]
+}
+
+@test "bundle create refuses to create bundles without references" {
+    ruc bundle create ../full.bundle "$(cat .ruc/refs/heads/main)"
+
+    [ "$status" -ne 0 ]
+    [[ ! -f ../full.bundle ]]
+}
+
+@test "clone works from a bundle" {
+    ruc bundle create ../full.bundle main
+
+    cd ..
+    ruc clone full.bundle clone
+    cd clone
+
+    [ "$(cat .ruc/HEAD)" = "ref: refs/heads/main" ]
+    [ "$(cat .ruc/refs/heads/main)" = "$(cat ../origin/.ruc/refs/heads/main)" ]
+    [ "$(cat a.txt)" = "a" ]
+    [ "$(cat b.txt)" = "b" ]
+}
+
+@test "incremental bundles require their prerequisites" {
+    cd ..
+    ruc clone origin clone
+
+    cd origin
+    old=$(cat .ruc/refs/heads/main)
+    echo "c" > c.txt
+    ruc commit -m "Third"
+    ruc bundle create ../incremental.bundle "${old}..main"
+
+    [ "$status" -eq 0 ]
+    [ "$(sed -n 3p ../incremental.bundle)" = "-${old} Second" ]
+
+    mkdir -p ../other
+    cd ../other
+    ruc init
+    ruc bundle verify ../incremental.bundle
+
+    [ "$status" -ne 0 ]
+
+    cd ../clone
+    ruc bundle verify ../incremental.bundle
+
+    [ "$status" -eq 0 ]
+}
+
+@test "fetch works from an incremental bundle" {
+    cd ..
+    ruc clone origin clone
+
+    cd origin
+    old=$(cat .ruc/refs/heads/main)
+    echo "c" > c.txt
+    ruc commit -m "Third"
+    ruc bundle create ../incremental.bundle "${old}..main"
+
+    cd ../clone
+    ruc remote add bundle ../incremental.bundle
+    ruc fetch bundle
+
+    [ "$status" -eq 0 ]
+    [ "$(cat .ruc/refs/remotes/bundle/main)" = "$(cat ../origin/.ruc/refs/heads/main)" ]
+
+    ruc log --from bundle/main
+    [ "${lines[1]}" = "Third" ]
+    [ "${lines[3]}" = "Second" ]
+}
+
+@test "bundle unbundle stores the objects into the repository" {
+    ruc bundle create ../full.bundle main
+
+    mkdir -p ../other
+    cd ../other
+    ruc init
+    ruc bundle unbundle ../full.bundle
+
+    [ "$status" -eq 0 ]
+    [ "${lines[0]}" = "$(cat ../origin/.ruc/refs/heads/main) refs/heads/main" ]
+
+    ruc log --from "$(cat ../origin/.ruc/refs/heads/main)"
+    [ "${lines[1]}" = "Second" ]
+}
+
+@test "bundles with invalid reference names are rejected" {
+    ruc bundle create ../full.bundle main
+    sed -i '3s|refs/heads/main|refs/tags/../../../pwned|' ../full.bundle
+    [ "$(sed -n 3p ../full.bundle)" = "$(cat .ruc/refs/heads/main) refs/tags/../../../pwned" ]
+
+    ruc bundle verify ../full.bundle
+    [ "$status" -ne 0 ]
+    [[ "$output" == *"'refs/tags/../../../pwned' is not a valid reference name"* ]]
+
+    cd ..
+    ruc clone full.bundle clone
+    [ "$status" -ne 0 ]
+    [[ ! -e "pwned" ]]
+}
+
+@test "fetch rejects references to objects which did not arrive" {
+    cd ..
+    ruc clone origin clone
+
+    cd origin
+    old=$(cat .ruc/refs/heads/main)
+    echo "c" > c.txt
+    ruc commit -m "Third"
+    ruc bundle create ../incremental.bundle "${old}..main"
+    missing="$(echo "missing" | cargo -q run -- hash-object --stdin)"
+    sed -i "s|^[0-9a-f]* refs/heads/main$|$missing refs/heads/main|" ../incremental.bundle
+    grep -q "^$missing refs/heads/main$" ../incremental.bundle
+
+    cd ../clone
+    ruc remote add bundle ../incremental.bundle
+    ruc fetch bundle
+    [ "$status" -ne 0 ]
+    [[ "$output" == *"the remote did not send object $missing"* ]]
+    [ ! -e .ruc/refs/remotes/bundle/main ]
+}
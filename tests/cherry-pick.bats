@@ -0,0 +1,149 @@
+#!/usr/bin/env bats
+
+load "helpers.bats"
+
+setup() {
+    ##
+    # On setup, we will initialize a repository under "repo" with a "topic"
+    # branch which has three commits on top of "main". HEAD is left on "main".
+
+    clean_cd "repo"
+    ruc init
+
+    seq 1 10 > numbers.txt
+    ruc commit -m "Base"
+    ruc branch main
+    ruc branch topic
+
+    ruc checkout topic
+    sed -i 's/^3$/three/' numbers.txt
+    ruc commit -m "Topic 1"
+    sed -i 's/^8$/eight/' numbers.txt
+    ruc commit -m "Topic 2"
+    echo "t" > t.txt
+    ruc commit -m "Topic 3"
+
+    ruc checkout main
+}
+
+@test "cherry-pick applies the changes from a commit" {
+    ruc cherry-pick topic~1
+    [ "$status" -eq 0 ]
+
+    [ "$(sed -n 3p numbers.txt)" = "3" ]
+    [ "$(sed -n 8p numbers.txt)" = "eight" ]
+    [ "$(cat .ruc/HEAD)" = "ref: refs/heads/main" ]
+
+    ruc log
+    [ "${lines[1]}" = "Topic 2" ]
+    [[ "${lines[2]}" == "(cherry picked from commit "* ]]
+}
+
+@test "cherry-pick accepts ranges of commits" {
+    ruc cherry-pick main..topic
+    [ "$status" -eq 0 ]
+
+    [ "$(sed -n 3p numbers.txt)" = "three" ]
+    [ "$(cat t.txt)" = "t" ]
+
+    ruc log
+    [ "${lines[1]}" = "Topic 3" ]
+    [ "${lines[4]}" = "Topic 2" ]
+    [ "${lines[7]}" = "Topic 1" ]
+}
+
+@test "revert undoes the changes from a commit" {
+    ruc cherry-pick main..topic
+    ruc revert HEAD~1
+    [ "$status" -eq 0 ]
+
+    [ "$(sed -n 3p numbers.txt)" = "three" ]
+    [ "$(sed -n 8p numbers.txt)" = "8" ]
+    [ "$(cat t.txt)" = "t" ]
+
+    ruc log
+    [ "${lines[1]}" = "Revert \"Topic 2\"" ]
+    [[ "${lines[2]}" == "This reverts commit "* ]]
+}
+
+@test "cherry-pick stops on conflicts and continues after resolving them" {
+    sed -i 's/^3$/main/' numbers.txt
+    ruc commit -m "Main"
+
+    ruc cherry-pick topic~2 topic
+    [ "$status" -ne 0 ]
+    [[ "$output" == *"CONFLICT (content): Merge conflict in numbers.txt"* ]]
+    [ "$(sed -n 3p numbers.txt)" = "<<<<<<< HEAD" ]
+
+    sed -i '/^[<=>]/d; /^main$/d' numbers.txt
+    ruc cherry-pick --continue
+    [ "$status" -eq 0 ]
+    [[ ! -d .ruc/sequencer ]]
+
+    [ "$(sed -n 3p numbers.txt)" = "three" ]
+    [ "$(cat t.txt)" = "t" ]
+
+    ruc log
+    [ "${lines[1]}" = "Topic 3" ]
+    [ "${lines[4]}" = "Topic 1" ]
+}
+
+@test "cherry-pick --continue refuses to commit unresolved conflicts" {
+    sed -i 's/^3$/main/' numbers.txt
+    ruc commit -m "Main"
+    head="$(cat .ruc/refs/heads/main)"
+
+    ruc cherry-pick topic~2
+    [ "$status" -ne 0 ]
+
+    ruc cherry-pick --continue
+    [ "$status" -ne 0 ]
+    [[ "$output" == *"'numbers.txt' still has conflict markers"* ]]
+    [ "$(cat .ruc/refs/heads/main)" = "$head" ]
+    [[ -d .ruc/sequencer ]]
+
+    sed -i '/^[<=>]/d; /^main$/d' numbers.txt
+    ruc cherry-pick --continue
+    [ "$status" -eq 0 ]
+    [[ ! -d .ruc/sequencer ]]
+    [ "$(cat .ruc/refs/heads/main)" != "$head" ]
+}
+
+@test "cherry-pick keeps full commit IDs in its state" {
+    sed -i 's/^3$/main/' numbers.txt
+    ruc commit -m "Main"
+    topic1="$(cargo -q run -- rev-list topic | sed -n 3p)"
+    topic3="$(cargo -q run -- rev-list topic | sed -n 1p)"
+
+    ruc cherry-pick topic~2 topic
+    [ "$status" -ne 0 ]
+    [ "$(cat .ruc/sequencer/stopped)" = "pick $topic1 Topic 1" ]
+    [ "$(cat .ruc/sequencer/todo)" = "pick $topic3 Topic 3" ]
+
+    # Branches named after abbreviated IDs must not be picked instead.
+    ruc branch "${topic1:0:7}" main
+    ruc branch "${topic3:0:7}" main
+    sed -i '/^[<=>]/d; /^main$/d' numbers.txt
+    ruc cherry-pick --continue
+    [ "$status" -eq 0 ]
+
+    ruc log
+    [ "${lines[1]}" = "Topic 3" ]
+    [ "${lines[4]}" = "Topic 1" ]
+}
+
+@test "revert --abort goes back to the original state" {
+    sed -i 's/^3$/main/' numbers.txt
+    ruc commit -m "Main"
+    ruc cherry-pick topic~1
+    before=$(cat .ruc/refs/heads/main)
+
+    ruc revert topic~2
+    [ "$status" -ne 0 ]
+
+    ruc revert --abort
+    [ "$status" -eq 0 ]
+    [ "$(cat .ruc/refs/heads/main)" = "$before" ]
+    [ "$(sed -n 3p numbers.txt)" = "main" ]
+    [[ ! -d .ruc/sequencer ]]
+}
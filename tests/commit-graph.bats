@@ -0,0 +1,141 @@
+#!/usr/bin/env bats
+
+load "helpers.bats"
+
+setup() {
+    ##
+    # On setup, we will initialize a repository under "repo" with an octopus
+    # merge on "main" of the "side" and "other" branches.
+
+    clean_cd "repo"
+    ruc init
+
+    cat > ../stream <<'EOS'
+commit refs/heads/main
+mark :1
+committer C O Mitter <committer@example.com> 1112911993 -0700
+data 1
+A
+M 644 inline a.txt
+data 2
+a
+
+commit refs/heads/main
+mark :2
+committer C O Mitter <committer@example.com> 1112911994 -0700
+data 1
+B
+from :1
+M 644 inline b.txt
+data 2
+b
+
+commit refs/heads/side
+mark :3
+committer C O Mitter <committer@example.com> 1112911995 -0700
+data 1
+C
+from :1
+M 644 inline c.txt
+data 2
+c
+
+commit refs/heads/other
+mark :4
+committer C O Mitter <committer@example.com> 1112911996 -0700
+data 1
+D
+from :1
+M 644 inline d.txt
+data 2
+d
+
+commit refs/heads/main
+mark :5
+committer C O Mitter <committer@example.com> 1112911997 -0700
+data 1
+M
+from :2
+merge :3
+merge :4
+M 644 inline c.txt
+data 2
+c
+M 644 inline d.txt
+data 2
+d
+EOS
+    run sh -c "cargo -q run -- fast-import < ../stream"
+    rm ../stream
+}
+
+@test "commit-graph write stores all commits" {
+    ruc commit-graph write
+    [ "$status" -eq 0 ]
+    [ "$output" = "Wrote a commit graph with 5 commits" ]
+    [ -f .ruc/objects/info/commit-graph ]
+    [ "$(head -c 4 .ruc/objects/info/commit-graph)" = "CGPH" ]
+
+    # Writing it again does not take the file as an object.
+    ruc commit-graph write
+    [ "$status" -eq 0 ]
+    [ "$output" = "Wrote a commit graph with 5 commits" ]
+}
+
+@test "commit-graph is used for history traversals" {
+    ruc merge-base --all main side other
+    expected="$output"
+    ruc rev-list main ^side
+    expected_list="$output"
+
+    ruc commit-graph write
+    ruc merge-base --all main side other
+    [ "$output" = "$expected" ]
+    ruc merge-base --octopus main side other
+    [ "$output" = "$(cargo -q run -- rev-list side | tail -n 1)" ]
+    ruc rev-list main ^side
+    [ "$output" = "$expected_list" ]
+
+    ruc merge-base --is-ancestor other main
+    [ "$status" -eq 0 ]
+    ruc merge-base --is-ancestor main other
+    [ "$status" -eq 1 ]
+
+    # Parents come from the commit graph, so walking through a commit does not
+    # need its object anymore.
+    root="$(cargo -q run -- rev-list main | tail -n 1)"
+    all="$(cargo -q run -- rev-list main)"
+    rm ".ruc/objects/$(cargo -q run -- rev-list main~1 | head -n 1)"
+    ruc merge-base --is-ancestor "$root" main
+    [ "$status" -eq 0 ]
+    ruc merge-base --all main side other
+    [ "$output" = "$expected" ]
+    ruc rev-list main
+    [ "$status" -eq 0 ]
+    [ "$output" = "$all" ]
+}
+
+@test "commit-graph does not need to have all commits" {
+    ruc commit-graph write
+    ruc checkout main
+
+    echo "e" > e.txt
+    ruc commit -m "E"
+    ruc merge-base --is-ancestor side main
+    [ "$status" -eq 0 ]
+    ruc merge-base --is-ancestor main~1 main
+    [ "$status" -eq 0 ]
+    ruc merge-base main other
+    [ "$output" = "$(cargo -q run -- rev-list other | head -n 1)" ]
+}
+
+@test "commit-graph is ignored if it's corrupted" {
+    ruc commit-graph write
+    printf 'CGPH garbage' > .ruc/objects/info/commit-graph
+
+    ruc merge-base --is-ancestor other main
+    [ "$status" -eq 0 ]
+    ruc merge-base side other
+    [ "$status" -eq 0 ]
+    [ "$output" = "$(cargo -q run -- rev-list side | tail -n 1)" ]
+}
@@ -95,3 +95,88 @@ setup() {
     [ "$(cat .ruc/HEAD)" = "$(cat .ruc/refs/tags/second)" ]
     [ "${sha1}" = "$(cat .ruc/refs/tags/first)" ]
 }
+
+@test "checkout restores executable bits" {
+    chmod +x a.txt
+    ruc commit -m "First"
+    sha1=$(cat .ruc/HEAD)
+
+    chmod -x a.txt
+    ruc commit -m "Second"
+
+    [[ ! -x "a.txt" ]]
+
+    ruc checkout "${sha1}"
+
+    [[ -x "a.txt" ]]
+    [[ ! -x "b.txt" ]]
+}
+
+@test "checkout restores symbolic links" {
+    ln -s b.txt link
+    ln -s b dirlink
+    ruc commit -m "First"
+    sha1=$(cat .ruc/HEAD)
+
+    rm link dirlink
+    ruc commit -m "Second"
+
+    ruc checkout "${sha1}"
+
+    [[ -L "link" ]]
+    [ "$(readlink link)" = "b.txt" ]
+    [[ -L "dirlink" ]]
+    [ "$(readlink dirlink)" = "b" ]
+}
+
+@test "commit --amend replaces the last commit" {
+    ruc commit -m "First"
+    sha1=$(cat .ruc/HEAD)
+
+    RUC_AUTHOR_NAME="Original" ruc commit -m "Secnod"
+    sha2=$(cat .ruc/HEAD)
+
+    echo "forgotten" > c.txt
+    RUC_AUTHOR_NAME="Someone Else" ruc commit --amend -m "Second"
+    [ "$status" -eq 0 ]
+    [ "$(cat .ruc/HEAD)" != "${sha2}" ]
+
+    ruc log
+    [ "${lines[1]}" = "Second" ]
+    [ "${lines[2]}" = "commit ${sha1}" ]
+
+    ruc cat-file -p HEAD
+    [[ "$output" == *"parent ${sha1}"* ]]
+    [[ "$output" == *"author Original <"* ]]
+
+    ruc status
+    [[ "$output" == *"nothing to commit, working tree clean"* ]]
+}
+
+@test "commit --amend starts the editor with the last message" {
+    ruc commit -m "First"
+    ruc branch main
+    ruc checkout main
+    ruc commit -m "Secnod"
+
+    cat > ../editor.sh <<'SCRIPT'
+#!/bin/sh
+sed -i 's/Secnod/Second/' "$1"
+SCRIPT
+    chmod +x ../editor.sh
+
+    EDITOR="$(cd .. && pwd)/editor.sh" ruc commit --amend
+    [ "$status" -eq 0 ]
+    rm ../editor.sh
+
+    ruc log
+    [ "${lines[1]}" = "Second" ]
+    [ "${lines[3]}" = "First" ]
+    [ "$(cat .ruc/refs/heads/main)" = "$(echo "${lines[0]}" | awk '{ print $2; }')" ]
+}
+
+@test "commit --amend needs a commit" {
+    ruc commit --amend -m "First"
+    [ "$status" -ne 0 ]
+    [ "$output" = "Error: there is no commit to amend" ]
+}
@@ -23,19 +23,11 @@ setup() {
     # 3. Restore things back with `read-tree`.
 
     ruc write-tree
+    sha="${lines[0]}"
 
     rm -f a.txt b.txt
     rm -rf b
 
-    # Finding the tree can be a bit tricky :)
-    sha=''
-    for file in $(ls .ruc/objects); do
-        if [ -n "$(awk '{ if ($1 == "tree" && $3 == "b") print $2 }' .ruc/objects/$file)" ]; then
-            sha="$file"
-        fi
-    done
-    [[ -n "$sha" ]]
-
     ruc read-tree $sha
 
     [[ -f "a.txt" ]]
@@ -44,17 +36,9 @@ setup() {
 }
 
 @test "cat-file works" {
+    # The root tree is printed by `write-tree`.
     ruc write-tree
-
-    # We will find a known object. For this simple case, finding the root tree
-    # should cut it.
-    sha=''
-    for file in $(ls .ruc/objects); do
-        if [ -n "$(awk '{ if ($1 == "tree" && $3 == "b") print $2 }' .ruc/objects/$file)" ]; then
-            sha="$file"
-        fi
-    done
-    [[ -n "$sha" ]]
+    sha="${lines[0]}"
 
     ##
     # The root tree is guaranteed to have 5 entries (2 lines from presentation
@@ -66,3 +50,323 @@ setup() {
     lines=$(ruc cat-file $sha | grep blob | wc -l)
     [[ "$lines" = "2" ]]
 }
+
+@test "trees support file names with spaces" {
+    echo "c" > "with some spaces.txt"
+    mkdir -p "dir with spaces"
+    echo "d" > "dir with spaces/d.txt"
+
+    ruc write-tree
+    sha="${lines[0]}"
+
+    rm -rf "with some spaces.txt" "dir with spaces"
+
+    ruc read-tree $sha
+
+    [ "$(cat "with some spaces.txt")" = "c" ]
+    [ "$(cat "dir with spaces/d.txt")" = "d" ]
+}
+
+@test "trees store names relative to their parent" {
+    mkdir -p c
+    echo "b2" > c/b.txt
+
+    ruc write-tree
+    sha="${lines[0]}"
+
+    # Both directories have the same contents, so they point to the same tree.
+    ruc cat-file $sha
+    b=$(echo "$output" | grep -P '\tb$' | awk '{ print $3; }')
+    c=$(echo "$output" | grep -P '\tc$' | awk '{ print $3; }')
+    [[ -n "$b" ]]
+    [ "$b" = "$c" ]
+
+    rm -rf b c
+    ruc read-tree $sha
+
+    [ "$(cat b/b.txt)" = "b2" ]
+    [ "$(cat c/b.txt)" = "b2" ]
+}
+
+@test "cat-file accepts abbreviated object IDs" {
+    ruc commit -m "First"
+    sha=$(cat .ruc/HEAD)
+
+    ruc cat-file "${sha:0:8}"
+
+    [ "$status" -eq 0 ]
+    [ "${lines[0]}" = "Kind: commit" ]
+}
+
+@test "cat-file accepts full object IDs in any case" {
+    ruc commit -m "First"
+    sha=$(cat .ruc/HEAD)
+
+    ruc cat-file "${sha^^}"
+    [ "$status" -eq 0 ]
+    [ "${lines[0]}" = "Kind: commit" ]
+
+    missing="${sha:0:39}$([ "${sha: -1}" = "0" ] && echo 1 || echo 0)"
+    ruc cat-file "$missing"
+    [ "$status" -ne 0 ]
+    [ "$output" = "Error: '$missing' is not a valid object name" ]
+}
+
+@test "cat-file rejects names which are not object IDs" {
+    ruc cat-file "not-an-id"
+
+    [ "$status" -ne 0 ]
+    [ "$output" = "Error: 'not-an-id' is not a valid object name" ]
+}
+
+@test "object IDs only accept hexadecimal digits" {
+    signed="$(printf '+a%.0s' $(seq 20))"
+    echo "$signed" > .ruc/refs/heads/bad
+
+    ruc cat-file -t bad
+    [ "$status" -ne 0 ]
+    [[ "$output" == *"'$signed' is not a valid object ID"* ]]
+}
+
+@test "cat-file shows the kind and the size of objects" {
+    ruc commit -m "First"
+    sha=$(cat .ruc/HEAD)
+
+    ruc cat-file -t "$sha"
+    [ "$output" = "commit" ]
+
+    ruc cat-file -p "$sha"
+    tree=$(echo "${lines[0]}" | cut -d ' ' -f 2)
+
+    ruc cat-file -t "$tree"
+    [ "$output" = "tree" ]
+
+    # "100644 a.txt" and "100644 b.txt" plus "40000 b", each one followed by a
+    # NUL byte and a raw SHA-1.
+    ruc cat-file -s "$tree"
+    [ "$output" = "$(( 13 + 20 + 13 + 20 + 8 + 20 ))" ]
+}
+
+@test "cat-file pretty-prints objects" {
+    ruc commit -m "First"
+
+    ruc cat-file -p HEAD
+    [[ "${lines[0]}" == "tree "* ]]
+    [ "${lines[-1]}" = "First" ]
+
+    tree=$(echo "${lines[0]}" | cut -d ' ' -f 2)
+    ruc cat-file -p "$tree"
+    [ "${#lines[@]}" = "3" ]
+    [ "$(echo "${lines[2]}" | cut -f 1)" = "040000 tree $(echo "${lines[2]}" | cut -d ' ' -f 3 | cut -f 1)" ]
+
+    blob=$(echo "${lines[0]}" | cut -d ' ' -f 3 | cut -f 1)
+    ruc cat-file -p "$blob"
+    [ "$output" = "a" ]
+}
+
+@test "cat-file -e tells whether an object exists through its exit status" {
+    ruc commit -m "First"
+
+    ruc cat-file -e HEAD
+    [ "$status" -eq 0 ]
+    [ "$output" = "" ]
+
+    ruc cat-file -e "not-an-id"
+    [ "$status" -eq 1 ]
+    [ "$output" = "" ]
+}
+
+@test "cat-file --batch-check and --batch read object names from stdin" {
+    ruc commit -m "First"
+    sha=$(cat .ruc/HEAD)
+
+    run sh -c "printf 'HEAD\nnot-an-id\n' | cargo -q run -- cat-file --batch-check"
+    [ "${lines[0]}" = "$sha commit $(cargo -q run -- cat-file -s "$sha")" ]
+    [ "${lines[1]}" = "not-an-id missing" ]
+
+    run sh -c "echo HEAD | cargo -q run -- cat-file --batch"
+    [ "${lines[0]}" = "$sha commit $(cargo -q run -- cat-file -s "$sha")" ]
+    [[ "${lines[1]}" == "tree "* ]]
+    [ "${lines[-1]}" = "First" ]
+}
+
+@test "hash-object prints IDs without writing unless asked to" {
+    ruc hash-object a.txt b.txt
+    [ "${#lines[@]}" = "2" ]
+    a="${lines[0]}"
+
+    ruc cat-file -e "$a"
+    [ "$status" -eq 1 ]
+
+    ruc hash-object -w a.txt
+    [ "$output" = "$a" ]
+
+    ruc cat-file -p "$a"
+    [ "$output" = "a" ]
+}
+
+@test "hash-object reads from the standard input" {
+    a=$(cargo -q run -- hash-object a.txt)
+
+    run sh -c "echo a | cargo -q run -- hash-object --stdin"
+    [ "$output" = "$a" ]
+}
+
+@test "hash-object validates objects of the given kind" {
+    ruc write-tree
+    tree="${lines[0]}"
+
+    run sh -c "cargo -q run -- cat-file -p $tree | cargo -q run -- hash-object -t tree --stdin"
+    [ "$status" -ne 0 ]
+
+    run sh -c "echo junk | cargo -q run -- hash-object -t commit --stdin"
+    [ "$status" -ne 0 ]
+    [ "$output" = "Error: commits must start with a tree" ]
+
+    printf 'tree %s\n\nHand-made' "$tree" > ../commit.txt
+    ruc hash-object -w -t commit ../commit.txt
+    [ "$status" -eq 0 ]
+
+    ruc cat-file -t "$output"
+    [ "$output" = "commit" ]
+
+    rm ../commit.txt
+}
+
+@test "hash-object rejects trees with entries pointing outside of them" {
+    ruc hash-object -w a.txt
+    raw="$(echo "${lines[0]}" | sed 's/../\\x&/g')"
+
+    for name in . .. .ruc .RUC; do
+        printf "100644 %s\0$raw" "$name" > ../tree.bin
+        ruc hash-object -w -t tree ../tree.bin
+        [ "$status" -ne 0 ]
+        [[ "$output" == *"'$name' is not a valid name for a tree entry"* ]]
+    done
+
+    printf "100644 fine\0$raw" > ../tree.bin
+    ruc hash-object -w -t tree ../tree.bin
+    [ "$status" -eq 0 ]
+
+    rm ../tree.bin
+}
+
+@test "trees with duplicate or unsorted entries are rejected" {
+    rm -rf /tmp/outside
+    mkdir -p /tmp/outside
+
+    # Writes the given bytes as a tree object, bypassing any validation, and
+    # prints its ID.
+    write_raw_tree() {
+        printf 'tree\0' > ../object.bin
+        cat >> ../object.bin
+        local id="$(sha1sum ../object.bin | cut -d ' ' -f 1)"
+        mv ../object.bin ".ruc/objects/$id"
+        echo "$id"
+    }
+    raw() {
+        echo "$1" | sed 's/../\\x&/g'
+    }
+
+    link="$(printf '/tmp/outside' | cargo -q run -- hash-object -w --stdin)"
+    file="$(echo "escaped" | cargo -q run -- hash-object -w --stdin)"
+    subtree="$(printf "100644 file\0$(raw "$file")" | write_raw_tree)"
+    tree="$(printf "120000 d\0$(raw "$link")40000 d\0$(raw "$subtree")" | write_raw_tree)"
+
+    ruc read-tree "$tree"
+    [ "$status" -ne 0 ]
+    [[ "$output" == *"duplicate entry 'd' in tree"* ]]
+    [ ! -e /tmp/outside/file ]
+
+    tree="$(printf "100644 b\0$(raw "$file")100644 a\0$(raw "$file")" | write_raw_tree)"
+    ruc read-tree "$tree"
+    [ "$status" -ne 0 ]
+    [[ "$output" == *"entry 'a' is out of order in tree"* ]]
+
+    rm -rf /tmp/outside
+}
+
+@test "checkouts do not write through symbolic links" {
+    rm -rf ../outside
+    mkdir -p ../outside
+    ruc commit -m "First"
+
+    rm -rf b
+    ln -s ../outside b
+    ruc restore b/b.txt
+    [ "$status" -ne 0 ]
+    [[ "$output" == *"'b' is a symbolic link, refusing to write through it"* ]]
+    [ ! -e ../outside/b.txt ]
+
+    rm -rf ../outside
+}
+
+@test "commits in the old format without an author nor a committer can be read" {
+    ruc write-tree
+    printf 'tree %s\n\nOld format' "${lines[0]}" > ../commit.txt
+    ruc hash-object -w -t commit ../commit.txt
+    sha="$output"
+    rm ../commit.txt
+
+    ruc log --from "$sha"
+    [ "$status" -eq 0 ]
+    [ "${lines[0]}" = "commit ${sha}" ]
+    [ "${lines[1]}" = "Old format" ]
+
+    ruc checkout "$sha"
+    [ "$status" -eq 0 ]
+    echo "c" > c.txt
+    ruc commit -m "New format"
+    ruc cat-file -p HEAD
+    [ "${lines[1]}" = "parent ${sha}" ]
+    [[ "${lines[2]}" == "author "* ]]
+}
+
+##
+# The following tests assert fixed IDs for the fixture from `setup`. These IDs
+# must never change across platforms or filesystems, since trees are sorted in
+# the canonical order from Git (note that "b.txt" goes before the "b"
+# directory). Commits also need a fixed author and committer.
+
+@test "write-tree produces a fixed ID for a known tree" {
+    ruc write-tree
+
+    [ "${lines[0]}" = "a719ac45274e568759ee18efdf89f4b6590560a4" ]
+
+    ruc cat-file "${lines[0]}"
+
+    [ "$(echo "${lines[2]}" | cut -f 2)" = "a.txt" ]
+    [ "$(echo "${lines[3]}" | cut -f 2)" = "b.txt" ]
+    [ "$(echo "${lines[4]}" | cut -f 2)" = "b" ]
+}
+
+@test "write-tree does not depend on the order in which files were created" {
+    rm -rf a.txt b.txt b
+    mkdir -p b
+    echo "b2" > b/b.txt
+    echo "b1" > b.txt
+    echo "a" > a.txt
+
+    ruc write-tree
+
+    [ "${lines[0]}" = "a719ac45274e568759ee18efdf89f4b6590560a4" ]
+}
+
+@test "commit produces a fixed ID for a known tree" {
+    fixed_identity
+    ruc commit -m "First"
+
+    [ "$(cat .ruc/HEAD)" = "e6c76819dd35a880c17e7f7f05a508dc836be4f4" ]
+}
+
+@test "write-tree and commit produce fixed SHA-256 IDs for a known tree" {
+    rm -rf .ruc
+    ruc init --object-format=sha256
+
+    ruc write-tree
+    [ "${lines[0]}" = "d6b06bece09d4851da73f578191c06d9294cb68be766820d7f99be606efd2d76" ]
+
+    fixed_identity
+    ruc commit -m "First"
+    [ "$(cat .ruc/HEAD)" = "40c6f51cdea6bdeab8e117b7832bec017327a0ce1a84ebc40422b3cd8b322a86" ]
+}
@@ -0,0 +1,147 @@
+#!/usr/bin/env bats
+
+load "helpers.bats"
+
+setup() {
+    ##
+    # On setup, we will initialize a repository under "origin" with a couple of
+    # commits on the "main" branch and an annotated tag.
+
+    fixed_identity
+
+    clean_cd "origin"
+    ruc init
+
+    echo "a" > a.txt
+    mkdir -p b
+    echo "b" > b/b.txt
+    ruc commit -m "First"
+    ruc branch main
+    ruc checkout main
+
+    rm -rf b
+    echo "c" > "c with spaces.txt"
+    ruc commit -m "Second"
+    ruc tag -a v1 -m "Version 1"
+
+    rm -rf ../other ../exported
+}
+
+@test "fast-export and fast-import keep the same history" {
+    ruc fast-export
+    [ "$status" -eq 0 ]
+    [[ "$output" == *"commit refs/heads/main"* ]]
+    [[ "$output" == *"tag v1"* ]]
+
+    main=$(cat .ruc/refs/heads/main)
+    v1=$(cat .ruc/refs/tags/v1)
+
+    mkdir ../other
+    cargo -q run -- fast-export > ../other/stream
+    cd ../other
+    ruc init
+
+    run sh -c "cargo -q run -- fast-import < stream"
+    [ "$status" -eq 0 ]
+    [ "${lines[0]}" = "Imported 6 objects and 2 references" ]
+
+    [ "$(cat .ruc/refs/heads/main)" = "$main" ]
+    [ "$(cat .ruc/refs/tags/v1)" = "$v1" ]
+
+    ruc checkout main
+    [ "$(cat "c with spaces.txt")" = "c" ]
+    [[ ! -e b/b.txt ]]
+}
+
+@test "fast-import supports inline data, deletions and renames" {
+    cat > ../stream <<'EOF'
+# A hand-written stream.
+commit refs/heads/imported
+mark :1
+committer C O Mitter <committer@example.com> 1112911993 -0700
+data <<END
+Imported
+END
+M 644 inline dir/file.txt
+data 6
+hello
+M 100755 inline run.sh
+data 5
+echo
+
+commit refs/heads/imported
+committer C O Mitter <committer@example.com> 1112911993 -0700
+data 7
+Renamed
+R dir other
+D run.sh
+EOF
+
+    run sh -c "cargo -q run -- fast-import < ../stream"
+    [ "$status" -eq 0 ]
+
+    ruc log -f imported
+    [ "${lines[1]}" = "Renamed" ]
+    [ "${lines[3]}" = "Imported" ]
+
+    ruc checkout imported
+    [ "$(cat other/file.txt)" = "hello" ]
+    [[ ! -e run.sh ]]
+    [[ ! -e dir ]]
+
+    rm ../stream
+}
+
+@test "fast-import rejects paths which cannot be checked out" {
+    for path in ../escaped.txt a//b /a a/ .ruc/config '"a/../../escaped.txt"'; do
+        cat > ../stream <<EOF
+commit refs/heads/imported
+committer C O Mitter <committer@example.com> 1112911993 -0700
+data 5
+Fine
+M 644 inline fine.txt
+data 5
+fine
+
+commit refs/heads/imported
+committer C O Mitter <committer@example.com> 1112911993 -0700
+data 4
+Bad
+M 644 inline $path
+data 4
+bad
+EOF
+
+        run sh -c "cargo -q run -- fast-import < ../stream"
+        [ "$status" -ne 0 ]
+        [[ "$output" == *"invalid path"* ]]
+        [[ ! -e .ruc/refs/heads/imported ]]
+    done
+
+    [[ ! -e ../escaped.txt ]]
+    rm ../stream
+}
+
+@test "fast-import rejects unknown commands" {
+    run sh -c "echo 'unknown' | cargo -q run -- fast-import"
+
+    [ "$status" -ne 0 ]
+    [[ "$output" == *"unsupported command 'unknown'"* ]]
+}
+
+@test "fast-export produces a stream that Git accepts" {
+    command -v git >/dev/null || skip "git is not installed"
+
+    mkdir ../other
+    git init -q ../other
+    cargo -q run -- fast-export | git -C ../other fast-import --quiet
+
+    run git -C ../other log --format=%s v1
+    [ "${lines[0]}" = "Second" ]
+    [ "${lines[1]}" = "First" ]
+
+    ruc export-git ../exported
+    [ "$(git -C ../other rev-parse main v1)" = "$(git -C ../exported rev-parse main v1)" ]
+
+    rm -rf ../exported
+}
@@ -0,0 +1,141 @@
+#!/usr/bin/env bats
+
+load "helpers.bats"
+
+setup() {
+    ##
+    # On setup, we will create a Git repository under "git" with a couple of
+    # commits on "main", a "feature" branch and an annotated tag. Most objects
+    # are moved into a pack, but the last commit is kept as loose objects.
+
+    command -v git >/dev/null || skip "git is not installed"
+
+    export GIT_AUTHOR_NAME="A U Thor"
+    export GIT_AUTHOR_EMAIL="author@example.com"
+    export GIT_COMMITTER_NAME="C O Mitter"
+    export GIT_COMMITTER_EMAIL="committer@example.com"
+
+    clean_cd "git"
+    git init -q -b main .
+
+    seq 1 1000 > numbers.txt
+    echo "echo hello" > run.sh
+    chmod +x run.sh
+    git add -A
+    git commit -q -m "First"
+
+    seq 1 1001 > numbers.txt
+    git commit -q -a -m "Second"
+    git tag -a v1 -m "Version 1"
+
+    git checkout -q -b feature
+    echo "f" > feature.txt
+    git add -A
+    git commit -q -m "Feature"
+    git checkout -q main
+
+    git gc -q
+
+    echo "c" > c.txt
+    git add -A
+    git commit -q -m "Third"
+
+    clean_cd "repo"
+    ruc init
+
+    rm -rf ../other
+}
+
+@test "import-git imports branches, tags and objects from packs" {
+    ruc import-git ../git
+
+    [ "$status" -eq 0 ]
+    [[ "$output" == *"refs/heads/feature"* ]]
+    [[ "$output" == *"refs/tags/v1"* ]]
+
+    ruc log
+    [ "${lines[1]}" = "Third" ]
+    [ "${lines[3]}" = "Second" ]
+    [ "${lines[5]}" = "First" ]
+
+    [ "$(seq 1 1001)" = "$(cat numbers.txt)" ]
+    [[ -x run.sh ]]
+    [ "$(cat .ruc/HEAD)" = "ref: refs/heads/main" ]
+
+    ruc log -f v1
+    [ "${lines[1]}" = "Second" ]
+
+    ruc checkout feature
+    [ "$(cat feature.txt)" = "f" ]
+}
+
+@test "export-git writes a repository that Git accepts" {
+    echo "a" > a.txt
+    ruc commit -m "First"
+    ruc branch main
+    ruc checkout main
+    echo "b" > b.txt
+    ruc commit -m "Second"
+    ruc tag -a v1 -m "Version 1"
+
+    ruc export-git ../other
+    [ "$status" -eq 0 ]
+
+    cd ../other
+    run git log --format=%s v1
+    [ "${lines[0]}" = "Second" ]
+    [ "${lines[1]}" = "First" ]
+
+    run git fsck --strict
+    [ "$status" -eq 0 ]
+
+    git checkout -q -f main
+    [ "$(cat b.txt)" = "b" ]
+}
+
+@test "import-git and export-git preserve Git object IDs" {
+    ruc import-git ../git
+    ruc export-git ../other
+
+    [ "$(git -C ../git rev-parse main feature v1)" = "$(git -C ../other rev-parse main feature v1)" ]
+}
+
+@test "export-git does not overwrite an existing Git repository" {
+    echo "a" > a.txt
+    ruc commit -m "First"
+
+    ruc export-git ../git
+
+    [ "$status" -ne 0 ]
+    [[ "$output" == *"already exists"* ]]
+}
+
+@test "import-git fails on something which is not a Git repository" {
+    ruc import-git ../nothing
+
+    [ "$status" -ne 0 ]
+    [[ "$output" == *"is not a Git repository"* ]]
+}
+
+@test "import-git rejects bad reference names" {
+    echo "$(git -C ../git rev-parse main) refs/heads/../../x" >> ../git/.git/packed-refs
+
+    ruc import-git ../git
+    [ "$status" -ne 0 ]
+    [[ "$output" == *"'refs/heads/../../x' is not a valid reference name"* ]]
+    [ ! -e .ruc/refs/heads/main ]
+    [ ! -e ../x ]
+}
+
+@test "import-git rejects trees with bad entry names" {
+    cd ../git
+    blob="$(echo "x" | git hash-object -w --stdin)"
+    tree="$(printf '100644 blob %s\t.ruc\n' "$blob" | git mktree)"
+    commit="$(git commit-tree -m "Bad" "$tree")"
+    git update-ref refs/heads/bad "$commit"
+    cd ../repo
+
+    ruc import-git ../git
+    [ "$status" -ne 0 ]
+    [[ "$output" == *"'.ruc' is not a valid name for a tree entry"* ]]
+}
@@ -7,7 +7,45 @@ ruc() {
 clean_cd() {
     DIR="$( cd "$( dirname "$BATS_TEST_FILENAME" )" >/dev/null 2>&1 && pwd )"
 
+    rm -rf "${DIR:?}/${1}"
     mkdir -p "${DIR}/${1}"
-    rm -rf "${DIR}/${1}/.ruc"
     cd "${DIR}/${1}" || exit 1
 }
+
+# Serves the repository from the current directory in the background on a
+# random port, which is then available through the PORT variable. Arguments are
+# passed to `ruc serve`. Call `kill_server` in order to stop it.
+ruc_serve() {
+    DIR="$( cd "$( dirname "$BATS_TEST_FILENAME" )" >/dev/null 2>&1 && pwd )"
+
+    cargo -q build
+    PORT=$((20000 + RANDOM % 20000))
+    "${DIR}/../target/debug/ruc" serve --port "$PORT" "$@" >/dev/null 2>&1 &
+    SERVER_PID=$!
+
+    for _ in $(seq 1 50); do
+        if (echo > "/dev/tcp/127.0.0.1/${PORT}") 2>/dev/null; then
+            return 0
+        fi
+        sleep 0.1
+    done
+
+    return 1
+}
+
+kill_server() {
+    if [ -n "${SERVER_PID}" ]; then
+        kill "${SERVER_PID}" || true
+        wait "${SERVER_PID}" 2>/dev/null || true
+    fi
+}
+
+# Sets a fixed author and committer, so commit IDs are always the same.
+fixed_identity() {
+    export RUC_AUTHOR_NAME="A U Thor"
+    export RUC_AUTHOR_EMAIL="author@example.com"
+    export RUC_AUTHOR_DATE="1112911993 -0700"
+    export RUC_COMMITTER_NAME="C O Mitter"
+    export RUC_COMMITTER_EMAIL="committer@example.com"
+    export RUC_COMMITTER_DATE="1112911993 -0700"
+}
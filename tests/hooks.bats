@@ -0,0 +1,180 @@
+#!/usr/bin/env bats
+
+load "helpers.bats"
+
+# Installs the script from the standard input as the hook with the given name.
+install_hook() {
+    mkdir -p .ruc/hooks
+    cat > ".ruc/hooks/$1"
+    chmod +x ".ruc/hooks/$1"
+}
+
+setup() {
+    ##
+    # On setup, we will initialize a repository under "repo" with a commit on
+    # the "main" branch. Hooks log into "../hooks.log", which is outside of the
+    # working directory.
+
+    clean_cd "repo"
+    ruc init
+
+    echo "a" > a.txt
+    ruc commit -m "First"
+    ruc branch main
+    ruc checkout main
+    rm -f ../hooks.log
+}
+
+teardown() {
+    rm -f ../hooks.log
+}
+
+@test "pre-commit can stop a commit unless --no-verify is given" {
+    install_hook pre-commit <<'HOOK'
+#!/bin/sh
+echo "pre-commit from $(pwd)" >> ../hooks.log
+exit 1
+HOOK
+    head="$(cat .ruc/refs/heads/main)"
+
+    mkdir -p dir
+    cd dir
+    echo "b" > b.txt
+    ruc commit -m "Second"
+    [ "$status" -ne 0 ]
+    [[ "$output" == *"the pre-commit hook failed"* ]]
+    cd ..
+    [ "$(cat .ruc/refs/heads/main)" = "$head" ]
+    [ "$(cat ../hooks.log)" = "pre-commit from $(pwd)" ]
+
+    ruc commit --no-verify -m "Second"
+    [ "$status" -eq 0 ]
+    [ "$(cat .ruc/refs/heads/main)" != "$head" ]
+    [ "$(wc -l < ../hooks.log)" -eq 1 ]
+}
+
+@test "commit-msg can change or reject the message" {
+    install_hook commit-msg <<'HOOK'
+#!/bin/sh
+grep -q "^WIP" "$1" && exit 1
+echo "" >> "$1"
+echo "Signed-off-by: A U Thor <author@example.com>" >> "$1"
+HOOK
+
+    echo "b" > b.txt
+    ruc commit -m "WIP"
+    [ "$status" -ne 0 ]
+    [[ "$output" == *"the commit-msg hook failed"* ]]
+    [ ! -f .ruc/COMMIT_EDITMSG ]
+
+    ruc commit -m "Second"
+    [ "$status" -eq 0 ]
+    ruc cat-file -p main
+    [ "${lines[-2]}" = "Second" ]
+    [ "${lines[-1]}" = "Signed-off-by: A U Thor <author@example.com>" ]
+
+    echo "c" > c.txt
+    ruc commit -n -m "WIP"
+    [ "$status" -eq 0 ]
+}
+
+@test "post-commit and post-checkout are notified" {
+    install_hook post-commit <<'HOOK'
+#!/bin/sh
+echo "post-commit $(cat .ruc/refs/heads/main)" >> ../hooks.log
+exit 1
+HOOK
+    install_hook post-checkout <<'HOOK'
+#!/bin/sh
+echo "post-checkout $1 $2 $3" >> ../hooks.log
+HOOK
+    first="$(cat .ruc/refs/heads/main)"
+
+    echo "b" > b.txt
+    ruc commit -m "Second"
+    [ "$status" -eq 0 ]
+    second="$(cat .ruc/refs/heads/main)"
+    [ "$(sed -n 1p ../hooks.log)" = "post-commit $second" ]
+
+    ruc checkout "$first"
+    [ "$status" -eq 0 ]
+    [ "$(sed -n 2p ../hooks.log)" = "post-checkout $second $first 1" ]
+}
+
+@test "commits replayed by cherry-pick and rebase only run post-commit" {
+    install_hook pre-commit <<'HOOK'
+#!/bin/sh
+echo "pre-commit" >> ../hooks.log
+exit 1
+HOOK
+    install_hook post-commit <<'HOOK'
+#!/bin/sh
+echo "post-commit $(cat .ruc/HEAD)" >> ../hooks.log
+HOOK
+    ruc branch topic
+    ruc checkout topic
+    echo "b" > b.txt
+    ruc commit --no-verify -m "Second"
+    ruc checkout main
+    rm -f ../hooks.log
+
+    ruc cherry-pick topic
+    [ "$status" -eq 0 ]
+    [ "$(cat ../hooks.log)" = "post-commit ref: refs/heads/main" ]
+
+    ruc checkout topic
+    echo "c" > c.txt
+    ruc commit --no-verify -m "Third"
+    rm -f ../hooks.log
+    ruc rebase main
+    [ "$status" -eq 0 ]
+    [[ "$(cat ../hooks.log)" == "post-commit "* ]]
+    [ "$(wc -l < ../hooks.log)" -eq 1 ]
+}
+
+@test "hooks which are not executable are ignored" {
+    install_hook pre-commit <<'HOOK'
+#!/bin/sh
+exit 1
+HOOK
+    chmod -x .ruc/hooks/pre-commit
+
+    echo "b" > b.txt
+    ruc commit -m "Second"
+    [ "$status" -eq 0 ]
+}
+
+@test "pre-push gets the references being pushed and can stop the push" {
+    cd ..
+    rm -rf clone
+    ruc clone repo clone
+    cd clone
+    install_hook pre-push <<'HOOK'
+#!/bin/sh
+echo "pre-push $1 $2" >> ../hooks.log
+cat >> ../hooks.log
+exit 1
+HOOK
+    old="$(cat .ruc/refs/heads/main)"
+
+    echo "b" > b.txt
+    ruc commit -m "Second"
+    new="$(cat .ruc/refs/heads/main)"
+    ruc push origin main:other
+    [ "$status" -ne 0 ]
+    [[ "$output" == *"the pre-push hook failed"* ]]
+    [ ! -f ../repo/.ruc/refs/heads/other ]
+    [[ "$(sed -n 1p ../hooks.log)" == "pre-push origin "*"repo" ]]
+    [ "$(sed -n 2p ../hooks.log)" = "refs/heads/main $new refs/heads/other 0000000000000000000000000000000000000000" ]
+
+    ruc push origin main
+    [ "$(sed -n 4p ../hooks.log)" = "refs/heads/main $new refs/heads/main $old" ]
+
+    ruc push --no-verify origin main
+    [ "$status" -eq 0 ]
+    [ "$(cat ../repo/.ruc/refs/heads/main)" = "$new" ]
+    [ "$(wc -l < ../hooks.log)" -eq 4 ]
+
+    cd ../repo
+    rm -rf ../clone
+}
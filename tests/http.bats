@@ -0,0 +1,149 @@
+#!/usr/bin/env bats
+
+load "helpers.bats"
+
+setup() {
+    ##
+    # On setup, we will initialize a repository under "origin" with a couple of
+    # commits on the "main" branch, and then serve it over HTTP.
+
+    clean_cd "origin"
+    ruc init
+
+    echo "a" > a.txt
+    ruc commit -m "First"
+    ruc branch main
+    ruc checkout main
+
+    echo "b" > b.txt
+    ruc commit -m "Second"
+
+    ruc_serve --allow-push
+
+    cd ..
+    rm -rf clone served 127.0.0.1
+}
+
+teardown() {
+    kill_server
+}
+
+@test "clone works over HTTP" {
+    ruc clone "http://127.0.0.1:${PORT}/" clone
+    cd clone
+
+    [ "$(cat .ruc/HEAD)" = "ref: refs/heads/main" ]
+    [ "$(cat .ruc/refs/heads/main)" = "$(cat ../origin/.ruc/refs/heads/main)" ]
+    [ "$(cat a.txt)" = "a" ]
+    [ "$(cat b.txt)" = "b" ]
+}
+
+@test "fetch works over HTTP" {
+    ruc clone "http://127.0.0.1:${PORT}/" clone
+
+    cd origin
+    echo "c" > c.txt
+    ruc commit -m "Third"
+
+    cd ../clone
+    ruc fetch
+
+    [ "$status" -eq 0 ]
+    [ "$(cat .ruc/refs/remotes/origin/main)" = "$(cat ../origin/.ruc/refs/heads/main)" ]
+}
+
+@test "push works over HTTP" {
+    ruc clone "http://127.0.0.1:${PORT}/" clone
+    cd clone
+
+    echo "c" > c.txt
+    ruc commit -m "Third"
+    ruc push
+
+    [ "$status" -eq 0 ]
+    [ "$(cat ../origin/.ruc/refs/heads/main)" = "$(cat .ruc/refs/heads/main)" ]
+
+    cd ../origin
+    ruc log --from main
+    [ "${lines[1]}" = "Third" ]
+}
+
+@test "push over HTTP rejects non-fast-forward updates unless forced" {
+    ruc clone "http://127.0.0.1:${PORT}/" clone
+
+    cd origin
+    echo "c" > c.txt
+    ruc commit -m "Third"
+    remote=$(cat .ruc/refs/heads/main)
+
+    cd ../clone
+    echo "d" > d.txt
+    ruc commit -m "Diverged"
+    ruc fetch
+    ruc push
+
+    [ "$status" -ne 0 ]
+    [ "$(cat ../origin/.ruc/refs/heads/main)" = "${remote}" ]
+
+    ruc push --force
+
+    [ "$status" -eq 0 ]
+    [ "$(cat ../origin/.ruc/refs/heads/main)" = "$(cat .ruc/refs/heads/main)" ]
+}
+
+@test "repositories can be served under any base path" {
+    ruc clone "http://127.0.0.1:${PORT}/path/to/served"
+    [ "$status" -eq 0 ]
+    [ "$(cat served/.ruc/refs/heads/main)" = "$(cat origin/.ruc/refs/heads/main)" ]
+
+    ruc clone "http://127.0.0.1:${PORT}/"
+    [ "$status" -eq 0 ]
+    [ "$(cat 127.0.0.1/.ruc/refs/heads/main)" = "$(cat origin/.ruc/refs/heads/main)" ]
+
+    rm -rf served 127.0.0.1
+}
+
+@test "push over HTTP needs to be allowed by the server" {
+    kill_server
+    cd origin
+    ruc_serve
+    cd ..
+
+    ruc clone "http://127.0.0.1:${PORT}/" clone
+    cd clone
+    echo "c" > c.txt
+    ruc commit -m "Third"
+    ruc push
+
+    [ "$status" -ne 0 ]
+    [[ "$output" == *"pushing is disabled"*"(403 Forbidden)"* ]]
+    [ "$(cat ../origin/.ruc/refs/heads/main)" != "$(cat .ruc/refs/heads/main)" ]
+}
+
+@test "the HTTP server rejects huge requests and keeps serving with slow clients" {
+    exec 3<>"/dev/tcp/127.0.0.1/${PORT}"
+    printf 'POST /upload-pack HTTP/1.1\r\nContent-Length: 99999999999\r\n\r\n' >&3
+    [ "$(head -n 1 <&3)" = "$(printf 'HTTP/1.1 413 Payload Too Large\r')" ]
+    exec 3<&-
+
+    # A client which does not send anything does not block the rest.
+    exec 3<>"/dev/tcp/127.0.0.1/${PORT}"
+    ruc clone "http://127.0.0.1:${PORT}/" clone
+    [ "$status" -eq 0 ]
+    exec 3<&-
+}
+
+@test "push over HTTP checks every update before applying any of them" {
+    main="$(cat origin/.ruc/refs/heads/main)"
+    missing="$(cd origin && echo "missing" | cargo -q run -- hash-object --stdin)"
+
+    printf 'update - %s refs/heads/ok\nupdate - %s refs/heads/bad\n\nRUCPACK 1 sha1 0\n' \
+        "$main" "$missing" > request.bin
+    run curl -s -o /dev/null -w '%{http_code}' --data-binary @request.bin \
+        "http://127.0.0.1:${PORT}/receive-pack"
+    rm request.bin
+
+    [ "$output" != "200" ]
+    [ ! -e origin/.ruc/refs/heads/ok ]
+    [ ! -e origin/.ruc/refs/heads/bad ]
+}
@@ -22,3 +22,27 @@ setup() {
     [[ -d ".ruc" ]]
     [[ -f ".ruc/objects/lala.txt" ]]
 }
+
+@test "init: records the object format in the configuration" {
+    ruc init
+
+    [ "$(grep objectformat .ruc/config | awk '{ print $3; }')" = "sha1" ]
+}
+
+@test "init: supports SHA-256 object IDs" {
+    ruc init --object-format=sha256
+    [ "$(grep objectformat .ruc/config | awk '{ print $3; }')" = "sha256" ]
+
+    echo "a" > a.txt
+    ruc commit -m "First"
+
+    [ "$(cat .ruc/HEAD | wc -c)" = "64" ]
+}
+
+@test "init: refuses to change the object format of a repository" {
+    ruc init
+    ruc init --object-format=sha256
+
+    [ "$status" -ne 0 ]
+    [ "$(grep objectformat .ruc/config | awk '{ print $3; }')" = "sha1" ]
+}
@@ -0,0 +1,99 @@
+#!/usr/bin/env bats
+
+load "helpers.bats"
+
+# Writes a commit for fast-import on the given branch, with the given mark and
+# message, and with the given extra commands (e.g. "from :1").
+stream_commit() {
+    printf 'commit refs/heads/%s\nmark :%s\n' "$1" "$2"
+    printf 'committer C O Mitter <committer@example.com> 1112911993 -0700\n'
+    printf 'data %s\n%s\n' "${#3}" "$3"
+    shift 3
+    for command in "$@"; do
+        printf '%s\n' "$command"
+    done
+    printf '\n'
+}
+
+setup() {
+    ##
+    # On setup, we will initialize a repository under "repo" with the
+    # following history, where "main" and "side" have been merged into each
+    # other (i.e. a criss-cross merge):
+    #
+    #       B---M1 main
+    #      / \ /
+    #     A   X
+    #     |\ / \
+    #     | C---M2 side
+    #      \
+    #       D other
+
+    clean_cd "repo"
+    ruc init
+
+    {
+        stream_commit main 1 "A" "M 644 inline a.txt" "data 2" "a"
+        stream_commit main 2 "B" "from :1" "M 644 inline b.txt" "data 2" "b"
+        stream_commit side 3 "C" "from :1" "M 644 inline c.txt" "data 2" "c"
+        stream_commit main 4 "M1" "from :2" "merge :3" "M 644 inline c.txt" "data 2" "c"
+        stream_commit side 5 "M2" "from :3" "merge :2" "M 644 inline b.txt" "data 2" "b"
+        stream_commit other 6 "D" "from :1" "M 644 inline d.txt" "data 2" "d"
+    } > ../stream
+    run sh -c "cargo -q run -- fast-import < ../stream"
+    rm ../stream
+}
+
+@test "merge-base finds the best common ancestor" {
+    a="$(cargo -q run -- rev-list other | tail -n 1)"
+
+    ruc merge-base main~1 side~1
+    [ "$status" -eq 0 ]
+    [ "$output" = "$a" ]
+
+    # A commit is the merge base with its descendants.
+    ruc merge-base main main~1
+    [ "$status" -eq 0 ]
+    [ "$output" = "$(cargo -q run -- rev-list main~1 | head -n 1)" ]
+}
+
+@test "merge-base --all shows all the best common ancestors" {
+    b="$(cargo -q run -- rev-list main~1 | head -n 1)"
+    c="$(cargo -q run -- rev-list side~1 | head -n 1)"
+
+    ruc merge-base main side
+    [ "${#lines[@]}" -eq 1 ]
+
+    ruc merge-base --all main side
+    [ "$status" -eq 0 ]
+    [ "${#lines[@]}" -eq 2 ]
+    [[ " ${lines[*]} " == *" $b "* ]]
+    [[ " ${lines[*]} " == *" $c "* ]]
+}
+
+@test "merge-base --octopus finds the common ancestors of all commits" {
+    a="$(cargo -q run -- rev-list other | tail -n 1)"
+
+    ruc merge-base --octopus main side other
+    [ "$status" -eq 0 ]
+    [ "$output" = "$a" ]
+
+    # Without --octopus, it's the merge base between "other" and a merge of
+    # the rest.
+    ruc merge-base other main side
+    [ "$status" -eq 0 ]
+    [ "$output" = "$a" ]
+}
+
+@test "merge-base --is-ancestor uses the exit status" {
+    ruc merge-base --is-ancestor main~1 main
+    [ "$status" -eq 0 ]
+    [ -z "$output" ]
+
+    ruc merge-base --is-ancestor main other
+    [ "$status" -eq 1 ]
+
+    ruc merge-base --is-ancestor main
+    [ "$status" -ne 0 ]
+    [[ "$output" == *"--is-ancestor takes exactly two commits"* ]]
+}
@@ -0,0 +1,70 @@
+// These tests run against in-memory stores, so they don't need a repository
+// on disk. Only functions taking the store explicitly can be used, since the
+// default store is the one from the current repository.
+
+use ruc::object::{self, Kind, MemoryStore, ObjectStore};
+use ruc::oid::{HashAlgorithm, Oid};
+use ruc::pack;
+use ruc::tree::{self, Files, Mode};
+
+#[test]
+fn objects_can_be_written_and_read_back() {
+    let store = MemoryStore::new(HashAlgorithm::Sha1);
+
+    // Objects are stored under the hash of their encoded bytes.
+    let id = object::hash_contents_into(&store, b"a\n", Kind::Blob).unwrap();
+    assert_eq!(HashAlgorithm::Sha1.hash(&store.read(&id).unwrap()), id);
+
+    assert!(store.contains(&id));
+    assert_eq!(store.ids().unwrap(), vec![id]);
+
+    let obj = object::get_from(&store, &id).unwrap();
+    assert_eq!(obj.kind, Kind::Blob);
+    assert_eq!(obj.contents, b"a\n");
+}
+
+#[test]
+fn missing_objects_are_reported() {
+    let store = MemoryStore::new(HashAlgorithm::Sha1);
+    let id = Oid::from_hex("78981922613b2afb6025042ff6bd878ac1994e85").unwrap();
+
+    assert!(!store.contains(&id));
+    assert!(store.read(&id).is_err());
+    assert!(store.ids().unwrap().is_empty());
+}
+
+#[test]
+fn trees_use_the_algorithm_from_the_store() {
+    let store = MemoryStore::new(HashAlgorithm::Sha256);
+    let blob = object::hash_contents_into(&store, b"b\n", Kind::Blob).unwrap();
+    assert_eq!(blob.algorithm(), HashAlgorithm::Sha256);
+
+    let mut files = Files::new();
+    files.insert("b.txt".to_owned(), (Mode::Regular, blob));
+    files.insert("dir/c.sh".to_owned(), (Mode::Executable, blob));
+
+    let id = tree::build(&store, &files).unwrap();
+    assert_eq!(object::get_from(&store, &id).unwrap().kind, Kind::Tree);
+    assert_eq!(tree::flatten(&store, &id).unwrap(), files);
+}
+
+#[test]
+fn packs_move_objects_between_stores() {
+    let (from, to) = (
+        MemoryStore::new(HashAlgorithm::Sha1),
+        MemoryStore::new(HashAlgorithm::Sha1),
+    );
+    let ids = [b"a\n".as_slice(), b"b\n"]
+        .iter()
+        .map(|contents| object::hash_contents_into(&from, contents, Kind::Blob).unwrap())
+        .collect::<Vec<_>>();
+
+    let data = pack::write(&from, &ids).unwrap();
+    assert_eq!(pack::read(&data, &to).unwrap(), ids);
+    for id in &ids {
+        assert_eq!(to.read(id).unwrap(), from.read(id).unwrap());
+    }
+
+    // Objects from a store using another algorithm are rejected.
+    assert!(pack::read(&data, &MemoryStore::new(HashAlgorithm::Sha256)).is_err());
+}
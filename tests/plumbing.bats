@@ -0,0 +1,120 @@
+#!/usr/bin/env bats
+
+load "helpers.bats"
+
+setup() {
+    ##
+    # On setup, we will initialize a repository under "repo" with two commits
+    # on the "main" branch and a tag on the first one.
+
+    clean_cd "repo"
+    ruc init
+
+    echo "a" > a.txt
+    mkdir -p b
+    echo "b" > b/b.txt
+    ruc commit -m "First"
+    ruc branch main
+    ruc checkout main
+    ruc tag -a v1 -m "Version 1"
+
+    echo "c" > c.txt
+    ruc commit -m "Second"
+}
+
+@test "ls-tree lists the entries of a tree" {
+    ruc ls-tree main
+
+    [ "${#lines[@]}" = "3" ]
+    [ "$(echo "${lines[0]}" | cut -f 2)" = "a.txt" ]
+    [ "$(echo "${lines[1]}" | cut -d ' ' -f 1,2)" = "040000 tree" ]
+    [ "$(echo "${lines[1]}" | cut -f 2)" = "b" ]
+}
+
+@test "ls-tree -r lists files with their full path" {
+    ruc ls-tree -r v1
+
+    [ "${#lines[@]}" = "2" ]
+    [ "$(echo "${lines[0]}" | cut -f 2)" = "a.txt" ]
+    [ "$(echo "${lines[1]}" | cut -f 2)" = "b/b.txt" ]
+    [ "$(echo "${lines[1]}" | cut -d ' ' -f 1,2)" = "100644 blob" ]
+}
+
+@test "ls-files lists the files from the working directory" {
+    ruc ls-files
+
+    [ "${lines[0]}" = "a.txt" ]
+    [ "${lines[1]}" = "b/b.txt" ]
+    [ "${lines[2]}" = "c.txt" ]
+}
+
+@test "show-ref lists references" {
+    ruc show-ref
+
+    [ "${lines[0]}" = "$(cat .ruc/refs/heads/main) refs/heads/main" ]
+    [ "${lines[1]}" = "$(cat .ruc/refs/tags/v1) refs/tags/v1" ]
+
+    ruc show-ref --tags
+    [ "${#lines[@]}" = "1" ]
+}
+
+@test "for-each-ref supports custom formats" {
+    ruc for-each-ref --format "%(refname:short) %(objecttype) %(subject)"
+
+    [ "${lines[0]}" = "main commit Second" ]
+    [ "${lines[1]}" = "v1 tag Version 1" ]
+
+    ruc for-each-ref --format "%(refname)" refs/tags
+    [ "$output" = "refs/tags/v1" ]
+
+    ruc for-each-ref --format "%(unknown)"
+    [ "$status" -ne 0 ]
+}
+
+@test "rev-list lists the commits from a range" {
+    ruc rev-list main
+    [ "${#lines[@]}" = "2" ]
+    [ "${lines[0]}" = "$(cat .ruc/refs/heads/main)" ]
+
+    ruc rev-list v1..main
+    [ "${#lines[@]}" = "1" ]
+    [ "${lines[0]}" = "$(cat .ruc/refs/heads/main)" ]
+
+    ruc rev-list main~1
+    [ "${#lines[@]}" = "1" ]
+}
+
+@test "rev-list --objects lists new trees and blobs" {
+    ruc rev-list --objects v1..main
+
+    # The commit, its root tree and the blob for "c.txt".
+    [ "${#lines[@]}" = "3" ]
+    [ "$(echo "${lines[2]}" | cut -d ' ' -f 2)" = "c.txt" ]
+}
+
+@test "symbolic-ref reads and updates symbolic references" {
+    ruc symbolic-ref HEAD
+    [ "$output" = "refs/heads/main" ]
+
+    ruc symbolic-ref --short HEAD
+    [ "$output" = "main" ]
+
+    ruc symbolic-ref HEAD refs/heads/other
+    [ "$(cat .ruc/HEAD)" = "ref: refs/heads/other" ]
+
+    ruc symbolic-ref HEAD main
+    [ "$status" -ne 0 ]
+
+    ruc symbolic-ref ../../escaped refs/heads/main
+    [ "$status" -ne 0 ]
+    [[ "$output" == *"'../../escaped' is neither HEAD nor a reference under refs/"* ]]
+    [[ ! -e ../../escaped ]]
+
+    ruc symbolic-ref refs/../../escaped refs/heads/main
+    [ "$status" -ne 0 ]
+    [[ "$output" == *"'refs/../../escaped' is not a valid reference name"* ]]
+
+    ruc symbolic-ref refs/remotes/origin/HEAD refs/remotes/origin/main
+    [ "$status" -eq 0 ]
+    [ "$(cat .ruc/refs/remotes/origin/HEAD)" = "ref: refs/remotes/origin/main" ]
+}
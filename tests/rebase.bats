@@ -0,0 +1,278 @@
+#!/usr/bin/env bats
+
+load "helpers.bats"
+
+setup() {
+    ##
+    # On setup, we will initialize a repository under "repo" where the "main"
+    # and "topic" branches have diverged from a common commit. HEAD is left on
+    # "topic".
+
+    clean_cd "repo"
+    ruc init
+
+    seq 1 10 > numbers.txt
+    ruc commit -m "Base"
+    ruc branch main
+    ruc branch topic
+
+    ruc checkout main
+    sed -i 's/^2$/two/' numbers.txt
+    ruc commit -m "Main"
+
+    ruc checkout topic
+    sed -i 's/^9$/nine/' numbers.txt
+    ruc commit -m "Topic 1"
+    echo "t" > t.txt
+    ruc commit -m "Topic 2"
+}
+
+@test "rebase replays commits on top of the upstream" {
+    ruc rebase main
+    [ "$status" -eq 0 ]
+    [[ "$output" == *"Successfully rebased and updated refs/heads/topic."* ]]
+
+    [ "$(cat .ruc/HEAD)" = "ref: refs/heads/topic" ]
+    [ "$(sed -n 2p numbers.txt)" = "two" ]
+    [ "$(sed -n 9p numbers.txt)" = "nine" ]
+    [ "$(cat t.txt)" = "t" ]
+
+    ruc log
+    [ "${lines[1]}" = "Topic 2" ]
+    [ "${lines[3]}" = "Topic 1" ]
+    [ "${lines[5]}" = "Main" ]
+
+    ruc rebase main
+    [ "$status" -eq 0 ]
+    [ "$output" = "Current branch topic is up to date." ]
+}
+
+@test "rebase --onto replays commits on top of another base" {
+    ruc rebase --onto main topic~1
+    [ "$status" -eq 0 ]
+
+    ruc log
+    [ "${lines[1]}" = "Topic 2" ]
+    [ "${lines[3]}" = "Main" ]
+    [ "$(sed -n 9p numbers.txt)" = "9" ]
+}
+
+@test "rebase refuses to run with local changes" {
+    echo "local" > local.txt
+
+    ruc rebase main
+    [ "$status" -ne 0 ]
+    [[ "$output" == *"local changes"* ]]
+}
+
+@test "rebase stops on conflicts and continues after resolving them" {
+    ruc checkout main
+    sed -i 's/^5$/main/' numbers.txt
+    ruc commit -m "Main 5"
+    ruc checkout topic
+
+    sed -i 's/^5$/topic/' numbers.txt
+    ruc commit -m "Topic 5"
+
+    ruc rebase main
+    [ "$status" -ne 0 ]
+    [[ "$output" == *"CONFLICT (content): Merge conflict in numbers.txt"* ]]
+    [[ "$output" == *"could not apply"*"Topic 5"* ]]
+    [ "$(sed -n 5p numbers.txt)" = "<<<<<<< HEAD" ]
+    [[ -d .ruc/rebase-merge ]]
+
+    sed -i '/^[<=>]/d; /^main$/d' numbers.txt
+    ruc rebase --continue
+    [ "$status" -eq 0 ]
+    [[ ! -d .ruc/rebase-merge ]]
+
+    ruc log
+    [ "${lines[1]}" = "Topic 5" ]
+    [ "${lines[7]}" = "Main 5" ]
+    [ "$(sed -n 5p numbers.txt)" = "topic" ]
+}
+
+@test "rebase --continue refuses to commit unresolved conflicts" {
+    ruc checkout main
+    sed -i 's/^5$/main/' numbers.txt
+    ruc commit -m "Main 5"
+    ruc checkout topic
+
+    sed -i 's/^5$/topic/' numbers.txt
+    ruc commit -m "Topic 5"
+
+    ruc rebase main
+    [ "$status" -ne 0 ]
+    head="$(cat .ruc/HEAD)"
+
+    ruc rebase --continue
+    [ "$status" -ne 0 ]
+    [[ "$output" == *"'numbers.txt' still has conflict markers"* ]]
+    [ "$(cat .ruc/HEAD)" = "$head" ]
+    [[ -d .ruc/rebase-merge ]]
+
+    sed -i '/^=/d; /^main$/d' numbers.txt
+    sed -i 's/^>>>>>>> .*$/tail/' numbers.txt
+    ruc rebase --continue
+    [ "$status" -ne 0 ]
+    [[ "$output" == *"'numbers.txt' still has conflict markers"* ]]
+
+    sed -i '/^<</d' numbers.txt
+    ruc rebase --continue
+    [ "$status" -eq 0 ]
+    [[ ! -d .ruc/rebase-merge ]]
+}
+
+@test "rebase keeps full commit IDs in its state" {
+    ruc checkout main
+    sed -i 's/^9$/main/' numbers.txt
+    ruc commit -m "Main 9"
+    ruc checkout topic
+    topic1="$(cargo -q run -- rev-list topic | sed -n 2p)"
+
+    ruc rebase main
+    [ "$status" -ne 0 ]
+    [[ "$(cat .ruc/rebase-merge/stopped)" == "pick $topic1 Topic 1" ]]
+
+    # A branch named after the abbreviated ID must not be picked instead.
+    ruc branch "${topic1:0:7}" main
+    sed -i '/^[<=>]/d; /^main$/d' numbers.txt
+    ruc rebase --continue
+    [ "$status" -eq 0 ]
+
+    ruc log
+    [ "${lines[1]}" = "Topic 2" ]
+    [ "${lines[3]}" = "Topic 1" ]
+    [ "${lines[5]}" = "Main 9" ]
+}
+
+@test "rebase --skip drops the conflicting commit and --abort goes back" {
+    ruc checkout main
+    sed -i 's/^9$/main/' numbers.txt
+    ruc commit -m "Main 9"
+    ruc checkout topic
+    before=$(cat .ruc/refs/heads/topic)
+
+    ruc rebase main
+    [ "$status" -ne 0 ]
+
+    ruc rebase --abort
+    [ "$status" -eq 0 ]
+    [ "$(cat .ruc/HEAD)" = "ref: refs/heads/topic" ]
+    [ "$(cat .ruc/refs/heads/topic)" = "$before" ]
+    [ "$(sed -n 9p numbers.txt)" = "nine" ]
+    [[ ! -d .ruc/rebase-merge ]]
+
+    ruc rebase main
+    ruc rebase --skip
+    [ "$status" -eq 0 ]
+
+    ruc log
+    [ "${lines[1]}" = "Topic 2" ]
+    [ "${lines[3]}" = "Main 9" ]
+    [ "$(sed -n 9p numbers.txt)" = "main" ]
+}
+
+# Sets an editor which runs the given sed script on the file being edited.
+sed_editor() {
+    cat > ../editor.sh <<SCRIPT
+#!/bin/sh
+sed -i '$1' "\$1"
+SCRIPT
+    chmod +x ../editor.sh
+    export EDITOR="$(cd .. && pwd)/editor.sh"
+}
+
+@test "rebase -i runs the todo list as edited" {
+    sed_editor 's/^pick \(.*\) Topic 1$/reword \1 Topic 1/; s/^Topic 1$/Reworded/; s/^pick \(.*\) Topic 2$/squash \1 Topic 2/'
+
+    ruc rebase -i main
+    [ "$status" -eq 0 ]
+
+    ruc log
+    [ "${lines[1]}" = "Reworded" ]
+    [ "${lines[2]}" = "Topic 2" ]
+    [ "${lines[4]}" = "Main" ]
+    [ "$(cat t.txt)" = "t" ]
+
+    rm ../editor.sh
+}
+
+@test "rebase -i saves the todo list with full commit IDs" {
+    sed_editor 's/^pick \(.*\) Topic 1$/edit \1 Topic 1/'
+    topic2="$(cargo -q run -- rev-list topic | sed -n 1p)"
+
+    ruc rebase -i main
+    [ "$status" -eq 0 ]
+    [ "$(cat .ruc/rebase-merge/todo)" = "pick $topic2 Topic 2" ]
+
+    ruc branch "${topic2:0:7}" main
+    ruc rebase --continue
+    [ "$status" -eq 0 ]
+
+    ruc log
+    [ "${lines[1]}" = "Topic 2" ]
+    [ "${lines[3]}" = "Topic 1" ]
+
+    rm ../editor.sh
+}
+
+@test "rebase -i supports drop, exec and edit" {
+    sed_editor 's/^pick \(.*\) Topic 1$/drop \1 Topic 1/; s/^pick \(.*\) Topic 2$/edit \1 Topic 2\nexec echo "exec" > exec.txt/'
+
+    ruc rebase -i main
+    [ "$status" -eq 0 ]
+    [[ "$output" == *"Stopped at"*"Topic 2"* ]]
+
+    echo "amended" > t.txt
+    ruc rebase --continue
+    [ "$status" -eq 0 ]
+    [ "$(cat exec.txt)" = "exec" ]
+
+    ruc log
+    [ "${lines[1]}" = "Topic 2" ]
+    [ "${lines[3]}" = "Main" ]
+    [ "$(sed -n 9p numbers.txt)" = "9" ]
+
+    ruc show HEAD:t.txt
+    [ "$output" = "amended" ]
+
+    rm ../editor.sh
+}
+
+@test "rebase --autosquash moves fixups in place" {
+    sed_editor ''
+
+    echo "fixed" > t.txt
+    ruc commit -m "fixup! Topic 2"
+    echo "u" > u.txt
+    ruc commit -m "Topic 3"
+    ruc commit -m "fixup! Topic 1"
+
+    ruc rebase -i --autosquash main
+    [ "$status" -eq 0 ]
+
+    ruc log
+    [ "${lines[1]}" = "Topic 3" ]
+    [ "${lines[3]}" = "Topic 2" ]
+    [ "${lines[5]}" = "Topic 1" ]
+    [ "${lines[7]}" = "Main" ]
+
+    ruc show HEAD~1:t.txt
+    [ "$output" = "fixed" ]
+
+    rm ../editor.sh
+}
+
+@test "rebase -i aborts when the todo list is empty" {
+    sed_editor '/^pick/d'
+    before=$(cat .ruc/refs/heads/topic)
+
+    ruc rebase -i main
+    [ "$status" -ne 0 ]
+    [[ "$output" == *"nothing to do"* ]]
+    [[ ! -d .ruc/rebase-merge ]]
+    [ "$(cat .ruc/refs/heads/topic)" = "$before" ]
+
+    rm ../editor.sh
+}
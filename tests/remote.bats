@@ -0,0 +1,152 @@
+#!/usr/bin/env bats
+
+load "helpers.bats"
+
+setup() {
+    ##
+    # On setup, we will initialize a repository under "origin" with a couple of
+    # commits on the "main" branch, which will act as the remote.
+
+    clean_cd "origin"
+    ruc init
+
+    echo "a" > a.txt
+    ruc commit -m "First"
+    ruc branch main
+    ruc checkout main
+
+    echo "b" > b.txt
+    ruc commit -m "Second"
+
+    cd ..
+    rm -rf clone
+}
+
+@test "clone copies the history and checks out the branch from the remote" {
+    ruc clone origin clone
+    cd clone
+
+    [ "$(cat .ruc/HEAD)" = "ref: refs/heads/main" ]
+    [ "$(cat .ruc/refs/heads/main)" = "$(cat ../origin/.ruc/refs/heads/main)" ]
+    [ "$(cat .ruc/refs/remotes/origin/main)" = "$(cat ../origin/.ruc/refs/heads/main)" ]
+    [ "$(cat a.txt)" = "a" ]
+    [ "$(cat b.txt)" = "b" ]
+
+    ruc log
+    [ "${lines[1]}" = "Second" ]
+    [ "${lines[3]}" = "First" ]
+}
+
+@test "fetch updates remote-tracking branches" {
+    ruc clone origin clone
+
+    cd origin
+    echo "c" > c.txt
+    ruc commit -m "Third"
+
+    cd ../clone
+    ruc fetch
+
+    [ "$status" -eq 0 ]
+    [ "$(cat .ruc/refs/remotes/origin/main)" = "$(cat ../origin/.ruc/refs/heads/main)" ]
+    [ "$(cat .ruc/refs/heads/main)" != "$(cat ../origin/.ruc/refs/heads/main)" ]
+
+    ruc log --from origin/main
+    [ "${lines[1]}" = "Third" ]
+}
+
+@test "push fast-forwards the remote branch" {
+    ruc clone origin clone
+    cd clone
+
+    echo "c" > c.txt
+    ruc commit -m "Third"
+    ruc push
+
+    [ "$status" -eq 0 ]
+    [ "$(cat .ruc/refs/heads/main)" = "$(cat ../origin/.ruc/refs/heads/main)" ]
+    [ "$(cat .ruc/refs/remotes/origin/main)" = "$(cat ../origin/.ruc/refs/heads/main)" ]
+
+    cd ../origin
+    ruc log --from main
+    [ "${lines[1]}" = "Third" ]
+}
+
+@test "push rejects non-fast-forward updates unless forced" {
+    ruc clone origin clone
+
+    cd origin
+    echo "c" > c.txt
+    ruc commit -m "Third"
+    remote=$(cat .ruc/refs/heads/main)
+
+    cd ../clone
+    echo "d" > d.txt
+    ruc commit -m "Diverged"
+    ruc push
+
+    [ "$status" -ne 0 ]
+    [ "$(cat ../origin/.ruc/refs/heads/main)" = "${remote}" ]
+
+    ruc push --force
+
+    [ "$status" -eq 0 ]
+    [ "$(cat ../origin/.ruc/refs/heads/main)" = "$(cat .ruc/refs/heads/main)" ]
+}
+
+@test "push can create new branches on the remote" {
+    ruc clone origin clone
+    cd clone
+
+    ruc push origin main:other
+
+    [ "$status" -eq 0 ]
+    [ "$(cat ../origin/.ruc/refs/heads/other)" = "$(cat .ruc/refs/heads/main)" ]
+}
+
+@test "remote adds and removes remotes" {
+    mkdir -p clone
+    cd clone
+    ruc init
+
+    ruc remote add upstream ../origin
+    ruc remote
+    [ "${lines[0]}" = "$(printf 'upstream\t%s' "$(cd ../origin && pwd -P)")" ]
+
+    mkdir -p sub
+    cd sub
+    ruc fetch upstream
+    cd ..
+    [ "$(cat .ruc/refs/remotes/upstream/main)" = "$(cat ../origin/.ruc/refs/heads/main)" ]
+
+    ruc remote remove upstream
+    ruc remote
+    [ "$output" = "" ]
+    [[ ! -f ".ruc/refs/remotes/upstream/main" ]]
+}
+
+@test "clone rejects a HEAD which does not point to a valid branch" {
+    echo "ref: refs/heads/../../../pwned" > origin/.ruc/HEAD
+
+    ruc clone origin clone
+    [ "$status" -ne 0 ]
+    [[ "$output" == *"bad HEAD advertised by the remote"* ]]
+    [[ ! -e "pwned" ]]
+    [[ ! -e "clone" ]]
+
+    echo "ref: refs/tags/v1" > origin/.ruc/HEAD
+    ruc clone origin clone
+    [ "$status" -ne 0 ]
+    [[ "$output" == *"HEAD on the remote points to 'refs/tags/v1', which is not a branch"* ]]
+}
+
+@test "fetch rejects invalid reference names from the remote" {
+    ruc clone origin clone
+    cp origin/.ruc/refs/heads/main origin/.ruc/refs/tags/.hidden
+
+    cd clone
+    ruc fetch
+    [ "$status" -ne 0 ]
+    [[ "$output" == *"'refs/tags/.hidden' is not a valid reference name"* ]]
+    [[ ! -e ".ruc/refs/tags/.hidden" ]]
+}
@@ -0,0 +1,86 @@
+#!/usr/bin/env bats
+
+load "helpers.bats"
+
+setup() {
+    ##
+    # On setup, we will initialize a repository under "repo" with two commits
+    # on the "main" branch.
+
+    clean_cd "repo"
+    ruc init
+
+    echo "a" > a.txt
+    mkdir -p b
+    echo "b" > b/b.txt
+    ruc commit -m "First"
+    ruc branch main
+    ruc checkout main
+
+    echo "a2" > a.txt
+    echo "c" > b/c.txt
+    ruc commit -m "Second"
+}
+
+@test "restore brings back a single file from HEAD" {
+    echo "changed" > a.txt
+    echo "changed" > b/b.txt
+
+    ruc restore a.txt
+    [ "$status" -eq 0 ]
+
+    [ "$(cat a.txt)" = "a2" ]
+    [ "$(cat b/b.txt)" = "changed" ]
+}
+
+@test "restore --source takes files from another revision" {
+    echo "untracked" > b/untracked.txt
+
+    ruc restore --source main~1 b
+    [ "$status" -eq 0 ]
+
+    [ "$(cat a.txt)" = "a2" ]
+    [ "$(cat b/b.txt)" = "b" ]
+    [[ ! -e b/c.txt ]]
+    [ "$(cat b/untracked.txt)" = "untracked" ]
+}
+
+@test "restore fails on unknown paths and on --staged" {
+    ruc restore unknown.txt
+    [ "$status" -ne 0 ]
+    [[ "$output" == *"pathspec 'unknown.txt' did not match"* ]]
+
+    ruc restore --staged a.txt
+    [ "$status" -ne 0 ]
+    [[ "$output" == *"no index"* ]]
+}
+
+@test "restore takes paths relative to the current directory" {
+    echo "changed" > a.txt
+    echo "changed" > b/b.txt
+    echo "changed" > b/c.txt
+    cd b
+
+    ruc restore b.txt
+    [ "$status" -eq 0 ]
+    [ "$(cat b.txt)" = "b" ]
+    [ "$(cat c.txt)" = "changed" ]
+    [ "$(cat ../a.txt)" = "changed" ]
+
+    ruc restore .
+    [ "$status" -eq 0 ]
+    [ "$(cat c.txt)" = "c" ]
+    [ "$(cat ../a.txt)" = "changed" ]
+
+    ruc restore ../a.txt
+    [ "$status" -eq 0 ]
+    [ "$(cat ../a.txt)" = "a2" ]
+
+    ruc restore a.txt
+    [ "$status" -ne 0 ]
+    [[ "$output" == *"pathspec 'a.txt' did not match any file known to ruc"* ]]
+
+    ruc restore ../../a.txt
+    [ "$status" -ne 0 ]
+    [[ "$output" == *"'../../a.txt' is outside of the repository"* ]]
+}
@@ -0,0 +1,85 @@
+#!/usr/bin/env bats
+
+load "helpers.bats"
+
+setup() {
+    ##
+    # On setup, we will initialize a repository under "repo" with two commits
+    # on the "main" branch and an annotated tag on the last one.
+
+    fixed_identity
+
+    clean_cd "repo"
+    ruc init
+
+    seq 1 10 > numbers.txt
+    mkdir -p b
+    echo "b" > b/b.txt
+    ruc commit -m "First"
+    ruc branch main
+    ruc checkout main
+
+    seq 1 10 | sed 's/^5$/five/' > numbers.txt
+    echo "c" > c.txt
+    ruc commit -m "Second"
+    ruc tag -a v1 -m "Version 1"
+}
+
+@test "show prints a commit along with its diff" {
+    ruc show main
+
+    [ "${lines[0]}" = "commit $(cat .ruc/refs/heads/main)" ]
+    [ "${lines[1]}" = "Author: A U Thor <author@example.com>" ]
+    [ "${lines[2]}" = "Date:   Thu Apr 7 15:13:13 2005 -0700" ]
+    [ "${lines[3]}" = "    Second" ]
+    [ "${lines[4]}" = "diff --git a/c.txt b/c.txt" ]
+    [ "${lines[5]}" = "new file mode 100644" ]
+    [[ "$output" == *"@@ -2,7 +2,7 @@"* ]]
+    [[ "$output" == *"-5"* ]]
+    [[ "$output" == *"+five"* ]]
+    [[ "$output" != *"b/b.txt"* ]]
+}
+
+@test "show diffs root commits against an empty tree" {
+    ruc show main~1
+
+    [[ "$output" == *"+++ b/b/b.txt"* ]]
+    [[ "$output" == *"@@ -0,0 +1,10 @@"* ]]
+}
+
+@test "show prints the annotation of tags" {
+    ruc show v1
+
+    [ "${lines[0]}" = "tag v1" ]
+    [ "${lines[3]}" = "Version 1" ]
+    [ "${lines[4]}" = "commit $(cat .ruc/refs/heads/main)" ]
+}
+
+@test "show prints trees and blobs, also through <rev>:<path>" {
+    ruc show main:
+    [ "${lines[0]}" = "tree main:" ]
+    [ "${lines[1]}" = "b/" ]
+    [ "${lines[2]}" = "c.txt" ]
+
+    ruc show main~1:b/b.txt
+    [ "$output" = "b" ]
+
+    ruc cat-file -p main:c.txt
+    [ "$output" = "c" ]
+
+    ruc show main:unknown
+    [ "$status" -ne 0 ]
+    [[ "$output" == *"path 'unknown' does not exist in 'main'"* ]]
+}
+
+@test "show rejects revisions with bad suffixes" {
+    for rev in 'HEAD~0é' 'HEAD~é' 'HEAD^x' 'HEAD~99999999999999999999999'; do
+        ruc show "$rev"
+        [ "$status" -ne 0 ]
+        [ "$output" = "Error: '$rev' is not a valid revision" ]
+    done
+
+    ruc show 'HEAD~5'
+    [ "$status" -ne 0 ]
+    [ "$output" = "Error: 'HEAD~5' does not have that many ancestors" ]
+}
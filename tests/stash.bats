@@ -0,0 +1,125 @@
+#!/usr/bin/env bats
+
+load "helpers.bats"
+
+setup() {
+    ##
+    # On setup, we will initialize a repository under "repo" with a single
+    # commit on the "main" branch.
+
+    clean_cd "repo"
+    ruc init
+
+    seq 1 5 > numbers.txt
+    echo "a" > a.txt
+    ruc commit -m "First"
+    ruc branch main
+    ruc checkout main
+}
+
+@test "stash saves local changes and brings back HEAD" {
+    ruc stash
+    [ "$output" = "No local changes to save" ]
+
+    echo "changed" > a.txt
+    echo "new" > new.txt
+    ruc stash push -m "Work in progress"
+    [ "$status" -eq 0 ]
+
+    [ "$(cat a.txt)" = "a" ]
+    [[ ! -e new.txt ]]
+    [[ -f .ruc/refs/stash ]]
+    [[ -f .ruc/logs/refs/stash ]]
+
+    ruc stash list
+    [ "$output" = "stash@{0}: On main: Work in progress" ]
+
+    ruc stash show
+    [[ "$output" == *"+changed"* ]]
+    [[ "$output" == *"new file mode 100644"* ]]
+}
+
+@test "stash push only stashes the given paths" {
+    echo "changed" > a.txt
+    sed -i 's/^2$/two/' numbers.txt
+
+    ruc stash push a.txt
+    [ "$status" -eq 0 ]
+
+    [ "$(cat a.txt)" = "a" ]
+    [ "$(sed -n 2p numbers.txt)" = "two" ]
+}
+
+@test "stash push takes paths relative to the current directory" {
+    mkdir -p sub
+    echo "sub" > sub/a.txt
+    ruc commit -m "Second"
+
+    echo "changed" > a.txt
+    echo "changed" > sub/a.txt
+    cd sub
+
+    ruc stash push a.txt
+    [ "$status" -eq 0 ]
+    [ "$(cat a.txt)" = "sub" ]
+    [ "$(cat ../a.txt)" = "changed" ]
+
+    ruc stash push ../a.txt
+    [ "$status" -eq 0 ]
+    [ "$(cat ../a.txt)" = "a" ]
+
+    ruc stash list
+    [ "${#lines[@]}" = "2" ]
+}
+
+@test "stash apply and pop bring back the changes" {
+    echo "changed" > a.txt
+    ruc stash
+    sed -i 's/^2$/two/' numbers.txt
+    ruc stash
+
+    ruc stash list
+    [[ "${lines[0]}" == "stash@{0}: WIP on main: "*" First" ]]
+    [ "${#lines[@]}" = "2" ]
+
+    sed -i 's/^5$/five/' numbers.txt
+    ruc stash apply stash@{1}
+    [ "$status" -eq 0 ]
+    [ "$(cat a.txt)" = "changed" ]
+    [ "$(sed -n 5p numbers.txt)" = "five" ]
+
+    ruc stash pop
+    [ "$status" -eq 0 ]
+    [ "$(sed -n 2p numbers.txt)" = "two" ]
+    [ "$(sed -n 5p numbers.txt)" = "five" ]
+
+    ruc stash list
+    [ "${#lines[@]}" = "1" ]
+}
+
+@test "stash pop keeps the stash on conflicts" {
+    sed -i 's/^2$/two/' numbers.txt
+    ruc stash
+    sed -i 's/^2$/dos/' numbers.txt
+
+    ruc stash pop
+    [ "$status" -ne 0 ]
+    [[ "$output" == *"CONFLICT (content): Merge conflict in numbers.txt"* ]]
+
+    ruc stash list
+    [ "${#lines[@]}" = "1" ]
+}
+
+@test "stash drop removes stashes" {
+    echo "changed" > a.txt
+    ruc stash
+
+    ruc stash drop
+    [ "$status" -eq 0 ]
+    [[ "$output" == "Dropped stash@{0} ("* ]]
+    [[ ! -e .ruc/refs/stash ]]
+
+    ruc stash drop
+    [ "$status" -ne 0 ]
+    [[ "$output" == *"no stash entries found"* ]]
+}
@@ -0,0 +1,78 @@
+#!/usr/bin/env bats
+
+load "helpers.bats"
+
+setup() {
+    ##
+    # On setup, we will initialize a repository under "repo" with two commits
+    # on "main", where "origin/main" points to the first one.
+
+    clean_cd "repo"
+    ruc init
+
+    echo "1" > file.txt
+    ruc commit -m "First"
+    ruc branch main
+    ruc checkout main
+    mkdir -p .ruc/refs/remotes/origin
+    cp .ruc/refs/heads/main .ruc/refs/remotes/origin/main
+    echo "2" > file.txt
+    ruc commit -m "Second"
+}
+
+@test "status shows the changes from the working directory" {
+    ruc status
+    [ "$status" -eq 0 ]
+    [ "${lines[0]}" = "On branch main" ]
+    [ "${lines[-1]}" = "nothing to commit, working tree clean" ]
+
+    echo "3" > file.txt
+    echo "new" > new.txt
+    ruc status
+    [ "$status" -eq 0 ]
+    [ "${lines[-3]}" = "Changes to be committed:" ]
+    [ "${lines[-2]}" = "$(printf '\tmodified:   file.txt')" ]
+    [ "${lines[-1]}" = "$(printf '\tnew file:   new.txt')" ]
+
+    rm file.txt new.txt
+    ruc status
+    [ "${lines[-1]}" = "$(printf '\tdeleted:    file.txt')" ]
+}
+
+@test "status shows how far the branch diverged from its upstream" {
+    ruc status
+    [ "${lines[1]}" = "Your branch is ahead of 'origin/main' by 1 commit." ]
+
+    cp .ruc/refs/heads/main .ruc/refs/remotes/origin/main
+    ruc status
+    [ "${lines[1]}" = "Your branch is up to date with 'origin/main'." ]
+
+    echo "3" > file.txt
+    ruc commit -m "Third"
+    echo "4" > file.txt
+    ruc commit -m "Fourth"
+    cp .ruc/refs/heads/main .ruc/refs/remotes/origin/main
+    ruc checkout main~2
+    ruc branch other
+    ruc checkout other
+    ruc status
+    [ "${lines[0]}" = "On branch other" ]
+    [ "${#lines[@]}" -eq 2 ]
+
+    cp .ruc/refs/heads/main .ruc/refs/remotes/origin/other
+    ruc status
+    [ "${lines[1]}" = "Your branch is behind 'origin/other' by 2 commits, and can be fast-forwarded." ]
+
+    echo "5" > file.txt
+    ruc commit -m "Fifth"
+    ruc status
+    [ "${lines[1]}" = "Your branch and 'origin/other' have diverged," ]
+    [ "${lines[2]}" = "and have 1 and 2 different commits each, respectively." ]
+}
+
+@test "status shows when HEAD is detached" {
+    ruc checkout main~1
+    ruc status
+    [[ "${lines[0]}" == "HEAD detached at "* ]]
+    [ "${#lines[@]}" -eq 2 ]
+}